@@ -0,0 +1,20 @@
+CREATE TABLE grade_scales
+(
+    id    SERIAL       NOT NULL PRIMARY KEY,
+    owner INTEGER      NOT NULL REFERENCES users ON DELETE CASCADE,
+    name  VARCHAR(255) NOT NULL,
+    kind  SMALLINT     NOT NULL DEFAULT 0
+);
+
+CREATE TABLE grade_bands
+(
+    id      SERIAL           NOT NULL PRIMARY KEY,
+    scale   INTEGER          NOT NULL REFERENCES grade_scales ON DELETE CASCADE,
+    minimum DOUBLE PRECISION NOT NULL,
+    label   VARCHAR(16)      NOT NULL,
+    points  DOUBLE PRECISION,
+    UNIQUE (scale, minimum)
+);
+
+ALTER TABLE subjects
+    ADD COLUMN grade_scale INTEGER REFERENCES grade_scales ON DELETE SET NULL;
@@ -0,0 +1,33 @@
+CREATE TABLE academic_years
+(
+    id         SERIAL       NOT NULL PRIMARY KEY,
+    owner      INTEGER      NOT NULL REFERENCES users ON DELETE CASCADE,
+    name       VARCHAR(255) NOT NULL,
+    start_date DATE         NOT NULL,
+    end_date   DATE         NOT NULL,
+    CHECK (start_date <= end_date)
+);
+
+CREATE TABLE terms
+(
+    id         SERIAL       NOT NULL PRIMARY KEY,
+    year       INTEGER      NOT NULL REFERENCES academic_years ON DELETE CASCADE,
+    name       VARCHAR(255) NOT NULL,
+    start_date DATE         NOT NULL,
+    end_date   DATE         NOT NULL,
+    CHECK (start_date <= end_date)
+);
+
+CREATE TABLE holidays
+(
+    id         SERIAL       NOT NULL PRIMARY KEY,
+    year       INTEGER      NOT NULL REFERENCES academic_years ON DELETE CASCADE,
+    name       VARCHAR(255) NOT NULL,
+    kind       SMALLINT     NOT NULL DEFAULT 0,
+    start_date DATE         NOT NULL,
+    end_date   DATE         NOT NULL,
+    CHECK (start_date <= end_date)
+);
+
+ALTER TABLE subjects
+    ADD COLUMN year INTEGER REFERENCES academic_years ON DELETE SET NULL;
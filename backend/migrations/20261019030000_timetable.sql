@@ -0,0 +1,13 @@
+ALTER TABLE academic_years
+    ADD COLUMN rotation_weeks SMALLINT NOT NULL DEFAULT 1 CHECK (rotation_weeks > 0);
+
+CREATE TABLE timetable_entries
+(
+    id      SERIAL       NOT NULL PRIMARY KEY,
+    subject INTEGER      NOT NULL REFERENCES subjects ON DELETE CASCADE,
+    weekday SMALLINT     NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    period  SMALLINT     NOT NULL,
+    week    SMALLINT CHECK (week > 0),
+    room    VARCHAR(16),
+    teacher VARCHAR(255)
+);
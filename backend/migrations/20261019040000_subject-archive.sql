@@ -0,0 +1,2 @@
+ALTER TABLE subjects
+    ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
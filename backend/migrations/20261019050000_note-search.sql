@@ -0,0 +1,40 @@
+-- Concatenates the text nodes of a TipTap document.
+CREATE FUNCTION tiptap_text(document JSONB) RETURNS TEXT
+    LANGUAGE sql
+    IMMUTABLE
+    AS $$
+        SELECT COALESCE(string_agg(text #>> '{}', ' '), '')
+        FROM jsonb_path_query(document, 'strict $.** ? (@.type == "text").text') AS text;
+    $$;
+
+CREATE TABLE note_search
+(
+    note     INTEGER  NOT NULL PRIMARY KEY REFERENCES notes ON DELETE CASCADE,
+    document TSVECTOR NOT NULL
+);
+
+CREATE INDEX note_search_document ON note_search USING GIN (document);
+
+CREATE FUNCTION notes_update_search() RETURNS TRIGGER
+    LANGUAGE plpgsql
+    AS $$
+        BEGIN
+            INSERT INTO note_search (note, document)
+            VALUES (
+                NEW.id,
+                setweight(to_tsvector('english', NEW.title), 'A') || setweight(to_tsvector('english', tiptap_text(NEW.content)), 'B')
+            )
+            ON CONFLICT (note) DO UPDATE SET document = EXCLUDED.document;
+            RETURN NEW;
+        END;
+    $$;
+
+CREATE TRIGGER notes_update_search
+    AFTER INSERT OR UPDATE OF title, content
+    ON notes
+    FOR EACH ROW
+    EXECUTE FUNCTION notes_update_search();
+
+INSERT INTO note_search (note, document)
+SELECT id, setweight(to_tsvector('english', title), 'A') || setweight(to_tsvector('english', tiptap_text(content)), 'B')
+FROM notes;
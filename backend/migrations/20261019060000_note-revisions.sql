@@ -0,0 +1,18 @@
+-- Counts the words in the text nodes of a TipTap document.
+CREATE FUNCTION tiptap_word_count(document JSONB) RETURNS INTEGER
+    LANGUAGE sql
+    IMMUTABLE
+    AS $$
+        SELECT COUNT(*)::INTEGER FROM regexp_matches(tiptap_text(document), '\S+', 'g');
+    $$;
+
+CREATE TABLE note_revisions
+(
+    id         SERIAL    NOT NULL PRIMARY KEY,
+    note       INTEGER   NOT NULL REFERENCES notes ON DELETE CASCADE,
+    content    JSONB     NOT NULL,
+    word_count INTEGER   NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX note_revisions_note ON note_revisions (note, created_at);
@@ -0,0 +1,62 @@
+CREATE TABLE user_groups
+(
+    id    SERIAL       NOT NULL PRIMARY KEY,
+    owner INTEGER      NOT NULL REFERENCES users ON DELETE CASCADE,
+    name  VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE user_group_members
+(
+    group_id INTEGER NOT NULL REFERENCES user_groups ON DELETE CASCADE,
+    user_id  INTEGER NOT NULL REFERENCES users ON DELETE CASCADE,
+    PRIMARY KEY (group_id, user_id)
+);
+
+CREATE INDEX user_group_members_user ON user_group_members (user_id);
+
+CREATE TABLE note_grants
+(
+    id       SERIAL   NOT NULL PRIMARY KEY,
+    note     INTEGER  NOT NULL REFERENCES notes ON DELETE CASCADE,
+    user_id  INTEGER REFERENCES users ON DELETE CASCADE,
+    group_id INTEGER REFERENCES user_groups ON DELETE CASCADE,
+    role     SMALLINT NOT NULL,
+    CHECK ((user_id IS NULL) <> (group_id IS NULL)),
+    UNIQUE (note, user_id),
+    UNIQUE (note, group_id)
+);
+
+CREATE INDEX note_grants_user ON note_grants (user_id);
+CREATE INDEX note_grants_group ON note_grants (group_id);
+
+ALTER TABLE notes
+    ADD COLUMN last_edited_by INTEGER REFERENCES users ON DELETE SET NULL;
+
+-- The IDs of the notes shared with a user, directly or through a group they belong to.
+CREATE FUNCTION shared_notes(for_user INTEGER) RETURNS SETOF INTEGER
+    LANGUAGE sql
+    STABLE
+    AS $$
+        SELECT note FROM note_grants WHERE user_id = for_user
+        UNION
+        SELECT note FROM note_grants
+        JOIN user_group_members ON user_group_members.group_id = note_grants.group_id
+        WHERE user_group_members.user_id = for_user;
+    $$;
+
+-- A user's role for a note: 0 for viewers, 1 for editors and 2 for owners, or NULL if they cannot access it.
+-- The note's owner is always an owner, and users granted several roles get the highest.
+CREATE FUNCTION note_role(for_note INTEGER, for_user INTEGER) RETURNS SMALLINT
+    LANGUAGE sql
+    STABLE
+    AS $$
+        SELECT CASE
+            WHEN EXISTS (SELECT 1 FROM notes WHERE id = for_note AND owner = for_user) THEN 2::SMALLINT
+            ELSE (
+                SELECT MAX(role) FROM note_grants
+                LEFT JOIN user_group_members ON user_group_members.group_id = note_grants.group_id
+                WHERE note_grants.note = for_note
+                    AND (note_grants.user_id = for_user OR user_group_members.user_id = for_user)
+            )
+        END;
+    $$;
@@ -0,0 +1,8 @@
+-- The Automerge documents of notes that have been edited collaboratively.
+-- Removed whenever a note's content is replaced without collaborating, so that the note's content is used instead.
+CREATE TABLE note_documents
+(
+    note     INTEGER   NOT NULL PRIMARY KEY REFERENCES notes ON DELETE CASCADE,
+    state    BYTEA     NOT NULL,
+    saved_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,44 @@
+-- The IDs of the notes linked to by the `noteLink` nodes of a TipTap document.
+CREATE FUNCTION tiptap_note_links(document JSONB) RETURNS SETOF INTEGER
+    LANGUAGE sql
+    IMMUTABLE
+    AS $$
+        SELECT DISTINCT id::NUMERIC::INTEGER
+        FROM jsonb_path_query(document, 'strict $.** ? (@.type == "noteLink").attrs.id') AS id
+        WHERE jsonb_typeof(id) = 'number' AND id::NUMERIC BETWEEN 1 AND 2147483647 AND id::NUMERIC = trunc(id::NUMERIC);
+    $$;
+
+CREATE TABLE note_links
+(
+    source INTEGER NOT NULL REFERENCES notes ON DELETE CASCADE,
+    target INTEGER NOT NULL REFERENCES notes ON DELETE CASCADE,
+    PRIMARY KEY (source, target)
+);
+
+CREATE INDEX note_links_target ON note_links (target);
+
+-- Keeps the links of a note up to date whenever its content is written.
+-- Links to the note itself or to notes that do not exist are left out.
+CREATE FUNCTION notes_update_links() RETURNS TRIGGER
+    LANGUAGE plpgsql
+    AS $$
+        BEGIN
+            DELETE FROM note_links WHERE source = NEW.id;
+            INSERT INTO note_links (source, target)
+            SELECT NEW.id, target
+            FROM tiptap_note_links(NEW.content) AS target
+            WHERE target <> NEW.id AND EXISTS (SELECT 1 FROM notes WHERE id = target);
+            RETURN NEW;
+        END;
+    $$;
+
+CREATE TRIGGER notes_update_links
+    AFTER INSERT OR UPDATE OF content
+    ON notes
+    FOR EACH ROW
+    EXECUTE FUNCTION notes_update_links();
+
+INSERT INTO note_links (source, target)
+SELECT notes.id, target
+FROM notes, tiptap_note_links(notes.content) AS target
+WHERE target <> notes.id AND EXISTS (SELECT 1 FROM notes AS linked WHERE linked.id = target);
@@ -0,0 +1,40 @@
+-- Assessments never had a primary key, which tags need to refer to them
+ALTER TABLE assessments
+    ADD PRIMARY KEY (id);
+
+CREATE TABLE tags
+(
+    id     SERIAL      NOT NULL PRIMARY KEY,
+    owner  INTEGER     NOT NULL REFERENCES users ON DELETE CASCADE,
+    name   VARCHAR(64) NOT NULL,
+    colour VARCHAR(7)  NOT NULL DEFAULT '#808080'
+);
+
+CREATE UNIQUE INDEX tags_owner_name ON tags (owner, LOWER(name));
+
+CREATE TABLE note_tags
+(
+    note INTEGER NOT NULL REFERENCES notes ON DELETE CASCADE,
+    tag  INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE,
+    PRIMARY KEY (note, tag)
+);
+
+CREATE INDEX note_tags_tag ON note_tags (tag);
+
+CREATE TABLE todo_tags
+(
+    todo INTEGER NOT NULL REFERENCES todos ON DELETE CASCADE,
+    tag  INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE,
+    PRIMARY KEY (todo, tag)
+);
+
+CREATE INDEX todo_tags_tag ON todo_tags (tag);
+
+CREATE TABLE assessment_tags
+(
+    assessment INTEGER NOT NULL REFERENCES assessments ON DELETE CASCADE,
+    tag        INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE,
+    PRIMARY KEY (assessment, tag)
+);
+
+CREATE INDEX assessment_tags_tag ON assessment_tags (tag);
@@ -0,0 +1,40 @@
+CREATE TABLE decks
+(
+    id      SERIAL       NOT NULL PRIMARY KEY,
+    owner   INTEGER      NOT NULL REFERENCES users ON DELETE CASCADE,
+    subject INTEGER      NOT NULL REFERENCES subjects ON DELETE CASCADE,
+    name    VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE cards
+(
+    id          SERIAL           NOT NULL PRIMARY KEY,
+    deck        INTEGER          NOT NULL REFERENCES decks ON DELETE CASCADE,
+    -- The note the card was extracted from, if any
+    note        INTEGER          REFERENCES notes ON DELETE SET NULL,
+    front       TEXT             NOT NULL,
+    back        TEXT             NOT NULL,
+    due         DATE             NOT NULL DEFAULT CURRENT_DATE,
+    -- Days until the card is next due after a successful review
+    interval    INTEGER          NOT NULL DEFAULT 0,
+    ease        DOUBLE PRECISION NOT NULL DEFAULT 2.5,
+    -- Successful reviews in a row
+    repetitions INTEGER          NOT NULL DEFAULT 0,
+    lapses      INTEGER          NOT NULL DEFAULT 0
+);
+
+CREATE INDEX cards_deck_due ON cards (deck, due);
+
+CREATE TABLE card_reviews
+(
+    id                SERIAL           NOT NULL PRIMARY KEY,
+    card              INTEGER          NOT NULL REFERENCES cards ON DELETE CASCADE,
+    reviewed_at       TIMESTAMP        NOT NULL DEFAULT NOW(),
+    grade             SMALLINT         NOT NULL,
+    previous_interval INTEGER          NOT NULL,
+    previous_ease     DOUBLE PRECISION NOT NULL,
+    interval          INTEGER          NOT NULL,
+    ease              DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX card_reviews_card ON card_reviews (card);
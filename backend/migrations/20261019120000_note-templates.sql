@@ -0,0 +1,14 @@
+CREATE TABLE note_templates
+(
+    id      SERIAL       NOT NULL PRIMARY KEY,
+    owner   INTEGER      NOT NULL REFERENCES users ON DELETE CASCADE,
+    name    VARCHAR(255) NOT NULL,
+    -- The title of notes created from the template, which can contain placeholders like the content
+    title   VARCHAR(255) NOT NULL DEFAULT '',
+    content JSONB        NOT NULL DEFAULT '{"type": "doc", "content": []}'
+);
+
+CREATE INDEX note_templates_owner ON note_templates (owner);
+
+ALTER TABLE subjects
+    ADD COLUMN default_template INTEGER REFERENCES note_templates ON DELETE SET NULL;
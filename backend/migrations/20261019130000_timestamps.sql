@@ -0,0 +1,63 @@
+-- Keeps created_at as it was and sets updated_at whenever a row actually changes
+CREATE FUNCTION update_timestamps() RETURNS TRIGGER
+    LANGUAGE plpgsql
+    AS $$
+        BEGIN
+            NEW.created_at = OLD.created_at;
+            NEW.updated_at = CURRENT_TIMESTAMP;
+            RETURN NEW;
+        END;
+    $$;
+
+ALTER TABLE notes
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+-- The oldest revision of an existing note is the closest thing to when it was created
+UPDATE notes
+SET created_at = revisions.created_at, updated_at = revisions.created_at
+FROM (SELECT note, MIN(created_at) AS created_at FROM note_revisions GROUP BY note) AS revisions
+WHERE revisions.note = notes.id;
+
+ALTER TABLE todos
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE subjects
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE assessments
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+CREATE TRIGGER notes_update_timestamps
+    BEFORE UPDATE
+    ON notes
+    FOR EACH ROW
+    WHEN (OLD.* IS DISTINCT FROM NEW.*)
+    EXECUTE FUNCTION update_timestamps();
+
+CREATE TRIGGER todos_update_timestamps
+    BEFORE UPDATE
+    ON todos
+    FOR EACH ROW
+    WHEN (OLD.* IS DISTINCT FROM NEW.*)
+    EXECUTE FUNCTION update_timestamps();
+
+CREATE TRIGGER subjects_update_timestamps
+    BEFORE UPDATE
+    ON subjects
+    FOR EACH ROW
+    WHEN (OLD.* IS DISTINCT FROM NEW.*)
+    EXECUTE FUNCTION update_timestamps();
+
+CREATE TRIGGER assessments_update_timestamps
+    BEFORE UPDATE
+    ON assessments
+    FOR EACH ROW
+    WHEN (OLD.* IS DISTINCT FROM NEW.*)
+    EXECUTE FUNCTION update_timestamps();
+
+CREATE INDEX notes_owner_updated_at ON notes (owner, updated_at);
+CREATE INDEX todos_owner_updated_at ON todos (owner, updated_at);
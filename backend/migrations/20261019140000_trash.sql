@@ -0,0 +1,60 @@
+-- Everything deleted at once, such as a to-do and its children, is one entry in the trash.
+-- Deleting an entry permanently deletes everything in it.
+CREATE TABLE trash
+(
+    id         SERIAL    NOT NULL PRIMARY KEY,
+    owner      INTEGER   NOT NULL REFERENCES users ON DELETE CASCADE,
+    deleted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX trash_owner ON trash (owner);
+CREATE INDEX trash_deleted_at ON trash (deleted_at);
+
+ALTER TABLE notes
+    ADD COLUMN trash INTEGER REFERENCES trash ON DELETE CASCADE;
+
+ALTER TABLE todos
+    ADD COLUMN trash INTEGER REFERENCES trash ON DELETE CASCADE;
+
+ALTER TABLE subjects
+    ADD COLUMN trash INTEGER REFERENCES trash ON DELETE CASCADE;
+
+CREATE INDEX notes_trash ON notes (trash);
+CREATE INDEX todos_trash ON todos (trash);
+CREATE INDEX subjects_trash ON subjects (trash);
+
+-- Subjects with assessments could not be deleted, but subjects in the trash must be purged eventually
+ALTER TABLE assessments
+    DROP CONSTRAINT assessments_subject_fkey,
+    ADD CONSTRAINT assessments_subject_fkey FOREIGN KEY (subject) REFERENCES subjects ON DELETE CASCADE;
+
+-- Notes in the trash are not shared with anyone.
+CREATE OR REPLACE FUNCTION shared_notes(for_user INTEGER) RETURNS SETOF INTEGER
+    LANGUAGE sql
+    STABLE
+    AS $$
+        SELECT note FROM note_grants WHERE user_id = for_user
+        UNION
+        SELECT note FROM note_grants
+        JOIN user_group_members ON user_group_members.group_id = note_grants.group_id
+        WHERE user_group_members.user_id = for_user
+        EXCEPT
+        SELECT id FROM notes WHERE trash IS NOT NULL;
+    $$;
+
+-- Nobody can access notes in the trash.
+CREATE OR REPLACE FUNCTION note_role(for_note INTEGER, for_user INTEGER) RETURNS SMALLINT
+    LANGUAGE sql
+    STABLE
+    AS $$
+        SELECT CASE
+            WHEN NOT EXISTS (SELECT 1 FROM notes WHERE id = for_note AND trash IS NULL) THEN NULL
+            WHEN EXISTS (SELECT 1 FROM notes WHERE id = for_note AND owner = for_user) THEN 2::SMALLINT
+            ELSE (
+                SELECT MAX(role) FROM note_grants
+                LEFT JOIN user_group_members ON user_group_members.group_id = note_grants.group_id
+                WHERE note_grants.note = for_note
+                    AND (note_grants.user_id = for_user OR user_group_members.user_id = for_user)
+            )
+        END;
+    $$;
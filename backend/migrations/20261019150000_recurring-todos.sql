@@ -0,0 +1,5 @@
+-- An iCalendar RRULE, such as FREQ=WEEKLY;BYDAY=MO. Completing an occurrence creates the next one.
+ALTER TABLE todos
+    ADD COLUMN recurrence      TEXT,
+    ADD COLUMN occurrence      INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN next_occurrence INTEGER REFERENCES todos ON DELETE SET NULL;
@@ -0,0 +1,35 @@
+-- The position of a to-do among the to-dos with the same parent, or among the top-level to-dos of its owner.
+-- Positions are spaced 1024 apart so to-dos can be moved between others without renumbering the whole list.
+ALTER TABLE todos
+    ADD COLUMN position INTEGER;
+
+UPDATE todos
+SET position = ordered.position
+FROM (SELECT id, ROW_NUMBER() OVER (PARTITION BY owner, parent ORDER BY title, id) * 1024 AS position FROM todos) AS ordered
+WHERE ordered.id = todos.id;
+
+ALTER TABLE todos
+    ALTER COLUMN position SET NOT NULL;
+
+CREATE INDEX todos_position ON todos (owner, parent, position);
+
+-- Puts new to-dos at the end of their list unless they are given a position
+CREATE FUNCTION append_todo() RETURNS TRIGGER
+    LANGUAGE plpgsql
+    AS $$
+        BEGIN
+            IF NEW.position IS NULL THEN
+                NEW.position = COALESCE((
+                    SELECT MAX(position) FROM todos
+                    WHERE owner = NEW.owner AND parent IS NOT DISTINCT FROM NEW.parent
+                ), 0) + 1024;
+            END IF;
+            RETURN NEW;
+        END;
+    $$;
+
+CREATE TRIGGER todos_append
+    BEFORE INSERT
+    ON todos
+    FOR EACH ROW
+    EXECUTE FUNCTION append_todo();
@@ -0,0 +1,19 @@
+-- Parents used to be set without any checks, so break any cycles by moving the to-do with the lowest ID in each to the top level
+WITH RECURSIVE ancestry AS (
+    SELECT id, parent AS ancestor, ARRAY[id] AS path FROM todos WHERE parent IS NOT NULL
+    UNION ALL
+    SELECT ancestry.id, todos.parent, ancestry.path || todos.id
+    FROM ancestry
+    JOIN todos ON todos.id = ancestry.ancestor
+    WHERE todos.parent IS NOT NULL AND NOT todos.id = ANY(ancestry.path)
+)
+UPDATE todos SET parent = NULL
+WHERE id IN (SELECT id FROM ancestry WHERE ancestor = id AND id = (SELECT MIN(member) FROM UNNEST(path) AS member));
+
+-- To-dos can only be children of to-dos with the same owner
+UPDATE todos SET parent = NULL
+FROM todos AS parents
+WHERE parents.id = todos.parent AND parents.owner <> todos.owner;
+
+ALTER TABLE todos
+    ADD CONSTRAINT todos_parent_not_self CHECK (parent <> id);
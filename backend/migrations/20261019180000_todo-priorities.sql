@@ -0,0 +1,7 @@
+-- Priorities are 0 (low), 1 (normal), 2 (high) and 3 (urgent)
+ALTER TABLE todos
+    ADD COLUMN priority          SMALLINT NOT NULL DEFAULT 1,
+    ADD COLUMN estimated_minutes INTEGER CHECK (estimated_minutes > 0),
+    ADD COLUMN assessment        INTEGER REFERENCES assessments ON DELETE SET NULL;
+
+CREATE INDEX todos_assessment ON todos (assessment);
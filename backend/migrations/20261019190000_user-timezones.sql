@@ -0,0 +1,3 @@
+-- An IANA time zone name, such as Australia/Sydney. Null uses the server's time zone.
+ALTER TABLE users
+    ADD COLUMN timezone VARCHAR(64);
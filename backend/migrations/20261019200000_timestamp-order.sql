@@ -0,0 +1,15 @@
+-- A sort key for `TimestampOrder`, to be sorted in ascending order: 0 puts the newest created first, 1 the oldest created,
+-- 2 the most recently changed and 3 the least recently changed.
+-- A NULL order gives a NULL key, so rows fall through to the query's own ordering.
+-- Written in SQL so that it is inlined into the queries that sort by it.
+CREATE FUNCTION timestamp_order(sort_order SMALLINT, created TIMESTAMP, updated TIMESTAMP) RETURNS DOUBLE PRECISION
+    LANGUAGE sql
+    IMMUTABLE
+    AS $$
+        SELECT CASE sort_order
+            WHEN 0 THEN -EXTRACT(EPOCH FROM created)
+            WHEN 1 THEN EXTRACT(EPOCH FROM created)
+            WHEN 2 THEN -EXTRACT(EPOCH FROM updated)
+            WHEN 3 THEN EXTRACT(EPOCH FROM updated)
+        END::DOUBLE PRECISION;
+    $$;
@@ -0,0 +1,3 @@
+-- Priorities are 0 (low), 1 (normal), 2 (high) and 3 (urgent)
+ALTER TABLE todos
+    ADD CONSTRAINT todos_priority_check CHECK (priority BETWEEN 0 AND 3);
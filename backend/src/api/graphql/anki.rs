@@ -0,0 +1,387 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as B64;
+use chrono::Utc;
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use sqlx::{ConnectOptions, Connection, Executor, query};
+use sqlx::sqlite::SqliteConnectOptions;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+use crate::api::graphql::tiptap::flashcards::{find, Flashcard};
+use crate::api::graphql::tiptap::{html, Node};
+
+/// The IDs of the note types in exported packages.
+/// They never change so that Anki recognises them when a package is imported again.
+const BASIC_MODEL: i64 = 1_697_500_000_001;
+const CLOZE_MODEL: i64 = 1_697_500_000_002;
+
+/// Separates the fields of an Anki note.
+const FIELD_SEPARATOR: &str = "\x1f";
+
+/// Distinguishes the collections of packages being built at the same time.
+static COLLECTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The schema of an Anki collection, as of version 11 which every Anki version can import.
+const SCHEMA: &str = "
+    CREATE TABLE col (id INTEGER PRIMARY KEY, crt INTEGER NOT NULL, mod INTEGER NOT NULL, scm INTEGER NOT NULL, ver INTEGER NOT NULL, dty INTEGER NOT NULL, usn INTEGER NOT NULL, ls INTEGER NOT NULL, conf TEXT NOT NULL, models TEXT NOT NULL, decks TEXT NOT NULL, dconf TEXT NOT NULL, tags TEXT NOT NULL);
+    CREATE TABLE notes (id INTEGER PRIMARY KEY, guid TEXT NOT NULL, mid INTEGER NOT NULL, mod INTEGER NOT NULL, usn INTEGER NOT NULL, tags TEXT NOT NULL, flds TEXT NOT NULL, sfld INTEGER NOT NULL, csum INTEGER NOT NULL, flags INTEGER NOT NULL, data TEXT NOT NULL);
+    CREATE TABLE cards (id INTEGER PRIMARY KEY, nid INTEGER NOT NULL, did INTEGER NOT NULL, ord INTEGER NOT NULL, mod INTEGER NOT NULL, usn INTEGER NOT NULL, type INTEGER NOT NULL, queue INTEGER NOT NULL, due INTEGER NOT NULL, ivl INTEGER NOT NULL, factor INTEGER NOT NULL, reps INTEGER NOT NULL, lapses INTEGER NOT NULL, left INTEGER NOT NULL, odue INTEGER NOT NULL, odid INTEGER NOT NULL, flags INTEGER NOT NULL, data TEXT NOT NULL);
+    CREATE TABLE revlog (id INTEGER PRIMARY KEY, cid INTEGER NOT NULL, usn INTEGER NOT NULL, ease INTEGER NOT NULL, ivl INTEGER NOT NULL, lastIvl INTEGER NOT NULL, factor INTEGER NOT NULL, time INTEGER NOT NULL, type INTEGER NOT NULL);
+    CREATE TABLE graves (usn INTEGER NOT NULL, oid INTEGER NOT NULL, type INTEGER NOT NULL);
+    CREATE INDEX ix_notes_usn ON notes (usn);
+    CREATE INDEX ix_cards_usn ON cards (usn);
+    CREATE INDEX ix_revlog_usn ON revlog (usn);
+    CREATE INDEX ix_cards_nid ON cards (nid);
+    CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+    CREATE INDEX ix_revlog_cid ON revlog (cid);
+    CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+/// An Anki note, which has one card if it is basic or one card for each deletion if it is a cloze.
+struct AnkiNote {
+    guid: String,
+    deck: i64,
+    model: i64,
+    fields: Vec<String>,
+    tags: Vec<String>,
+    cards: usize,
+}
+
+/// An Anki package being built, which can be imported into Anki as an `.apkg` file.
+#[derive(Default)]
+pub struct Package {
+    decks: Vec<(i64, String)>,
+    notes: Vec<AnkiNote>,
+    /// The names and contents of the images the notes show.
+    media: Vec<(String, Vec<u8>)>,
+}
+
+impl Package {
+    /// Adds a deck. Decks inside other decks have names like `Chemistry::Acids`.
+    /// The ID should stay the same between exports so that Anki updates the deck.
+    pub fn add_deck(&mut self, id: i64, name: &str) {
+        self.decks.push((id, name.replace(FIELD_SEPARATOR, " ")));
+    }
+
+    /// Adds a basic note with plain text on the front and back.
+    /// The GUID should stay the same between exports so that Anki updates the note instead of duplicating it.
+    pub fn add_basic(&mut self, deck: i64, guid: String, front: &str, back: &str, tags: &[String]) {
+        self.notes.push(AnkiNote {
+            guid,
+            deck,
+            model: BASIC_MODEL,
+            fields: vec![plain_html(front), plain_html(back)],
+            tags: tags.iter().map(|tag| tag.replace(char::is_whitespace, "_")).collect(),
+            cards: 1,
+        });
+    }
+
+    /// Adds a note for each question and answer, definition and block with cloze deletions of a document.
+    /// Questions and definitions become basic notes and blocks with deletions become cloze notes.
+    /// Their GUIDs are derived from `source` and their text, so they stay the same as long as the question, term or block is unchanged.
+    /// Repeats of the same text are numbered in order, so each still gets a note of its own.
+    pub fn add_document(&mut self, deck: i64, source: &str, title: &str, document: &Node, tags: &[String]) -> Result<()> {
+        let tags = tags.iter().map(|tag| tag.replace(char::is_whitespace, "_")).collect::<Vec<_>>();
+        let mut guids = HashSet::new();
+        for flashcard in find(document) {
+            let (key, model, fields, cards) = match flashcard {
+                Flashcard::Pair { front, back } => {
+                    let back = back.into_iter().map(|node| self.render(node)).collect::<Result<String>>()?;
+                    (front.text_content(), BASIC_MODEL, vec![self.render(front)?, back], 1)
+                }
+                Flashcard::Cloze { content, deletions } => {
+                    let mut text = Node::new("paragraph");
+                    for (index, node) in content.iter().enumerate() {
+                        if let Some(number) = deletions.iter().position(|deletion| deletion.start == index) {
+                            text.content.push(Node::text(&format!("{{{{c{}::", number + 1), Vec::new()));
+                        }
+                        let mut node = node.clone();
+                        node.marks.retain(|mark| mark.kind != "cloze");
+                        text.content.push(node);
+                        if deletions.iter().any(|deletion| deletion.end == index + 1) {
+                            text.content.push(Node::text("}}", Vec::new()));
+                        }
+                    }
+                    let key = content.iter().map(Node::text_content).collect::<String>();
+                    (key, CLOZE_MODEL, vec![self.render(&text)?, html::escape(title)], deletions.len())
+                }
+            };
+            if key.trim().is_empty() {
+                continue;
+            }
+            let mut note_guid = guid(&format!("{source}:{key}"));
+            let mut occurrence = 1;
+            while !guids.insert(note_guid.clone()) {
+                occurrence += 1;
+                note_guid = guid(&format!("{source}:{key}:{occurrence}"));
+            }
+            self.notes.push(AnkiNote {
+                guid: note_guid,
+                deck,
+                model,
+                fields,
+                tags: tags.clone(),
+                cards,
+            });
+        }
+        Ok(())
+    }
+
+    /// Renders the content of a node as HTML, moving images embedded in the document into the package's media.
+    fn render(&mut self, node: &Node) -> Result<String> {
+        let mut node = node.clone();
+        self.embed_media(&mut node);
+        Ok(html::render(&node)?)
+    }
+
+    fn embed_media(&mut self, node: &mut Node) {
+        if node.kind == "image" {
+            let media = node.str_attr("src")
+                .and_then(|src| src.strip_prefix("data:image/"))
+                .and_then(|data| data.split_once(";base64,"))
+                .and_then(|(kind, data)| Some((kind, B64.decode(data.trim()).ok()?)));
+            if let Some((kind, data)) = media {
+                let extension = match kind {
+                    "jpeg" => "jpg",
+                    "svg+xml" => "svg",
+                    kind => kind,
+                };
+                let name = format!("{}.{extension}", hex(&Sha1::digest(&data)));
+                if !self.media.iter().any(|(existing, _)| *existing == name) {
+                    self.media.push((name.clone(), data));
+                }
+                node.attrs.insert("src".to_string(), Value::String(name));
+            }
+        }
+        for child in &mut node.content {
+            self.embed_media(child);
+        }
+    }
+
+    /// Builds the `.apkg` file, which is a zip archive of an Anki collection database and the media it uses.
+    pub async fn build(self) -> Result<Vec<u8>> {
+        let path = env::temp_dir().join(format!("sapiprudentia-{}-{}.anki2", process::id(), COLLECTIONS.fetch_add(1, Ordering::Relaxed)));
+        let collection = match self.write_collection(&path).await {
+            Ok(()) => tokio::fs::read(&path).await.map_err(Into::into),
+            Err(error) => Err(error),
+        };
+        // The database is only needed until it is in the archive
+        let _ = tokio::fs::remove_file(&path).await;
+        let collection = collection?;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("collection.anki2", FileOptions::default())?;
+        zip.write_all(&collection)?;
+        // Media files are numbered, and the media file maps the numbers to the names the notes use
+        let mut names = HashMap::new();
+        for (index, (name, data)) in self.media.iter().enumerate() {
+            zip.start_file(index.to_string(), FileOptions::default())?;
+            zip.write_all(data)?;
+            names.insert(index.to_string(), name.clone());
+        }
+        zip.start_file("media", FileOptions::default())?;
+        zip.write_all(serde_json::to_string(&names)?.as_bytes())?;
+        Ok(zip.finish()?.into_inner())
+    }
+
+    async fn write_collection(&self, path: &std::path::Path) -> Result<()> {
+        let mut db = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .connect().await?;
+        db.execute(SCHEMA).await?;
+        let now = Utc::now();
+        let (seconds, milliseconds) = (now.timestamp(), now.timestamp_millis());
+        let mut decks = serde_json::Map::new();
+        decks.insert("1".to_string(), deck(1, "Default", seconds));
+        for (id, name) in &self.decks {
+            decks.insert(id.to_string(), deck(*id, name, seconds));
+        }
+        let models = json!({
+            BASIC_MODEL.to_string(): model(BASIC_MODEL, "Sapiprudentia Basic", 0, &["Front", "Back"], &[
+                ("Card 1", "{{Front}}", "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}"),
+            ], seconds),
+            CLOZE_MODEL.to_string(): model(CLOZE_MODEL, "Sapiprudentia Cloze", 1, &["Text", "Back Extra"], &[
+                ("Cloze", "{{cloze:Text}}", "{{cloze:Text}}<br>\n{{Back Extra}}"),
+            ], seconds),
+        });
+        let mut tx = db.begin().await?;
+        query(/* language=sqlite */ "INSERT INTO col VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}');")
+            .bind(seconds)
+            .bind(milliseconds)
+            .bind(milliseconds)
+            .bind(configuration().to_string())
+            .bind(models.to_string())
+            .bind(Value::Object(decks).to_string())
+            .bind(json!({ "1": deck_options(seconds) }).to_string())
+            .execute(&mut *tx).await?;
+        let mut card_id = milliseconds;
+        for (position, (id, note)) in (milliseconds..).zip(&self.notes).enumerate() {
+            let sort_field = strip_html(&note.fields[0]);
+            let tags = if note.tags.is_empty() { String::new() } else { format!(" {} ", note.tags.join(" ")) };
+            query(/* language=sqlite */ "INSERT INTO notes VALUES (?, ?, ?, ?, -1, ?, ?, ?, ?, 0, '');")
+                .bind(id)
+                .bind(&note.guid)
+                .bind(note.model)
+                .bind(seconds)
+                .bind(tags)
+                .bind(note.fields.join(FIELD_SEPARATOR))
+                .bind(&sort_field)
+                .bind(checksum(&sort_field))
+                .execute(&mut *tx).await?;
+            for ord in 0..note.cards {
+                // New cards are shown in the order they were added
+                query(/* language=sqlite */ "INSERT INTO cards VALUES (?, ?, ?, ?, ?, -1, 0, 0, ?, 0, 0, 0, 0, 0, 0, 0, 0, '');")
+                    .bind(card_id)
+                    .bind(id)
+                    .bind(note.deck)
+                    .bind(i64::try_from(ord)?)
+                    .bind(seconds)
+                    .bind(i64::try_from(position)?)
+                    .execute(&mut *tx).await?;
+                card_id += 1;
+            }
+        }
+        tx.commit().await?;
+        db.close().await?;
+        Ok(())
+    }
+}
+
+/// Derives a note GUID from a key that identifies the note's content.
+fn guid(key: &str) -> String {
+    format!("sp-{}", &hex(&Sha1::digest(key.as_bytes()))[..16])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Escapes plain text for an Anki field, keeping its line breaks.
+fn plain_html(text: &str) -> String {
+    html::escape(text).replace('\n', "<br>")
+}
+
+/// Gets the text of a field without HTML tags, which Anki sorts and finds duplicates by.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for char in html.chars() {
+        match char {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            char if !in_tag => text.push(char),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#39;", "'").replace("&amp;", "&")
+}
+
+/// Anki's checksum of a field, the first 32 bits of its SHA-1 hash.
+fn checksum(text: &str) -> i64 {
+    let hash = Sha1::digest(text.as_bytes());
+    i64::from(u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]))
+}
+
+fn configuration() -> Value {
+    json!({
+        "nextPos": 1,
+        "estTimes": true,
+        "activeDecks": [1],
+        "sortType": "noteFld",
+        "timeLim": 0,
+        "sortBackwards": false,
+        "addToCur": true,
+        "curDeck": 1,
+        "newBury": true,
+        "newSpread": 0,
+        "dueCounts": true,
+        "curModel": BASIC_MODEL.to_string(),
+        "collapseTime": 1200,
+    })
+}
+
+fn deck(id: i64, name: &str, modified: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": modified,
+        "usn": -1,
+        "lrnToday": [0, 0],
+        "revToday": [0, 0],
+        "newToday": [0, 0],
+        "timeToday": [0, 0],
+        "collapsed": false,
+        "browserCollapsed": false,
+        "desc": "",
+        "dyn": 0,
+        "conf": 1,
+        "extendNew": 0,
+        "extendRev": 0,
+    })
+}
+
+fn deck_options(modified: i64) -> Value {
+    json!({
+        "id": 1,
+        "name": "Default",
+        "mod": modified,
+        "usn": -1,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": { "delays": [1, 10], "ints": [1, 4, 0], "initialFactor": 2500, "order": 1, "perDay": 20, "bury": false },
+        "rev": { "perDay": 200, "ease4": 1.3, "ivlFct": 1, "maxIvl": 36500, "bury": false, "hardFactor": 1.2 },
+        "lapse": { "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 1 },
+    })
+}
+
+/// Describes a note type. Cloze note types have the kind 1 and a single template.
+fn model(id: i64, name: &str, kind: u8, fields: &[&str], templates: &[(&str, &str, &str)], modified: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "type": kind,
+        "mod": modified,
+        "usn": -1,
+        "sortf": 0,
+        "did": 1,
+        "flds": fields.iter().enumerate().map(|(ord, name)| json!({
+            "name": name,
+            "ord": ord,
+            "sticky": false,
+            "rtl": false,
+            "font": "Arial",
+            "size": 20,
+            "media": [],
+        })).collect::<Vec<_>>(),
+        "tmpls": templates.iter().enumerate().map(|(ord, (name, question, answer))| json!({
+            "name": name,
+            "ord": ord,
+            "qfmt": question,
+            "afmt": answer,
+            "bqfmt": "",
+            "bafmt": "",
+            "did": null,
+        })).collect::<Vec<_>>(),
+        "css": ".card { font-family: arial; font-size: 20px; text-align: center; color: black; background-color: white; }\n.cloze { font-weight: bold; color: blue; }",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "latexsvg": false,
+        "req": [[0, "any", [0]]],
+        "tags": [],
+        "vers": [],
+    })
+}
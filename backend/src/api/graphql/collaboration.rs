@@ -0,0 +1,521 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use async_graphql::{ComplexObject, Context, Result, SimpleObject, Union};
+use automerge::{AutoCommit, AutomergeError, AutoSerde, Change, ChangeHash, ObjId, ObjType, Prop, ROOT, ScalarValue};
+use automerge::transaction::Transactable;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rocket::error;
+use rocket::http::Status;
+use serde_json::Value;
+use sqlx::{PgPool, query};
+use sqlx::postgres::PgListener;
+use tokio::sync::{broadcast, Mutex, OwnedMutexGuard};
+use crate::api::graphql::mutation::note::save_revision;
+use crate::api::graphql::query::user::UserProfile;
+use crate::api::graphql::tiptap::schema::repair;
+
+/// How often collaborative edits are written back to their notes.
+const COMPACT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to wait before listening for rewritten notes again after the connection failed.
+const LISTEN_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The number of events a subscriber can fall behind before it is sent the whole document again.
+const EVENT_BUFFER: usize = 256;
+
+/// The channel notes are announced on when their content is rewritten outside of the server, such as by `repair-notes`.
+pub const REWRITTEN_CHANNEL: &str = "note_rewritten";
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Collaborator {
+    /// The ID the client chose for itself when it subscribed.
+    pub client_id: String,
+
+    #[graphql(skip)]
+    pub user: i32,
+
+    /// The client's selection, in whatever form its editor uses. Null if it has not shared one.
+    pub selection: Option<Value>,
+}
+
+#[ComplexObject]
+impl Collaborator {
+    /// The user the client belongs to.
+    async fn user(&self, ctx: &Context<'_>) -> Result<UserProfile> {
+        UserProfile::get(ctx.data::<PgPool>()?, self.user).await
+    }
+}
+
+/// The whole collaborative document of a note.
+/// Sent when a client subscribes, when it falls too far behind, and when the note's content is replaced without collaborating.
+#[derive(SimpleObject, Clone)]
+pub struct NoteSync {
+    /// The saved Automerge document, base64-encoded.
+    pub document: String,
+
+    /// The hashes of the document's latest changes.
+    pub heads: Vec<String>,
+
+    /// The clients viewing or editing the note.
+    pub collaborators: Vec<Collaborator>,
+}
+
+/// Changes another client made to a note's collaborative document.
+#[derive(SimpleObject, Clone)]
+pub struct NoteChanges {
+    /// The ID of the client that made the changes.
+    pub client_id: String,
+
+    /// The Automerge changes, base64-encoded.
+    pub changes: Vec<String>,
+
+    /// The hashes of the document's latest changes after applying these.
+    pub heads: Vec<String>,
+}
+
+/// The clients viewing or editing a note, sent whenever one joins, leaves or moves its selection.
+#[derive(SimpleObject, Clone)]
+pub struct NotePresence {
+    /// The clients viewing or editing the note.
+    pub collaborators: Vec<Collaborator>,
+}
+
+#[derive(Union, Clone)]
+pub enum NoteEvent {
+    Sync(NoteSync),
+    Changes(NoteChanges),
+    Presence(NotePresence),
+}
+
+/// The result of merging a client's changes into a note's collaborative document.
+#[derive(SimpleObject)]
+pub struct NoteMerge {
+    /// The changes the client does not have yet, base64-encoded.
+    pub changes: Vec<String>,
+
+    /// The hashes of the document's latest changes.
+    pub heads: Vec<String>,
+}
+
+/// A note that is being edited collaboratively.
+struct Session {
+    document: AutoCommit,
+    events: broadcast::Sender<NoteEvent>,
+    collaborators: Vec<Collaborator>,
+    /// The user who made the latest changes that have not been written to the note yet.
+    editor: Option<i32>,
+}
+
+impl Session {
+    fn sync(&mut self) -> NoteSync {
+        NoteSync {
+            document: BASE64.encode(self.document.save()),
+            heads: encode_heads(&self.document.get_heads()),
+            collaborators: self.collaborators.clone(),
+        }
+    }
+
+    fn broadcast_presence(&self) {
+        // Sending only fails if nobody is subscribed
+        let _ = self.events.send(NoteEvent::Presence(NotePresence {
+            collaborators: self.collaborators.clone(),
+        }));
+    }
+
+    /// Gets the collaborator that subscribed with the client ID.
+    /// Fails with 404 Not Found if the client is not subscribed, or 403 Forbidden if it was subscribed by another user.
+    fn collaborator(&mut self, client_id: &str, user: i32) -> Result<&mut Collaborator> {
+        let collaborator = self.collaborators.iter_mut()
+            .find(|collaborator| collaborator.client_id == client_id)
+            .ok_or(Status::NotFound)?;
+        if collaborator.user != user {
+            return Err(Status::Forbidden.into());
+        }
+        Ok(collaborator)
+    }
+
+    /// Writes the document to its note if it has changes that have not been written yet, keeping the previous content as a revision.
+    /// The document is repaired first if the merged changes made it invalid for the editor.
+    /// Returns `false` if the note no longer exists.
+    async fn save(&mut self, pool: &PgPool, note: i32) -> Result<bool> {
+        let Some(editor) = self.editor else {
+            return Ok(true);
+        };
+        let content = repair(&serde_json::to_value(AutoSerde::from(&self.document))?).content;
+        let mut tx = pool.begin().await?;
+        save_revision(&mut tx, note, Some(&content), true).await?;
+        let updated = query!(/* language=postgresql */ "UPDATE notes SET content = $2, last_edited_by = $3 WHERE id = $1 RETURNING id;", note, content, editor)
+            .fetch_optional(&mut *tx).await?;
+        if updated.is_none() {
+            return Ok(false);
+        }
+        query!(/* language=postgresql */ "
+            INSERT INTO note_documents (note, state) VALUES ($1, $2)
+            ON CONFLICT (note) DO UPDATE SET state = excluded.state, saved_at = NOW();
+            ", note, self.document.save())
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        self.editor = None;
+        Ok(true)
+    }
+}
+
+/// Exclusive access to a note's session, or to where it would be loaded if nobody is editing the note.
+/// Held while the note's content is replaced, so that collaborative edits cannot be saved over the new content.
+pub struct NoteLock {
+    note: i32,
+    session: OwnedMutexGuard<Option<Session>>,
+}
+
+impl NoteLock {
+    /// Gets the note's session. Fails with 404 Not Found if it is not loaded.
+    fn session(&mut self) -> Result<&mut Session> {
+        self.session.as_mut().ok_or(Status::NotFound.into())
+    }
+}
+
+fn encode_heads(heads: &[ChangeHash]) -> Vec<String> {
+    heads.iter().map(ToString::to_string).collect()
+}
+
+fn encode_changes(changes: &[&Change]) -> Vec<String> {
+    changes.iter().map(|change| BASE64.encode(change.raw_bytes())).collect()
+}
+
+/// Converts a JSON value to an Automerge scalar.
+fn scalar(value: &Value) -> ScalarValue {
+    match value {
+        Value::Bool(value) => ScalarValue::Boolean(*value),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(number), _) => ScalarValue::Int(number),
+            (None, Some(number)) => ScalarValue::Uint(number),
+            _ => ScalarValue::F64(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(value) => ScalarValue::Str(value.as_str().into()),
+        _ => ScalarValue::Null,
+    }
+}
+
+/// Writes a JSON value into an Automerge object.
+/// The `text` of text nodes is stored as Automerge text so that concurrent typing in the same node merges character by character.
+fn write(document: &mut AutoCommit, parent: &ObjId, prop: Prop, value: &Value) -> Result<(), AutomergeError> {
+    let kind = match value {
+        Value::Object(_) => ObjType::Map,
+        Value::Array(_) => ObjType::List,
+        Value::String(_) if prop == Prop::Map("text".into()) => ObjType::Text,
+        _ => return match prop {
+            Prop::Map(key) => document.put(parent, key, scalar(value)),
+            Prop::Seq(index) => document.insert(parent, index, scalar(value)),
+        },
+    };
+    let object = match prop {
+        Prop::Map(key) => document.put_object(parent, key, kind)?,
+        Prop::Seq(index) => document.insert_object(parent, index, kind)?,
+    };
+    match value {
+        Value::Object(map) => for (key, value) in map {
+            write(document, &object, Prop::Map(key.clone()), value)?;
+        },
+        Value::Array(items) => for (index, value) in items.iter().enumerate() {
+            write(document, &object, Prop::Seq(index), value)?;
+        },
+        Value::String(text) => document.splice_text(&object, 0, 0, text)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Converts a `TipTap` document to an Automerge document with the same structure.
+fn build(content: &Value) -> Result<AutoCommit, AutomergeError> {
+    let mut document = AutoCommit::new();
+    if let Value::Object(map) = content {
+        for (key, value) in map {
+            write(&mut document, &ROOT, Prop::Map(key.clone()), value)?;
+        }
+    }
+    document.commit();
+    Ok(document)
+}
+
+/// Keeps the collaborative documents of the notes being edited, relaying changes and presence between their clients.
+/// Documents are kept in Automerge form, so changes made concurrently or offline merge without conflicts.
+/// Merged documents are periodically written back to their notes, which are left unchanged until then.
+/// Each note's session has its own lock, so a note being loaded or saved does not hold up the others.
+#[derive(Default)]
+pub struct Collaboration {
+    sessions: Mutex<HashMap<i32, Arc<Mutex<Option<Session>>>>>,
+}
+
+impl Collaboration {
+    /// Locks a note's session, whether or not it is loaded.
+    pub async fn lock(&self, note: i32) -> NoteLock {
+        loop {
+            let slot = Arc::clone(self.sessions.lock().await.entry(note).or_default());
+            if let Some(lock) = self.lock_slot(note, slot).await {
+                return lock;
+            }
+        }
+    }
+
+    /// Locks a note's session if it is loaded.
+    async fn lock_loaded(&self, note: i32) -> Option<NoteLock> {
+        let slot = self.sessions.lock().await.get(&note).map(Arc::clone)?;
+        let lock = self.lock_slot(note, slot).await?;
+        if lock.session.is_none() {
+            self.unload(lock).await;
+            return None;
+        }
+        Some(lock)
+    }
+
+    /// Locks the session in `slot`. Returns `None` if it was unloaded while waiting for the lock.
+    async fn lock_slot(&self, note: i32, slot: Arc<Mutex<Option<Session>>>) -> Option<NoteLock> {
+        let session = slot.lock_owned().await;
+        let current = self.sessions.lock().await.get(&note)
+            .is_some_and(|current| Arc::ptr_eq(current, OwnedMutexGuard::mutex(&session)));
+        current.then_some(NoteLock { note, session })
+    }
+
+    /// Removes a note's session, ending its subscribers' event streams.
+    async fn unload(&self, lock: NoteLock) {
+        let mut sessions = self.sessions.lock().await;
+        if sessions.get(&lock.note).is_some_and(|slot| Arc::ptr_eq(slot, OwnedMutexGuard::mutex(&lock.session))) {
+            sessions.remove(&lock.note);
+        }
+    }
+
+    /// Locks the collaborative document of a note, loading it from where it was last saved or from the note's content.
+    /// Fails with 404 Not Found if the note does not exist or is in the trash.
+    async fn load(&self, pool: &PgPool, note: i32) -> Result<NoteLock> {
+        let mut lock = self.lock(note).await;
+        if lock.session.is_none() {
+            match Self::fetch(pool, note).await {
+                Ok(session) => *lock.session = Some(session),
+                Err(error) => {
+                    self.unload(lock).await;
+                    return Err(error);
+                }
+            }
+        }
+        Ok(lock)
+    }
+
+    /// Reads a note's collaborative document from the database, building it from the note's content if it was never saved.
+    async fn fetch(pool: &PgPool, note: i32) -> Result<Session> {
+        let saved = query!(/* language=postgresql */ "
+            SELECT notes.content, note_documents.state AS \"state?\"
+            FROM notes
+            LEFT JOIN note_documents ON note_documents.note = notes.id
+            WHERE notes.id = $1 AND notes.trash IS NULL
+            LIMIT 1;
+            ", note)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        let document = match saved.state {
+            Some(state) => AutoCommit::load(&state),
+            None => build(&saved.content),
+        }.or(Err(Status::InternalServerError))?;
+        Ok(Session {
+            document,
+            events: broadcast::channel(EVENT_BUFFER).0,
+            collaborators: Vec::new(),
+            editor: None,
+        })
+    }
+
+    /// Adds a client to the note's collaborators. Returns the whole document and the note's future events.
+    /// Fails with 409 Conflict if another user is subscribed with the same client ID.
+    pub async fn join(&self, pool: &PgPool, note: i32, collaborator: Collaborator) -> Result<(NoteSync, broadcast::Receiver<NoteEvent>)> {
+        let mut lock = self.load(pool, note).await?;
+        let session = lock.session()?;
+        if session.collaborators.iter().any(|other| other.client_id == collaborator.client_id && other.user != collaborator.user) {
+            return Err(Status::Conflict.into());
+        }
+        session.collaborators.retain(|other| other.client_id != collaborator.client_id);
+        session.collaborators.push(collaborator);
+        // Sent before subscribing, as the joining client gets the collaborators with the whole document
+        session.broadcast_presence();
+        Ok((session.sync(), session.events.subscribe()))
+    }
+
+    /// Removes a client from the note's collaborators.
+    pub async fn leave(&self, note: i32, client_id: &str) {
+        if let Some(mut lock) = self.lock_loaded(note).await {
+            if let Ok(session) = lock.session() {
+                session.collaborators.retain(|collaborator| collaborator.client_id != client_id);
+                session.broadcast_presence();
+            }
+        }
+    }
+
+    /// Gets the whole document of a note.
+    pub async fn sync(&self, pool: &PgPool, note: i32) -> Result<NoteSync> {
+        Ok(self.load(pool, note).await?.session()?.sync())
+    }
+
+    /// Merges changes made by a client into a note's document and relays them to the other clients.
+    /// `heads` are the hashes of the latest changes the client had received before making its own.
+    /// Returns the changes the client is missing.
+    /// The client must be subscribed to the note by the user.
+    /// Fails with a conflict if the changes were made to a document that has since been replaced.
+    pub async fn merge(&self, pool: &PgPool, note: i32, user: i32, client_id: &str, changes: &[String], heads: &[String]) -> Result<NoteMerge> {
+        let changes = changes.iter()
+            .map(|change| Change::from_bytes(BASE64.decode(change).or(Err(Status::BadRequest))?).or(Err(Status::BadRequest)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let heads = heads.iter()
+            .map(|head| head.parse::<ChangeHash>().or(Err(Status::BadRequest)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let received = changes.iter().map(Change::hash).collect::<HashSet<_>>();
+
+        let mut lock = self.load(pool, note).await?;
+        let session = lock.session()?;
+        session.collaborator(client_id, user)?;
+        // Changes must build on this document's history, not one it has since been replaced with
+        let known = |session: &mut Session, hash: &ChangeHash| received.contains(hash) || session.document.get_change_by_hash(hash).is_some();
+        for hash in heads.iter().chain(changes.iter().flat_map(Change::deps)) {
+            if !known(session, hash) {
+                return Err(Status::Conflict.into());
+            }
+        }
+
+        let before = session.document.get_heads();
+        session.document.apply_changes(changes).or(Err(Status::BadRequest))?;
+        let new = session.document.get_changes(&before);
+        if !new.is_empty() {
+            let new = encode_changes(&new);
+            let heads = encode_heads(&session.document.get_heads());
+            session.editor = Some(user);
+            let _ = session.events.send(NoteEvent::Changes(NoteChanges {
+                client_id: client_id.to_string(),
+                changes: new,
+                heads,
+            }));
+        }
+        let missing = session.document.get_changes(&heads).into_iter()
+            .filter(|change| !received.contains(&change.hash()))
+            .collect::<Vec<_>>();
+        let changes = encode_changes(&missing);
+        Ok(NoteMerge {
+            changes,
+            heads: encode_heads(&session.document.get_heads()),
+        })
+    }
+
+    /// Shares a client's selection with the note's other collaborators.
+    /// The client must be subscribed to the note by the user.
+    pub async fn select(&self, note: i32, user: i32, client_id: &str, selection: Option<Value>) -> Result<()> {
+        let mut lock = self.lock_loaded(note).await.ok_or(Status::NotFound)?;
+        let session = lock.session()?;
+        session.collaborator(client_id, user)?.selection = selection;
+        session.broadcast_presence();
+        Ok(())
+    }
+
+    /// Replaces a note's document after its content was changed without collaborating, discarding edits that were not saved yet.
+    /// `lock` must have been held since before the content was changed.
+    /// Clients are sent the new document.
+    pub async fn replace(&self, pool: &PgPool, mut lock: NoteLock, content: &Value) -> Result<()> {
+        query!(/* language=postgresql */ "DELETE FROM note_documents WHERE note = $1;", lock.note)
+            .execute(pool).await?;
+        match lock.session.as_mut() {
+            Some(session) => {
+                session.document = build(content).or(Err(Status::InternalServerError))?;
+                session.editor = None;
+                let sync = session.sync();
+                let _ = session.events.send(NoteEvent::Sync(sync));
+            }
+            None => self.unload(lock).await,
+        }
+        Ok(())
+    }
+
+    /// Closes a note's session after the note was deleted.
+    /// Edits that were not saved yet are written to the note first, so they are not lost.
+    /// Subscribers' event streams end, and clients must subscribe again to continue editing.
+    pub async fn close(&self, pool: &PgPool, note: i32) {
+        let Some(mut lock) = self.lock_loaded(note).await else {
+            return;
+        };
+        if let Ok(session) = lock.session() {
+            if let Err(error) = session.save(pool, note).await {
+                error!("Failed to save collaborative edits to note {note}: {}", error.message);
+            }
+        }
+        self.unload(lock).await;
+    }
+
+    /// Closes a note's session after its content was rewritten elsewhere, discarding edits that were not saved yet.
+    /// Saving them would write the session's older document over the rewritten content.
+    /// Subscribers' event streams end, and clients must subscribe again to continue editing.
+    pub async fn discard(&self, note: i32) {
+        if let Some(lock) = self.lock_loaded(note).await {
+            self.unload(lock).await;
+        }
+    }
+
+    /// Writes edited documents back to their notes, keeping the previous content as a revision.
+    /// Merged documents that the editor cannot open are repaired first.
+    /// Each note's session is locked while it is saved, so its content cannot be replaced in the meantime.
+    /// Documents nobody is subscribed to are then unloaded, unless they could not be saved.
+    pub async fn compact(&self, pool: &PgPool) {
+        let notes = self.sessions.lock().await.keys().copied().collect::<Vec<_>>();
+        for note in notes {
+            let Some(mut lock) = self.lock_loaded(note).await else {
+                continue;
+            };
+            let Ok(session) = lock.session() else {
+                continue;
+            };
+            let unload = match session.save(pool, note).await {
+                Ok(saved) => !saved || session.events.receiver_count() == 0,
+                Err(error) => {
+                    error!("Failed to save collaborative edits to note {note}: {}", error.message);
+                    false
+                }
+            };
+            if unload {
+                self.unload(lock).await;
+            }
+        }
+    }
+
+    /// Compacts edited documents every [`COMPACT_INTERVAL`], forever.
+    pub async fn compact_periodically(self: Arc<Self>, pool: PgPool) {
+        let mut interval = tokio::time::interval(COMPACT_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.compact(&pool).await;
+        }
+    }
+
+    /// Closes the sessions of notes announced on [`REWRITTEN_CHANNEL`], forever.
+    pub async fn close_rewritten(self: Arc<Self>, pool: PgPool) {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                error!("Failed to listen for rewritten notes: {error}");
+                return;
+            }
+        };
+        if let Err(error) = listener.listen(REWRITTEN_CHANNEL).await {
+            error!("Failed to listen for rewritten notes: {error}");
+            return;
+        }
+        loop {
+            // The listener reconnects by itself, missing only notifications sent while disconnected
+            match listener.recv().await {
+                Ok(notification) => {
+                    if let Ok(note) = notification.payload().parse() {
+                        self.discard(note).await;
+                    }
+                }
+                Err(error) => {
+                    error!("Failed to listen for rewritten notes: {error}");
+                    tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+}
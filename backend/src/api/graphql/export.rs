@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use chrono::Datelike;
+use lazy_static::lazy_static;
+use rocket::{FromFormField, get, Responder, Route, routes, State};
+use rocket::http::{ContentType, Header, Status};
+use sqlx::{PgPool, query, query_as};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+use crate::api::graphql::anki::Package;
+use crate::api::graphql::query::flashcard::{Card, Deck};
+use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::tiptap::{html, markdown, Node};
+use crate::auth::User;
+
+lazy_static! {
+    pub static ref ROUTES: Vec<Route> = routes![
+        note,
+        subject,
+        anki,
+    ];
+}
+
+/// The first IDs of the Anki decks made for subjects and flashcard decks, so that they stay the same between exports.
+const ANKI_SUBJECT_DECKS: i64 = 1_697_600_000_000;
+const ANKI_FLASHCARD_DECKS: i64 = 1_697_700_000_000;
+
+const STYLE: &str = "
+    body { font-family: sans-serif; margin: 2em auto; max-width: 48em; }
+    pre { background: #f4f4f4; padding: 0.5em; overflow-x: auto; }
+    blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1em; }
+    table { border-collapse: collapse; }
+    th, td { border: 1px solid #999; padding: 0.25em 0.5em; text-align: left; }
+    .task-list { list-style: none; padding-left: 1em; }
+    .muted { color: #666; }
+    .note-link { color: #2a5db0; }
+    .question-answer { border: 1px solid #ccc; padding: 0 1em; }
+    .question { font-weight: bold; }
+    .cloze { background: #fff3b0; }
+    dt { font-weight: bold; }
+";
+
+#[derive(FromFormField, Copy, Clone, Default)]
+enum Format {
+    #[default]
+    #[field(value = "md")]
+    Markdown,
+    #[field(value = "html")]
+    Html,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+#[derive(FromFormField, Copy, Clone, Default)]
+enum AnkiSource {
+    #[default]
+    #[field(value = "notes")]
+    Notes,
+    #[field(value = "flashcards")]
+    Flashcards,
+}
+
+#[derive(Responder)]
+struct Export {
+    body: Vec<u8>,
+    content_type: ContentType,
+    disposition: Header<'static>,
+}
+
+impl Export {
+    fn new(body: Vec<u8>, content_type: ContentType, file_name: &str) -> Self {
+        // Non-ASCII names are percent-encoded as described in RFC 6266
+        let encoded = file_name.bytes()
+            .map(|byte| if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                char::from(byte).to_string()
+            } else {
+                format!("%{byte:02X}")
+            })
+            .collect::<String>();
+        let fallback = file_name.chars().map(|char| if char.is_ascii() { char } else { '_' }).collect::<String>();
+        Self {
+            body,
+            content_type,
+            disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")),
+        }
+    }
+}
+
+/// Turns a note title into a name that is safe to use for a file.
+fn file_name(title: &str) -> String {
+    let name = title.chars()
+        .filter(|char| !char.is_control())
+        .map(|char| if matches!(char, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '-' } else { char })
+        .take(100)
+        .collect::<String>();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() { "Untitled".to_string() } else { name.to_string() }
+}
+
+/// Converts a note to a standalone Markdown or HTML document.
+/// Markdown documents record the note's title, date and subject in front matter so they can be imported again.
+fn render(note: &Note, subject: Option<&Subject>, format: Format) -> Result<String, Status> {
+    let document = Node::from_value(&note.content).or(Err(Status::UnprocessableEntity))?;
+    Ok(match format {
+        Format::Markdown => {
+            let mut front_matter = format!("title: {}\ndate: {}\n", serde_json::to_string(&note.title).or(Err(Status::InternalServerError))?, note.date);
+            if let Some(subject) = subject {
+                front_matter.push_str("subject: ");
+                front_matter.push_str(&serde_json::to_string(&subject.name).or(Err(Status::InternalServerError))?);
+                front_matter.push('\n');
+            }
+            format!("---\n{front_matter}---\n\n{}", markdown::render(&document))
+        }
+        Format::Html => format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title><style>{STYLE}</style></head><body><h1>{title}</h1><p class=\"muted\">{date}</p>{content}</body></html>",
+            title = html::escape(&note.title),
+            date = note.date.format("%-d %B %Y"),
+            content = html::render(&document).or(Err(Status::InternalServerError))?,
+        ),
+    })
+}
+
+/// Downloads a note the authenticated user owns or has been shared as Markdown or HTML.
+/// `format` is either `md` or `html`. Default: `md`
+#[get("/notes/<id>/export?<format>")]
+async fn note(user: User, db: &State<PgPool>, id: i32, format: Option<Format>) -> Result<Export, Status> {
+    let format = format.unwrap_or_default();
+    let note = query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE id = $2 AND note_role(id, $1) IS NOT NULL LIMIT 1;", user.id, id)
+        .fetch_optional(&**db).await.or(Err(Status::InternalServerError))?
+        .ok_or(Status::NotFound)?;
+    // Subjects are private, so notes shared with the user are exported without one
+    let subject = query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE id = $1 AND owner = $2 LIMIT 1;", note.subject, user.id)
+        .fetch_optional(&**db).await.or(Err(Status::InternalServerError))?;
+    let content_type = match format {
+        Format::Markdown => ContentType::new("text", "markdown").with_params(("charset", "utf-8")),
+        Format::Html => ContentType::HTML,
+    };
+    let file_name = format!("{} {}.{}", note.date, file_name(&note.title), format.extension());
+    Ok(Export::new(render(&note, subject.as_ref(), format)?.into_bytes(), content_type, &file_name))
+}
+
+/// Downloads all notes of one of the authenticated user's subjects as a zip archive of Markdown or HTML files.
+/// Notes are organised into folders by year and month of their date.
+/// `format` is either `md` or `html`. Default: `md`
+#[get("/subjects/<id>/export?<format>")]
+async fn subject(user: User, db: &State<PgPool>, id: i32, format: Option<Format>) -> Result<Export, Status> {
+    let format = format.unwrap_or_default();
+    let subject = query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;", user.id, id)
+        .fetch_optional(&**db).await.or(Err(Status::InternalServerError))?
+        .ok_or(Status::NotFound)?;
+    let notes = query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE subject = $1 AND trash IS NULL ORDER BY date, id;", subject.id)
+        .fetch_all(&**db).await.or(Err(Status::InternalServerError))?;
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut paths = HashSet::new();
+    for note in &notes {
+        let stem = format!("{year}/{month:02}/{date} {title}", year = note.date.year(), month = note.date.month(), date = note.date, title = file_name(&note.title));
+        let mut path = format!("{stem}.{}", format.extension());
+        let mut copy = 1;
+        while !paths.insert(path.clone()) {
+            copy += 1;
+            path = format!("{stem} ({copy}).{}", format.extension());
+        }
+        let mut options = FileOptions::default();
+        if let Ok(year) = u16::try_from(note.date.year()) {
+            // Dates outside the range zip supports keep the default modification time
+            if let Ok(modified) = zip::DateTime::from_date_and_time(year, u8::try_from(note.date.month()).unwrap_or(1), u8::try_from(note.date.day()).unwrap_or(1), 0, 0, 0) {
+                options = options.last_modified_time(modified);
+            }
+        }
+        zip.start_file(path, options).or(Err(Status::InternalServerError))?;
+        zip.write_all(render(note, Some(&subject), format)?.as_bytes()).or(Err(Status::InternalServerError))?;
+    }
+    let archive = zip.finish().or(Err(Status::InternalServerError))?.into_inner();
+    let file_name = format!("{} {}.zip", file_name(&subject.name), file_name(&subject.class));
+    Ok(Export::new(archive, ContentType::ZIP, &file_name))
+}
+
+/// Downloads an Anki package of one of the authenticated user's subjects, which can be imported into Anki.
+/// With the `notes` source, the question and answer blocks and definition lists of the subject's notes become basic notes and blocks with cloze deletions become cloze notes,
+/// in a deck named after the subject and tagged with the user's tags on the note.
+/// With the `flashcards` source, each of the subject's flashcard decks becomes a deck inside one named after the subject.
+/// Importing a newer package updates the notes from an older one instead of duplicating them.
+/// `source` is either `notes` or `flashcards`. Default: `notes`
+#[get("/subjects/<id>/anki?<source>")]
+async fn anki(user: User, db: &State<PgPool>, id: i32, source: Option<AnkiSource>) -> Result<Export, Status> {
+    let subject = query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;", user.id, id)
+        .fetch_optional(&**db).await.or(Err(Status::InternalServerError))?
+        .ok_or(Status::NotFound)?;
+    let mut package = Package::default();
+    match source.unwrap_or_default() {
+        AnkiSource::Notes => {
+            let deck = ANKI_SUBJECT_DECKS + i64::from(subject.id);
+            package.add_deck(deck, &subject.name);
+            let notes = query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE subject = $1 AND trash IS NULL ORDER BY date, id;", subject.id)
+                .fetch_all(&**db).await.or(Err(Status::InternalServerError))?;
+            let mut tags = HashMap::<i32, Vec<String>>::new();
+            for tag in query!(/* language=postgresql */ "
+                SELECT note_tags.note, tags.name FROM note_tags
+                JOIN tags ON tags.id = note_tags.tag
+                WHERE tags.owner = $1 AND note_tags.note IN (SELECT id FROM notes WHERE subject = $2 AND trash IS NULL)
+                ORDER BY tags.name;
+                ", user.id, subject.id)
+                .fetch_all(&**db).await.or(Err(Status::InternalServerError))? {
+                tags.entry(tag.note).or_default().push(tag.name);
+            }
+            for note in &notes {
+                let document = Node::from_value(&note.content).or(Err(Status::UnprocessableEntity))?;
+                let tags = tags.get(&note.id).map_or(&[][..], Vec::as_slice);
+                package.add_document(deck, &format!("note:{}", note.id), &note.title, &document, tags).or(Err(Status::InternalServerError))?;
+            }
+        }
+        AnkiSource::Flashcards => {
+            let decks = query_as!(Deck, /* language=postgresql */ "SELECT * FROM decks WHERE owner = $1 AND subject = $2 ORDER BY name;", user.id, subject.id)
+                .fetch_all(&**db).await.or(Err(Status::InternalServerError))?;
+            for deck in &decks {
+                package.add_deck(ANKI_FLASHCARD_DECKS + i64::from(deck.id), &format!("{}::{}", subject.name, deck.name));
+            }
+            let cards = query_as!(Card, /* language=postgresql */ "
+                SELECT cards.* FROM cards
+                JOIN decks ON decks.id = cards.deck
+                WHERE decks.owner = $1 AND decks.subject = $2
+                ORDER BY cards.id;
+                ", user.id, subject.id)
+                .fetch_all(&**db).await.or(Err(Status::InternalServerError))?;
+            for card in &cards {
+                package.add_basic(ANKI_FLASHCARD_DECKS + i64::from(card.deck), format!("sp-card-{}", card.id), &card.front, &card.back, &[]);
+            }
+        }
+    }
+    let body = package.build().await.or(Err(Status::InternalServerError))?;
+    let file_name = format!("{} {}.apkg", file_name(&subject.name), file_name(&subject.class));
+    Ok(Export::new(body, ContentType::Binary, &file_name))
+}
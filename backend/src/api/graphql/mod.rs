@@ -3,26 +3,44 @@
 
 mod query;
 mod mutation;
+mod subscription;
+mod tiptap;
+mod websocket;
+pub mod collaboration;
+pub mod report;
+pub mod export;
+mod anki;
+pub mod repair;
+pub mod trash;
+mod recurrence;
 
-use async_graphql::EmptySubscription;
+use std::sync::Arc;
 use async_graphql_rocket::{GraphQLRequest, GraphQLResponse};
+use jsonwebtoken::DecodingKey;
 use lazy_static::lazy_static;
 use rocket::{get, post, Route, routes, State};
 use sqlx::PgPool;
+use crate::api::graphql::collaboration::Collaboration;
+use crate::api::graphql::trash::Retention;
+use crate::api::graphql::websocket::{Connection, Upgrade};
 use crate::auth::User;
 
 lazy_static! {
     pub static ref ROUTES: Vec<Route> = routes![
         schema,
         post,
+        post_multipart,
+        subscribe,
     ];
 }
 
-pub type Schema = async_graphql::Schema<query::QueryRoot, mutation::MutationRoot, EmptySubscription>;
+pub type Schema = async_graphql::Schema<query::QueryRoot, mutation::MutationRoot, subscription::SubscriptionRoot>;
 
-pub fn create_schema(pool: PgPool) -> Schema {
-    Schema::build(query::QueryRoot, mutation::MutationRoot, EmptySubscription)
+pub fn create_schema(pool: PgPool, collaboration: Arc<Collaboration>, retention: Retention) -> Schema {
+    Schema::build(query::QueryRoot, mutation::MutationRoot, subscription::SubscriptionRoot)
         .data(pool)
+        .data(collaboration)
+        .data(retention)
         .finish()
 }
 
@@ -32,8 +50,26 @@ fn schema(schema: &State<Schema>) -> String {
 }
 
 #[post("/", data = "<request>", format = "application/json")]
-async fn post<'a>(schema: &State<Schema>, user: Option<User>, request: GraphQLRequest) -> GraphQLResponse {
+async fn post(schema: &State<Schema>, user: Option<User>, request: GraphQLRequest) -> GraphQLResponse {
     request
         .data(user)
         .execute(&**schema).await
 }
+
+/// Accepts requests with file uploads, as described by the GraphQL multipart request specification.
+#[post("/", data = "<request>", format = "multipart/form-data", rank = 2)]
+async fn post_multipart(schema: &State<Schema>, user: Option<User>, request: GraphQLRequest) -> GraphQLResponse {
+    post(schema, user, request).await
+}
+
+/// Runs subscriptions over a WebSocket, using the `graphql-transport-ws` or the older `graphql-ws` protocol.
+#[get("/ws")]
+fn subscribe(schema: &State<Schema>, db: &State<PgPool>, key: &State<DecodingKey>, user: Option<User>, upgrade: Upgrade) -> Connection {
+    Connection {
+        upgrade,
+        schema: (**schema).clone(),
+        user,
+        pool: (**db).clone(),
+        key: (**key).clone(),
+    }
+}
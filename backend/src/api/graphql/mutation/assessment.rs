@@ -0,0 +1,29 @@
+use async_graphql::{Context, Object, Result};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::mutation::tag::check_tags;
+use crate::api::graphql::query::assessment::Assessment;
+use crate::auth::User;
+
+pub struct AssessmentMutation(pub i32);
+
+#[Object]
+impl AssessmentMutation {
+    /// Replaces the assessment's tags. Returns the updated assessment.
+    async fn tags(&self, ctx: &Context<'_>, #[graphql(desc = "The IDs of the assessment's new tags.")] tags: Vec<i32>) -> Result<Assessment> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        check_tags(pool, user.id, &tags).await?;
+        let mut tx = pool.begin().await?;
+        query!(/* language=postgresql */ "DELETE FROM assessment_tags WHERE assessment = $1;", self.0)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "INSERT INTO assessment_tags (assessment, tag) SELECT $1, UNNEST($2::INTEGER[]) ON CONFLICT DO NOTHING;", self.0, &tags)
+            .execute(&mut *tx).await?;
+        let assessment = query_as!(Assessment, /* language=postgresql */ "SELECT * FROM assessments WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(assessment)
+    }
+}
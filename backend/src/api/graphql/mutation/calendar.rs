@@ -0,0 +1,257 @@
+use async_graphql::{Context, InputObject, Object, Result};
+use chrono::NaiveDate;
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::calendar::{AcademicYear, Holiday, HolidayKind, Term};
+use crate::api::graphql::query::subject::Subject;
+use crate::auth::User;
+
+pub struct AcademicYearMutation(pub i32);
+
+/// Checks that the days from `start_date` to `end_date` are in order and within the academic year.
+/// Fails with 400 Bad Request otherwise.
+async fn check_dates(pool: &PgPool, year: i32, start_date: NaiveDate, end_date: NaiveDate) -> Result<()> {
+    let year = query!(/* language=postgresql */ "SELECT start_date, end_date FROM academic_years WHERE id = $1 LIMIT 1;", year)
+        .fetch_one(pool).await?;
+    if start_date > end_date || start_date < year.start_date || end_date > year.end_date {
+        return Err(Status::BadRequest.into());
+    }
+    Ok(())
+}
+
+#[derive(InputObject)]
+pub struct SubjectRollover {
+    /// The ID of the subject to continue into the next year.
+    pub subject: i32,
+
+    /// The class of the new subject.
+    #[graphql(validator(max_length = 16))]
+    pub class: String,
+
+    /// The name of the new subject. Default: the name of the archived subject
+    #[graphql(validator(max_length = 255))]
+    pub name: Option<String>,
+}
+
+#[Object]
+impl AcademicYearMutation {
+    /// Delete the academic year and its terms and holidays. Always returns true or an error.
+    /// Subjects taken during the year are kept but no longer belong to a year.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM academic_years WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the academic year's name. Returns the updated academic year.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the academic year.", validator(max_length = 255))] name: String) -> Result<AcademicYear> {
+        query_as!(AcademicYear, /* language=postgresql */ "UPDATE academic_years SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the academic year's first and last days. Returns the updated academic year.
+    /// Fails with 409 Conflict if any of the year's terms or holidays would fall outside the new dates.
+    async fn dates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The new first day of the academic year.")] start_date: NaiveDate,
+        #[graphql(desc = "The new last day of the academic year.")] end_date: NaiveDate,
+    ) -> Result<AcademicYear> {
+        if start_date > end_date {
+            return Err(Status::BadRequest.into());
+        }
+        let pool = ctx.data::<PgPool>()?;
+        let clash = query!(/* language=postgresql */ r#"
+            SELECT 1 AS "clash!" FROM terms WHERE year = $1 AND (start_date < $2 OR end_date > $3)
+            UNION ALL
+            SELECT 1 FROM holidays WHERE year = $1 AND (start_date < $2 OR end_date > $3)
+            LIMIT 1;
+            "#, self.0, start_date, end_date)
+            .fetch_optional(pool).await?;
+        if clash.is_some() {
+            return Err(Status::Conflict.into());
+        }
+        query_as!(AcademicYear, /* language=postgresql */ "UPDATE academic_years SET start_date = $2, end_date = $3 WHERE id = $1 RETURNING *;", self.0, start_date, end_date)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Updates the number of weeks in the academic year's timetable rotation. Returns the updated academic year.
+    /// Fails with 409 Conflict if any classes of the year's subjects meet in a week beyond the new rotation.
+    async fn rotation_weeks(&self, ctx: &Context<'_>, #[graphql(desc = "The new number of weeks in the rotation.", validator(minimum = 1))] rotation_weeks: i16) -> Result<AcademicYear> {
+        let clash = query!(/* language=postgresql */ r#"
+            SELECT 1 AS "clash!" FROM timetable_entries
+            JOIN subjects ON subjects.id = timetable_entries.subject
+            WHERE subjects.year = $1 AND timetable_entries.week > $2
+            LIMIT 1;
+            "#, self.0, rotation_weeks)
+            .fetch_optional(ctx.data::<PgPool>()?).await?;
+        if clash.is_some() {
+            return Err(Status::Conflict.into());
+        }
+        query_as!(AcademicYear, /* language=postgresql */ "UPDATE academic_years SET rotation_weeks = $2 WHERE id = $1 RETURNING *;", self.0, rotation_weeks)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Ends the academic year by archiving all of its subjects, optionally continuing some of them into the next year.
+    /// Notes, to-dos and assessments stay with the archived subjects.
+    /// Returns the newly created subjects.
+    async fn rollover(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the academic year to continue subjects into. Required if any subjects are continued. Default: null", default)] next_year: Option<i32>,
+        #[graphql(desc = "The subjects to continue into the next year. Default: []", default)] subjects: Vec<SubjectRollover>,
+    ) -> Result<Vec<Subject>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        if next_year == Some(self.0) || (next_year.is_none() && !subjects.is_empty()) {
+            return Err(Status::BadRequest.into());
+        }
+        let mut tx = ctx.data::<PgPool>()?.begin().await?;
+        if let Some(next_year) = next_year {
+            query(/* language=postgresql */ "SELECT 1 FROM academic_years WHERE owner = $1 AND id = $2 LIMIT 1;")
+                .bind(user.id)
+                .bind(next_year)
+                .fetch_optional(&mut *tx).await?.ok_or(Status::NotFound)?;
+        }
+        let mut created = Vec::with_capacity(subjects.len());
+        for rollover in subjects {
+            created.push(query_as!(Subject, /* language=postgresql */ "
+                INSERT INTO subjects (owner, name, class, active, grade_scale, year, default_template)
+                SELECT owner, COALESCE($3, name), $4, TRUE, grade_scale, $5, default_template
+                FROM subjects
+                WHERE year = $1 AND id = $2 AND trash IS NULL
+                RETURNING *;
+                ", self.0, rollover.subject, rollover.name, rollover.class, next_year)
+                .fetch_optional(&mut *tx).await?.ok_or(Status::NotFound)?);
+        }
+        query!(/* language=postgresql */ "UPDATE subjects SET archived = TRUE, active = FALSE WHERE year = $1;", self.0)
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    /// Get a term of the academic year for modification.
+    async fn term(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the term to modify.")] id: i32) -> Result<TermMutation> {
+        query(/* language=postgresql */ "SELECT 1 FROM terms WHERE year = $1 AND id = $2 LIMIT 1;")
+            .bind(self.0)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(TermMutation(id))
+    }
+
+    /// Creates a term in the academic year. Returns the newly created term.
+    /// Fails with 400 Bad Request if the term is not within the academic year.
+    async fn create_term(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the term.", validator(max_length = 255))] name: String,
+        #[graphql(desc = "The first day of the term.")] start_date: NaiveDate,
+        #[graphql(desc = "The last day of the term.")] end_date: NaiveDate,
+    ) -> Result<Term> {
+        let pool = ctx.data::<PgPool>()?;
+        check_dates(pool, self.0, start_date, end_date).await?;
+        query_as!(Term, /* language=postgresql */ "INSERT INTO terms (year, name, start_date, end_date) VALUES ($1, $2, $3, $4) RETURNING *;", self.0, name, start_date, end_date)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Get a holiday of the academic year for modification.
+    async fn holiday(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the holiday to modify.")] id: i32) -> Result<HolidayMutation> {
+        query(/* language=postgresql */ "SELECT 1 FROM holidays WHERE year = $1 AND id = $2 LIMIT 1;")
+            .bind(self.0)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(HolidayMutation(id))
+    }
+
+    /// Creates a holiday or pupil-free day in the academic year. Returns the newly created holiday.
+    /// Fails with 400 Bad Request if the holiday is not within the academic year.
+    async fn create_holiday(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the holiday.", validator(max_length = 255))] name: String,
+        #[graphql(desc = "Whether this is a holiday or a pupil-free day. Default: HOLIDAY", default_with = "HolidayKind::Holiday")] kind: HolidayKind,
+        #[graphql(desc = "The first day of the holiday.")] start_date: NaiveDate,
+        #[graphql(desc = "The last day of the holiday. Default: the first day", default)] end_date: Option<NaiveDate>,
+    ) -> Result<Holiday> {
+        let end_date = end_date.unwrap_or(start_date);
+        let pool = ctx.data::<PgPool>()?;
+        check_dates(pool, self.0, start_date, end_date).await?;
+        query_as!(Holiday, /* language=postgresql */ "INSERT INTO holidays (year, name, kind, start_date, end_date) VALUES ($1, $2, $3, $4, $5) RETURNING *;", self.0, name, kind as i16, start_date, end_date)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+}
+
+pub struct TermMutation(pub i32);
+
+#[Object]
+impl TermMutation {
+    /// Delete the term. Always returns true or an error.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM terms WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the term's name. Returns the updated term.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the term.", validator(max_length = 255))] name: String) -> Result<Term> {
+        query_as!(Term, /* language=postgresql */ "UPDATE terms SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the term's first and last days. Returns the updated term.
+    /// Fails with 400 Bad Request if the term would not be within its academic year.
+    async fn dates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The new first day of the term.")] start_date: NaiveDate,
+        #[graphql(desc = "The new last day of the term.")] end_date: NaiveDate,
+    ) -> Result<Term> {
+        let pool = ctx.data::<PgPool>()?;
+        let year = query!(/* language=postgresql */ "SELECT year FROM terms WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(pool).await?.year;
+        check_dates(pool, year, start_date, end_date).await?;
+        query_as!(Term, /* language=postgresql */ "UPDATE terms SET start_date = $2, end_date = $3 WHERE id = $1 RETURNING *;", self.0, start_date, end_date)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+}
+
+pub struct HolidayMutation(pub i32);
+
+#[Object]
+impl HolidayMutation {
+    /// Delete the holiday. Always returns true or an error.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM holidays WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the holiday's name. Returns the updated holiday.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the holiday.", validator(max_length = 255))] name: String) -> Result<Holiday> {
+        query_as!(Holiday, /* language=postgresql */ "UPDATE holidays SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates whether the holiday is a holiday or a pupil-free day. Returns the updated holiday.
+    async fn kind(&self, ctx: &Context<'_>, #[graphql(desc = "The new kind of the holiday.")] kind: HolidayKind) -> Result<Holiday> {
+        query_as!(Holiday, /* language=postgresql */ "UPDATE holidays SET kind = $2 WHERE id = $1 RETURNING *;", self.0, kind as i16)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the holiday's first and last days. Returns the updated holiday.
+    /// Fails with 400 Bad Request if the holiday would not be within its academic year.
+    async fn dates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The new first day of the holiday.")] start_date: NaiveDate,
+        #[graphql(desc = "The new last day of the holiday.")] end_date: NaiveDate,
+    ) -> Result<Holiday> {
+        let pool = ctx.data::<PgPool>()?;
+        let year = query!(/* language=postgresql */ "SELECT year FROM holidays WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(pool).await?.year;
+        check_dates(pool, year, start_date, end_date).await?;
+        query_as!(Holiday, /* language=postgresql */ "UPDATE holidays SET start_date = $2, end_date = $3 WHERE id = $1 RETURNING *;", self.0, start_date, end_date)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+}
@@ -0,0 +1,194 @@
+use async_graphql::{Context, Object, Result};
+use chrono::{Days, NaiveDate};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::flashcard::{Card, Deck, ReviewGrade};
+use crate::api::graphql::tiptap::flashcards::extract;
+use crate::api::graphql::tiptap::Node;
+use crate::auth::User;
+
+/// The lowest ease a card can have, so that difficult cards are still spaced out.
+const MIN_EASE: f64 = 1.3;
+
+/// The longest interval between reviews in days.
+const MAX_INTERVAL: i32 = 36500;
+
+pub struct DeckMutation(pub i32);
+
+pub struct CardMutation(pub i32);
+
+/// A card's schedule after a review.
+struct Schedule {
+    interval: i32,
+    ease: f64,
+    repetitions: i32,
+    lapses: i32,
+}
+
+/// Schedules a card's next review with the SM-2 algorithm.
+/// Forgotten cards are due again the next day and learned again from the start.
+/// Remembered cards are due after 1 day, then 6 days, then the previous interval times the ease.
+/// The ease goes down for hard cards and up for easy ones.
+#[allow(clippy::cast_possible_truncation)]
+fn schedule(card: &Card, grade: ReviewGrade) -> Schedule {
+    // SM-2 grades answers from 0 to 5, where 3 or more is a pass
+    let quality = match grade {
+        ReviewGrade::Again => 1.0,
+        ReviewGrade::Hard => 3.0,
+        ReviewGrade::Good => 4.0,
+        ReviewGrade::Easy => 5.0,
+    };
+    let ease = (card.ease + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02)).max(MIN_EASE);
+    // Rounding keeps the ease readable after many reviews
+    let ease = (ease * 100.0).round() / 100.0;
+    if grade == ReviewGrade::Again {
+        return Schedule {
+            interval: 1,
+            ease,
+            repetitions: 0,
+            lapses: card.lapses + i32::from(card.repetitions > 0),
+        };
+    }
+    let interval = match card.repetitions {
+        0 => 1,
+        1 => 6,
+        _ => (f64::from(card.interval) * card.ease).round().min(f64::from(MAX_INTERVAL)) as i32,
+    };
+    Schedule {
+        interval,
+        ease,
+        repetitions: card.repetitions + 1,
+        lapses: card.lapses,
+    }
+}
+
+/// Records a review of a card on `today` and schedules its next one. Returns the updated card.
+pub async fn review(pool: &PgPool, card: i32, grade: ReviewGrade, today: NaiveDate) -> Result<Card> {
+    let mut tx = pool.begin().await?;
+    let current = query_as!(Card, /* language=postgresql */ "SELECT * FROM cards WHERE id = $1 LIMIT 1 FOR UPDATE;", card)
+        .fetch_one(&mut *tx).await?;
+    let next = schedule(&current, grade);
+    let due = today.checked_add_days(Days::new(next.interval.unsigned_abs().into())).unwrap_or(today);
+    query!(/* language=postgresql */ "
+        INSERT INTO card_reviews (card, grade, previous_interval, previous_ease, interval, ease)
+        VALUES ($1, $2, $3, $4, $5, $6);
+        ", card, grade as i16, current.interval, current.ease, next.interval, next.ease)
+        .execute(&mut *tx).await?;
+    let card = query_as!(Card, /* language=postgresql */ "
+        UPDATE cards SET due = $2, interval = $3, ease = $4, repetitions = $5, lapses = $6
+        WHERE id = $1
+        RETURNING *;
+        ", card, due, next.interval, next.ease, next.repetitions, next.lapses)
+        .fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(card)
+}
+
+#[Object]
+impl DeckMutation {
+    /// Delete the deck and its cards. Always returns true or an error.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM decks WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the deck's name. Returns the updated deck.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the deck.", validator(max_length = 255))] name: String) -> Result<Deck> {
+        query_as!(Deck, /* language=postgresql */ "UPDATE decks SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Moves the deck to another of the user's subjects. Returns the updated deck.
+    async fn subject(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the new subject of the deck.")] subject: i32) -> Result<Deck> {
+        let pool = ctx.data::<PgPool>()?;
+        query(/* language=postgresql */ "SELECT 1 FROM subjects JOIN decks ON decks.owner = subjects.owner WHERE decks.id = $1 AND subjects.id = $2 AND subjects.trash IS NULL LIMIT 1;")
+            .bind(self.0)
+            .bind(subject)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        query_as!(Deck, /* language=postgresql */ "UPDATE decks SET subject = $2 WHERE id = $1 RETURNING *;", self.0, subject)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Creates a card in the deck. Returns the newly created card, which is due today.
+    async fn create_card(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The prompt of the card.", validator(min_length = 1))] front: String,
+        #[graphql(desc = "The answer of the card.", validator(min_length = 1))] back: String,
+    ) -> Result<Card> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query_as!(Card, /* language=postgresql */ "INSERT INTO cards (deck, front, back, due) VALUES ($1, $2, $3, $4) RETURNING *;", self.0, front, back, user.today())
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Creates cards from a note's question and answer blocks, definition lists and cloze deletions. Returns the newly created cards.
+    /// Cards already extracted from the note into the deck with the same front are skipped, so notes can be extracted again after they change.
+    /// Requires access to the note.
+    async fn extract_cards(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the note to extract cards from.")] note: i32) -> Result<Vec<Card>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        let content = query!(/* language=postgresql */ "SELECT content FROM notes WHERE id = $1 AND note_role(id, $2) IS NOT NULL LIMIT 1;", note, user.id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?.content;
+        let document = Node::from_value(&content).or(Err(Status::InternalServerError))?;
+        let (fronts, backs): (Vec<_>, Vec<_>) = extract(&document).into_iter().map(|card| (card.front, card.back)).unzip();
+        query_as!(Card, /* language=postgresql */ "
+            INSERT INTO cards (deck, note, front, back, due)
+            SELECT $1, $2, extracted.front, extracted.back, $5
+            FROM (
+                SELECT DISTINCT ON (front) front, back, position
+                FROM UNNEST($3::TEXT[], $4::TEXT[]) WITH ORDINALITY AS extracted(front, back, position)
+                ORDER BY front, position
+            ) AS extracted
+            WHERE NOT EXISTS (SELECT 1 FROM cards WHERE deck = $1 AND note = $2 AND front = extracted.front)
+            ORDER BY extracted.position
+            RETURNING *;
+            ", self.0, note, &fronts, &backs, user.today())
+            .fetch_all(pool).await.map_err(Into::into)
+    }
+}
+
+#[Object]
+impl CardMutation {
+    /// Delete the card and its reviews. Always returns true or an error.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM cards WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the card's prompt. Returns the updated card.
+    async fn front(&self, ctx: &Context<'_>, #[graphql(desc = "The new prompt of the card.", validator(min_length = 1))] front: String) -> Result<Card> {
+        query_as!(Card, /* language=postgresql */ "UPDATE cards SET front = $2 WHERE id = $1 RETURNING *;", self.0, front)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the card's answer. Returns the updated card.
+    async fn back(&self, ctx: &Context<'_>, #[graphql(desc = "The new answer of the card.", validator(min_length = 1))] back: String) -> Result<Card> {
+        query_as!(Card, /* language=postgresql */ "UPDATE cards SET back = $2 WHERE id = $1 RETURNING *;", self.0, back)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Forgets the card's review history so it is learned again from the start, due today. Returns the updated card.
+    async fn reset(&self, ctx: &Context<'_>) -> Result<Card> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        let mut tx = pool.begin().await?;
+        query!(/* language=postgresql */ "DELETE FROM card_reviews WHERE card = $1;", self.0)
+            .execute(&mut *tx).await?;
+        let card = query_as!(Card, /* language=postgresql */ "
+            UPDATE cards SET due = $2, interval = DEFAULT, ease = DEFAULT, repetitions = DEFAULT, lapses = DEFAULT
+            WHERE id = $1
+            RETURNING *;
+            ", self.0, user.today())
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(card)
+    }
+}
@@ -0,0 +1,54 @@
+use async_graphql::{Context, Object, Result};
+use itertools::Itertools;
+use rocket::http::Status;
+use sqlx::{PgPool, Postgres, query, query_as, Transaction};
+use crate::api::graphql::query::grade_scale::{GradeBandInput, GradeScale};
+
+pub struct GradeScaleMutation(pub i32);
+
+/// Replaces the bands of a grade scale.
+pub(super) async fn set_bands(tx: &mut Transaction<'_, Postgres>, scale: i32, bands: Vec<GradeBandInput>) -> Result<()> {
+    let mut minimums = bands.iter().map(|band| band.minimum).collect::<Vec<_>>();
+    minimums.sort_by(f64::total_cmp);
+    if !minimums.windows(2).all(|pair| pair[0] < pair[1]) {
+        return Err(Status::BadRequest.into());
+    }
+    let (minimums, labels, points): (Vec<_>, Vec<_>, Vec<_>) = bands.into_iter()
+        .map(|band| (band.minimum, band.label, band.points))
+        .multiunzip();
+    query!(/* language=postgresql */ "DELETE FROM grade_bands WHERE scale = $1;", scale)
+        .execute(&mut **tx).await?;
+    query!(/* language=postgresql */ "
+        INSERT INTO grade_bands (scale, minimum, label, points)
+        SELECT $1, * FROM UNNEST($2::DOUBLE PRECISION[], $3::VARCHAR[], $4::DOUBLE PRECISION[]);
+        ", scale, &minimums, &labels, &points as &[Option<f64>])
+        .execute(&mut **tx).await?;
+    Ok(())
+}
+
+#[Object]
+impl GradeScaleMutation {
+    /// Delete the grade scale. Always returns true or an error.
+    /// Subjects using the scale are left without one.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM grade_scales WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the grade scale's name. Returns the updated grade scale.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the grade scale.", validator(max_length = 255))] name: String) -> Result<GradeScale> {
+        query_as!(GradeScale, /* language=postgresql */ "UPDATE grade_scales SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Replaces the grade scale's bands. Returns the updated grade scale.
+    async fn bands(&self, ctx: &Context<'_>, #[graphql(desc = "The new bands of the grade scale.")] bands: Vec<GradeBandInput>) -> Result<GradeScale> {
+        let mut tx = ctx.data::<PgPool>()?.begin().await?;
+        set_bands(&mut tx, self.0, bands).await?;
+        let scale = query_as!(GradeScale, /* language=postgresql */ "SELECT * FROM grade_scales WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(scale)
+    }
+}
@@ -0,0 +1,199 @@
+use std::io::{Cursor, Read};
+use async_graphql::{Error, Result, SimpleObject};
+use chrono::NaiveDate;
+use rocket::http::Status;
+use sqlx::{PgPool, query};
+use zip::ZipArchive;
+use crate::api::graphql::mutation::note::create_note;
+use crate::api::graphql::query::note::Note;
+use crate::api::graphql::tiptap::{markdown, Node};
+
+/// The largest file that can be imported, in bytes.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// The most files that can be imported from one archive.
+const MAX_FILES: usize = 1000;
+
+/// The most bytes that are decompressed from one archive, the same as the largest GraphQL request.
+const MAX_ARCHIVE_SIZE: u64 = 16 * 1024 * 1024;
+
+#[derive(SimpleObject)]
+pub struct NoteImport {
+    /// The name of the imported file, including its folder if it was in an archive.
+    pub file: String,
+
+    /// The note created from the file. Null if the file could not be imported.
+    pub note: Option<Note>,
+
+    /// Parts of the file that could not be imported as-is.
+    pub warnings: Vec<String>,
+
+    /// Why the file could not be imported. Null if the note was created.
+    pub error: Option<String>,
+}
+
+/// The fields a note can set in its front matter.
+#[derive(Default)]
+struct FrontMatter {
+    title: Option<String>,
+    date: Option<String>,
+    subject: Option<String>,
+}
+
+/// Splits YAML front matter from the start of a Markdown file.
+/// Only `key: value` pairs are understood, which covers everything a note can set.
+fn front_matter<'a>(text: &'a str, warnings: &mut Vec<String>) -> (FrontMatter, &'a str) {
+    let mut fields = FrontMatter::default();
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return (fields, text);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            return (fields, &rest[offset..]);
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = if value.starts_with('"') {
+            serde_json::from_str(value).unwrap_or_else(|_| value.trim_matches('"').to_string())
+        } else if value.len() > 1 && value.starts_with('\'') && value.ends_with('\'') {
+            value[1..value.len() - 1].replace("''", "'")
+        } else {
+            value.to_string()
+        };
+        match key.trim() {
+            "title" => fields.title = Some(value),
+            "date" => fields.date = Some(value),
+            "subject" => fields.subject = Some(value),
+            key => warnings.push(format!("The front matter field `{key}` was ignored.")),
+        }
+    }
+    // Without a closing line this was never front matter
+    (FrontMatter::default(), text)
+}
+
+/// Finds one of the user's subjects by its ID or name, preferring subjects that are not archived.
+async fn find_subject(pool: &PgPool, owner: i32, subject: &str) -> Result<Option<i32>> {
+    Ok(query!(/* language=postgresql */ "
+        SELECT id FROM subjects
+        WHERE owner = $1 AND trash IS NULL AND (id::TEXT = $2 OR LOWER(name) = LOWER($2) OR LOWER(name || ' ' || class) = LOWER($2))
+        ORDER BY archived, id DESC
+        LIMIT 1;
+        ", owner, subject.trim())
+        .fetch_optional(pool).await?
+        .map(|subject| subject.id))
+}
+
+/// Creates a note from a Markdown or plain text file.
+async fn import_file(pool: &PgPool, owner: i32, file: &str, bytes: &[u8], subject: Option<i32>, warnings: &mut Vec<String>) -> Result<Note> {
+    let name = file.rsplit('/').next().unwrap_or(file);
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    let text = std::str::from_utf8(bytes).or(Err(Error::new("The file is not UTF-8 text.")))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    // Exported notes are named after their date and title
+    let (mut date, mut title) = match stem.get(..10).map(|prefix| NaiveDate::parse_from_str(prefix, "%Y-%m-%d")) {
+        Some(Ok(date)) => (Some(date), stem[10..].trim_start_matches([' ', '-', '_']).to_string()),
+        _ => (None, stem.to_string()),
+    };
+    let mut subject = subject;
+    let content = match extension.to_ascii_lowercase().as_str() {
+        "txt" | "text" => Node::from_plain_text(text),
+        "md" | "markdown" | "mdown" => {
+            let (fields, body) = front_matter(text, warnings);
+            let (mut document, parse_warnings) = markdown::parse(body).ok_or(Status::BadRequest)?;
+            warnings.extend(parse_warnings);
+            if let Some(value) = fields.date {
+                match value.get(..10).map(|prefix| NaiveDate::parse_from_str(prefix, "%Y-%m-%d")) {
+                    Some(Ok(value)) => date = Some(value),
+                    _ => warnings.push(format!("The date `{value}` is not a valid date.")),
+                }
+            }
+            if let Some(value) = fields.subject {
+                match find_subject(pool, owner, &value).await? {
+                    Some(value) => subject = Some(value),
+                    None => warnings.push(format!("The subject `{value}` was not found.")),
+                }
+            }
+            if let Some(value) = fields.title {
+                title = value;
+            } else if document.content.first().is_some_and(|heading| heading.kind == "heading" && heading.int_attr("level") == Some(1)) {
+                // A leading top-level heading is the title, not part of the content
+                title = document.content.remove(0).text_content();
+                if document.content.is_empty() {
+                    document.content.push(Node::new("paragraph"));
+                }
+            }
+            document
+        }
+        _ => return Err(Error::new("Only Markdown and plain text files can be imported.")),
+    };
+    if title.chars().count() > 255 {
+        warnings.push("The title was shortened to 255 characters.".to_string());
+        title = title.chars().take(255).collect();
+    }
+    create_note(pool, owner, title.trim(), Some(serde_json::to_value(content)?), subject, date, None).await
+}
+
+/// Reads the files in a zip archive, skipping folders and hidden files.
+/// Fails if the archive has more than [`MAX_FILES`] files or decompresses to more than [`MAX_ARCHIVE_SIZE`] bytes.
+fn read_archive(data: Vec<u8>) -> zip::result::ZipResult<Vec<(String, Option<Vec<u8>>)>> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut files = Vec::new();
+    let mut total = 0;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        if file.is_dir() || name.split('/').any(|part| part.starts_with('.') || part == "__MACOSX") {
+            continue;
+        }
+        if files.len() == MAX_FILES {
+            return Err(zip::result::ZipError::InvalidArchive("The archive contains too many files"));
+        }
+        let mut content = Vec::new();
+        file.take(MAX_FILE_SIZE + 1).read_to_end(&mut content)?;
+        let size = u64::try_from(content.len()).unwrap_or(u64::MAX);
+        total += size;
+        if total > MAX_ARCHIVE_SIZE {
+            return Err(zip::result::ZipError::InvalidArchive("The archive is too large"));
+        }
+        let too_large = size > MAX_FILE_SIZE;
+        files.push((name, (!too_large).then_some(content)));
+    }
+    Ok(files)
+}
+
+/// Imports notes from a Markdown or plain text file, or a zip archive of them, for the user.
+/// Returns a report for each file, in the order they were read.
+pub async fn import(pool: &PgPool, owner: i32, file_name: &str, data: Vec<u8>, subject: Option<i32>) -> Result<Vec<NoteImport>> {
+    let files = if data.starts_with(b"PK\x03\x04") || file_name.to_ascii_lowercase().ends_with(".zip") {
+        // Decompressing is slow, so it is kept off the async runtime's threads
+        tokio::task::spawn_blocking(move || read_archive(data)).await?.or(Err(Status::BadRequest))?
+    } else {
+        let too_large = u64::try_from(data.len()).map_or(true, |size| size > MAX_FILE_SIZE);
+        vec![(file_name.to_string(), (!too_large).then_some(data))]
+    };
+    let mut imports = Vec::with_capacity(files.len());
+    for (file, data) in files {
+        let mut warnings = Vec::new();
+        let result = match data {
+            Some(data) => import_file(pool, owner, &file, &data, subject, &mut warnings).await,
+            None => Err(Error::new("The file is larger than 1 MiB.")),
+        };
+        let (note, error) = match result {
+            Ok(note) => (Some(note), None),
+            Err(error) => (None, Some(error.message)),
+        };
+        imports.push(NoteImport {
+            file,
+            note,
+            warnings,
+            error,
+        });
+    }
+    Ok(imports)
+}
@@ -4,19 +4,50 @@ mod session;
 mod subject;
 mod todo;
 mod user;
-mod note;
+pub(super) mod note;
+mod grade_scale;
+mod calendar;
+mod timetable;
+mod import;
+mod quick_add;
+mod sharing;
+mod tag;
+mod assessment;
+mod flashcard;
+mod template;
+mod trash;
 
-use async_graphql::{Context, Object, Result};
+use std::io::Read;
+use async_graphql::{Context, Object, Result, Upload};
 use chrono::NaiveDate;
 use rocket::http::Status;
+use serde_json::json;
 use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::mutation::assessment::AssessmentMutation;
+use crate::api::graphql::mutation::calendar::AcademicYearMutation;
+use crate::api::graphql::mutation::flashcard::{CardMutation, DeckMutation};
+use crate::api::graphql::mutation::grade_scale::GradeScaleMutation;
+use crate::api::graphql::mutation::import::NoteImport;
 use crate::api::graphql::mutation::note::NoteMutation;
+use crate::api::graphql::mutation::quick_add::QuickAddedTodo;
+use crate::api::graphql::mutation::sharing::UserGroupMutation;
 use crate::api::graphql::mutation::subject::SubjectMutation;
+use crate::api::graphql::mutation::tag::{check_tag_name, name_conflict, TagMutation};
+use crate::api::graphql::mutation::template::NoteTemplateMutation;
 use crate::api::graphql::mutation::todo::TodoMutation;
+use crate::api::graphql::mutation::trash::TrashEntryMutation;
 use crate::api::graphql::mutation::user::UserMutation;
+use crate::api::graphql::query::calendar::AcademicYear;
+use crate::api::graphql::query::flashcard::{Card, Deck, ReviewGrade};
+use crate::api::graphql::query::grade_scale::{GradeBandInput, GradeScale, GradeScaleKind};
 use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::sharing::{NoteRole, UserGroup};
 use crate::api::graphql::query::subject::Subject;
-use crate::api::graphql::query::todo::Todo;
+use crate::api::graphql::query::tag::Tag;
+use crate::api::graphql::query::template::NoteTemplate;
+use crate::api::graphql::query::todo::{Todo, TodoPriority};
+use crate::api::graphql::recurrence::{normalise, RecurrenceValidator};
+use crate::api::graphql::tiptap::schema::validate_content;
 use crate::auth::User;
 
 pub struct MutationRoot;
@@ -24,16 +55,17 @@ pub struct MutationRoot;
 #[Object]
 impl MutationRoot {
     /// Get a note for modification.
-    /// Requires authentication.
+    /// Requires authentication and access to the note. Most changes also require the editor or owner role.
     async fn note(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the note to modify.")] id: i32) -> Result<NoteMutation> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query(/* language=postgresql */ "SELECT 1 FROM notes WHERE owner = $1 AND id = $2 LIMIT 1;")
-            .bind(user.id)
-            .bind(id)
-            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
-        Ok(NoteMutation(id))
+        let role = NoteRole::of(ctx.data::<PgPool>()?, id, user.id).await?.ok_or(Status::NotFound)?;
+        Ok(NoteMutation {
+            id,
+            user: user.id,
+            role,
+        })
     }
 
     /// Creates a new note. Returns the newly created note.
@@ -44,22 +76,84 @@ impl MutationRoot {
         #[graphql(desc = r#"The title of the note. Default: """#, default = "", validator(max_length = 255))] title: String,
         #[graphql(desc = r#"The content of the note. Default: {"type": "doc", "content": []}"#, default)] content: Option<serde_json::Value>,
         #[graphql(desc = "The subject of the note. Default: null", default)] subject: Option<i32>,
-        // TODO: Date
+        #[graphql(desc = "The date of the note's class. Default: today, or the next school day if today is not a school day", default)] date: Option<NaiveDate>,
+        #[graphql(desc = "The ID of the template to fill in the note's title and content from, which cannot be given with content. Default: the subject's default template, if any", default)] template: Option<i32>,
     ) -> Result<Note> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query_as!(Note, /* language=postgresql */ "INSERT INTO notes (owner, title, content, subject) VALUES ($1, $2, $3, $4) RETURNING *;", user.id, title, content, subject)
+        note::create_note(ctx.data::<PgPool>()?, user.id, &title, content, subject, date, template).await
+    }
+
+    /// Get a note template for modification.
+    /// Requires authentication.
+    async fn note_template(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the template to modify.")] id: i32) -> Result<NoteTemplateMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM note_templates WHERE owner = $1 AND id = $2 LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(NoteTemplateMutation(id))
+    }
+
+    /// Creates a new note template. Returns the newly created template.
+    /// The title and text of the content can contain the placeholders `{{date}}`, `{{subject.name}}` and `{{subject.class}}`,
+    /// and the content can also contain `{{title}}`.
+    /// Requires authentication.
+    async fn create_note_template(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the template.", validator(max_length = 255))] name: String,
+        #[graphql(desc = r#"The title of notes created from the template. Default: """#, default = "", validator(max_length = 255))] title: String,
+        #[graphql(desc = r#"The content of notes created from the template. Default: {"type": "doc", "content": []}"#, default)] content: Option<serde_json::Value>,
+    ) -> Result<NoteTemplate> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let content = match content {
+            Some(content) => validate_content(&content)?,
+            None => json!({"type": "doc", "content": []}),
+        };
+        query_as!(NoteTemplate, /* language=postgresql */ "INSERT INTO note_templates (owner, name, title, content) VALUES ($1, $2, $3, $4) RETURNING *;", user.id, name, title, content)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
 
+    /// Imports notes from a Markdown or plain text file, or a zip archive of them. Returns a report for each file.
+    /// Markdown front matter can set a note's `title`, `date` and `subject`, which is a subject's ID or name.
+    /// Fails with 400 Bad Request if an archive has more than 1000 files or more than 16 MiB of them.
+    /// Requires authentication.
+    async fn import_notes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The file to import.")] file: Upload,
+        #[graphql(desc = "The subject of notes whose front matter does not set one. Default: null", default)] subject: Option<i32>,
+    ) -> Result<Vec<NoteImport>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        if let Some(subject) = subject {
+            query(/* language=postgresql */ "SELECT 1 FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;")
+                .bind(user.id)
+                .bind(subject)
+                .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        }
+        let file = file.value(ctx)?;
+        let file_name = file.filename.clone();
+        let mut data = Vec::new();
+        file.into_read().read_to_end(&mut data)?;
+        import::import(pool, user.id, &file_name, data, subject).await
+    }
+
     /// Get a subject for modification.
     /// Requires authentication.
     async fn subject(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the subject to modify.")] id: i32) -> Result<SubjectMutation> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query(/* language=postgresql */ "SELECT 1 FROM subjects WHERE owner = $1 AND id = $2 LIMIT 1;")
+        query(/* language=postgresql */ "SELECT 1 FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;")
             .bind(user.id)
             .bind(id)
             .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
@@ -75,12 +169,20 @@ impl MutationRoot {
         #[graphql(desc = "The class of the subject.", validator(max_length = 16))] class: String,
         #[graphql(desc = "Whether the subject is active. Default: true", default = true)] active: bool,
         #[graphql(desc = "The subject's Google Classroom ID. Default: null", default, validator(max_length = 16))] google_classroom_id: Option<String>,
+        #[graphql(desc = "The ID of the academic year the subject is taken during. Default: null", default)] year: Option<i32>,
     ) -> Result<Subject> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query_as!(Subject, /* language=postgresql */ "INSERT INTO subjects (owner, name, class, active, google_classroom_id) VALUES ($1, $2, $3, $4, $5) RETURNING *;", user.id, name, class, active, google_classroom_id)
-            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+        let pool = ctx.data::<PgPool>()?;
+        if let Some(year) = year {
+            query(/* language=postgresql */ "SELECT 1 FROM academic_years WHERE owner = $1 AND id = $2 LIMIT 1;")
+                .bind(user.id)
+                .bind(year)
+                .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        }
+        query_as!(Subject, /* language=postgresql */ "INSERT INTO subjects (owner, name, class, active, google_classroom_id, year) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *;", user.id, name, class, active, google_classroom_id, year)
+            .fetch_one(pool).await.map_err(Into::into)
     }
 
     /// Get a to-do for modification.
@@ -89,7 +191,7 @@ impl MutationRoot {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query(/* language=postgresql */ "SELECT 1 FROM todos WHERE owner = $1 AND id = $2 LIMIT 1;")
+        query(/* language=postgresql */ "SELECT 1 FROM todos WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;")
             .bind(user.id)
             .bind(id)
             .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
@@ -98,6 +200,7 @@ impl MutationRoot {
 
     /// Creates a new to-do. Returns the newly created to-do.
     /// Requires authentication.
+    #[allow(clippy::too_many_arguments)]
     async fn create_todo(
         &self,
         ctx: &Context<'_>,
@@ -106,12 +209,256 @@ impl MutationRoot {
         #[graphql(desc = "The due date of the to-do. Default: null", default)] due: Option<NaiveDate>,
         #[graphql(desc = "The archived status of the to-do. Default: false", default = false)] archived: bool,
         #[graphql(desc = "The to-do's subject's ID. Default: null", default)] subject: Option<i32>,
-        #[graphql(default = false)] standing: bool,
+        #[graphql(desc = "The standing status of the to-do. Default: false", default = false)] standing: bool,
+        #[graphql(desc = "The iCalendar recurrence rule of the to-do, such as `FREQ=WEEKLY;BYDAY=MO`. Default: null", default, validator(custom = "RecurrenceValidator"))] recurrence: Option<String>,
+        #[graphql(desc = "How important the to-do is. Default: NORMAL", default_with = "TodoPriority::Normal")] priority: TodoPriority,
+        #[graphql(desc = "How many minutes the to-do is expected to take. Default: null", default, validator(minimum = 1))] estimated_minutes: Option<i32>,
     ) -> Result<Todo> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query_as!(Todo, /* language=postgresql */ "INSERT INTO todos (owner, title, completed, due, archived, subject, standing) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;", user.id, title, completed, due, archived, subject, standing)
+        query_as!(Todo, /* language=postgresql */ "
+            INSERT INTO todos (owner, title, completed, due, archived, subject, standing, recurrence, priority, estimated_minutes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING *;
+            ", user.id, title, completed, due, archived, subject, standing, normalise(recurrence)?, priority as i16, estimated_minutes)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Creates a to-do from a line of text such as `finish chem prac report fri #CHEM !high every week`.
+    /// Understands due dates such as `fri`, `tomorrow`, `in 3 days` or `12/11`, `#` followed by a subject's class or a tag's name,
+    /// priorities such as `!high`, recurrences such as `every week` or `every school day`, and estimates such as `~30m`.
+    /// Everything else is the title. Returns the to-do and how the text was read.
+    /// Requires authentication.
+    async fn quick_add_todo(&self, ctx: &Context<'_>, #[graphql(desc = "The text to create the to-do from.", validator(max_length = 1000))] text: String) -> Result<QuickAddedTodo> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        quick_add::quick_add(ctx.data::<PgPool>()?, user.id, &text).await
+    }
+
+    /// Get an assessment for modification.
+    /// Requires authentication.
+    async fn assessment(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the assessment to modify.")] id: i32) -> Result<AssessmentMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM assessments WHERE owner = $1 AND id = $2 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(AssessmentMutation(id))
+    }
+
+    /// Get a flashcard deck for modification.
+    /// Requires authentication.
+    async fn deck(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the deck to modify.")] id: i32) -> Result<DeckMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM decks WHERE owner = $1 AND id = $2 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(DeckMutation(id))
+    }
+
+    /// Creates a new flashcard deck. Returns the newly created deck.
+    /// Requires authentication.
+    async fn create_deck(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the deck.", validator(max_length = 255))] name: String,
+        #[graphql(desc = "The ID of the subject the deck is for.")] subject: i32,
+    ) -> Result<Deck> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query(/* language=postgresql */ "SELECT 1 FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;")
+            .bind(user.id)
+            .bind(subject)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        query_as!(Deck, /* language=postgresql */ "INSERT INTO decks (owner, subject, name) VALUES ($1, $2, $3) RETURNING *;", user.id, subject, name)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Get a flashcard for modification.
+    /// Requires authentication.
+    async fn card(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the card to modify.")] id: i32) -> Result<CardMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM cards JOIN decks ON decks.id = cards.deck WHERE decks.owner = $1 AND cards.id = $2 AND decks.subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(CardMutation(id))
+    }
+
+    /// Records a review of a flashcard and schedules its next review. Returns the updated card.
+    /// Requires authentication.
+    async fn review_card(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the card that was reviewed.")] id: i32,
+        #[graphql(desc = "How well the card was remembered.")] grade: ReviewGrade,
+    ) -> Result<Card> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query(/* language=postgresql */ "SELECT 1 FROM cards JOIN decks ON decks.id = cards.deck WHERE decks.owner = $1 AND cards.id = $2 AND decks.subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        flashcard::review(pool, id, grade, user.today()).await
+    }
+
+    /// Get a tag for modification.
+    /// Requires authentication.
+    async fn tag(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the tag to modify.")] id: i32) -> Result<TagMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM tags WHERE owner = $1 AND id = $2 LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(TagMutation(id))
+    }
+
+    /// Creates a new tag for notes, to-dos and assessments. Returns the newly created tag.
+    /// Fails with 409 Conflict if another of the authenticated user's tags has the name.
+    /// Requires authentication.
+    async fn create_tag(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the tag.", validator(min_length = 1, max_length = 64))] name: String,
+        #[graphql(desc = "The colour of the tag as a hex code. Default: #808080", default = "#808080", validator(regex = "^#[0-9a-fA-F]{6}$"))] colour: String,
+    ) -> Result<Tag> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        check_tag_name(pool, user.id, &name, None).await?;
+        query_as!(Tag, /* language=postgresql */ "INSERT INTO tags (owner, name, colour) VALUES ($1, $2, $3) RETURNING *;", user.id, name, colour.to_lowercase())
+            .fetch_one(pool).await.map_err(name_conflict)
+    }
+
+    /// Get an entry in the trash to restore or permanently delete.
+    /// Requires authentication.
+    async fn trash_entry(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the trash entry.")] id: i32) -> Result<TrashEntryMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM trash WHERE owner = $1 AND id = $2 LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(TrashEntryMutation(id))
+    }
+
+    /// Permanently deletes everything in the authenticated user's trash. Always returns true or an error.
+    /// Requires authentication.
+    async fn empty_trash(&self, ctx: &Context<'_>) -> Result<bool> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query!(/* language=postgresql */ "DELETE FROM trash WHERE owner = $1;", user.id)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Get a grade scale for modification.
+    /// Requires authentication.
+    async fn grade_scale(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the grade scale to modify.")] id: i32) -> Result<GradeScaleMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM grade_scales WHERE owner = $1 AND id = $2 LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(GradeScaleMutation(id))
+    }
+
+    /// Creates a new grade scale. Returns the newly created grade scale.
+    /// Requires authentication.
+    async fn create_grade_scale(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the grade scale.", validator(max_length = 255))] name: String,
+        #[graphql(desc = "The kind of grade scale. Default: LETTER", default_with = "GradeScaleKind::Letter")] kind: GradeScaleKind,
+        #[graphql(desc = "The bands of the grade scale. Default: the standard A–E bands for LETTER and GPA scales, none for CUSTOM scales", default)] bands: Option<Vec<GradeBandInput>>,
+    ) -> Result<GradeScale> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let mut tx = ctx.data::<PgPool>()?.begin().await?;
+        let scale = query_as!(GradeScale, /* language=postgresql */ "INSERT INTO grade_scales (owner, name, kind) VALUES ($1, $2, $3) RETURNING *;", user.id, name, kind as i16)
+            .fetch_one(&mut *tx).await?;
+        grade_scale::set_bands(&mut tx, scale.id, bands.unwrap_or_else(|| kind.default_bands())).await?;
+        tx.commit().await?;
+        Ok(scale)
+    }
+
+    /// Get an academic year for modification.
+    /// Requires authentication.
+    async fn academic_year(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the academic year to modify.")] id: i32) -> Result<AcademicYearMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM academic_years WHERE owner = $1 AND id = $2 LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(AcademicYearMutation(id))
+    }
+
+    /// Creates a new academic year. Returns the newly created academic year.
+    /// Requires authentication.
+    async fn create_academic_year(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the academic year.", validator(max_length = 255))] name: String,
+        #[graphql(desc = "The first day of the academic year.")] start_date: NaiveDate,
+        #[graphql(desc = "The last day of the academic year.")] end_date: NaiveDate,
+    ) -> Result<AcademicYear> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        if start_date > end_date {
+            return Err(Status::BadRequest.into());
+        }
+        query_as!(AcademicYear, /* language=postgresql */ "INSERT INTO academic_years (owner, name, start_date, end_date) VALUES ($1, $2, $3, $4) RETURNING *;", user.id, name, start_date, end_date)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Get a group for modification.
+    /// Requires authentication and that the authenticated user manages the group.
+    async fn group(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the group to modify.")] id: i32) -> Result<UserGroupMutation> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query(/* language=postgresql */ "SELECT 1 FROM user_groups WHERE owner = $1 AND id = $2 LIMIT 1;")
+            .bind(user.id)
+            .bind(id)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(UserGroupMutation(id))
+    }
+
+    /// Creates a new group of users that notes can be shared with. Returns the newly created group.
+    /// Requires authentication.
+    async fn create_group(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The name of the group.", validator(max_length = 255))] name: String,
+    ) -> Result<UserGroup> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query_as!(UserGroup, /* language=postgresql */ "INSERT INTO user_groups (owner, name) VALUES ($1, $2) RETURNING *;", user.id, name)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
 
@@ -1,38 +1,305 @@
+use std::sync::Arc;
 use async_graphql::{Context, Object, Result};
-use sqlx::{PgPool, query_as};
+use chrono::NaiveDate;
+use rocket::http::Status;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, query, query_as, Transaction};
+use crate::api::graphql::collaboration::{Collaboration, NoteMerge};
+use crate::api::graphql::mutation::tag::check_tags;
+use crate::api::graphql::mutation::trash::trash_note;
+use crate::api::graphql::query::calendar::current_school_day;
 use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::sharing::{NoteGrant, NoteRole};
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::template::NoteTemplate;
+use crate::api::graphql::tiptap::schema::validate_content;
 
-pub struct NoteMutation(pub i32);
+/// Edits made within this many minutes of the last revision are coalesced into it.
+const REVISION_INTERVAL_MINUTES: i32 = 10;
+
+/// The number of revisions kept for each note.
+const MAX_REVISIONS: i64 = 50;
+
+pub struct NoteMutation {
+    /// The ID of the note.
+    pub id: i32,
+    /// The user modifying the note.
+    pub user: i32,
+    /// What the user can do with the note.
+    pub role: NoteRole,
+}
+
+/// Creates a note owned by the user. Returns the newly created note.
+/// Notes without a date are dated today in the owner's time zone, or the next school day if today is not a school day.
+/// Notes without content are filled in from the template, or the subject's default template, with its placeholders replaced.
+/// Notes without either are empty documents. Fails with 400 Bad Request if both content and a template are given.
+/// Content is validated against the editor's schema.
+pub async fn create_note(pool: &PgPool, owner: i32, title: &str, content: Option<serde_json::Value>, subject: Option<i32>, date: Option<NaiveDate>, template: Option<i32>) -> Result<Note> {
+    if content.is_some() && template.is_some() {
+        return Err(Status::BadRequest.into());
+    }
+    let subject = match subject {
+        Some(subject) => Some(query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;", owner, subject)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?),
+        None => None,
+    };
+    let date = match date {
+        Some(date) => date,
+        None => current_school_day(pool, owner).await?,
+    };
+    let template = match template {
+        Some(template) => Some(query_as!(NoteTemplate, /* language=postgresql */ "SELECT * FROM note_templates WHERE owner = $1 AND id = $2 LIMIT 1;", owner, template)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?),
+        None => match subject.as_ref().and_then(|subject| subject.default_template) {
+            Some(template) if content.is_none() => query_as!(NoteTemplate, /* language=postgresql */ "SELECT * FROM note_templates WHERE id = $1 LIMIT 1;", template)
+                .fetch_optional(pool).await?,
+            _ => None,
+        },
+    };
+    let (title, content) = match (content, template) {
+        (Some(content), _) => (title.to_string(), validate_content(&content)?),
+        (None, Some(template)) => template.render(title, date, subject.as_ref())?,
+        (None, None) => (title.to_string(), json!({"type": "doc", "content": []})),
+    };
+    query_as!(Note, /* language=postgresql */ "INSERT INTO notes (owner, title, content, subject, date, last_edited_by) VALUES ($1, $2, $3, $4, $5, $1) RETURNING *;", owner, title, content, subject.map(|subject| subject.id), date)
+        .fetch_one(pool).await.map_err(Into::into)
+}
+
+/// Saves the note's current content as a revision before it is replaced with `replacement`.
+/// Nothing is saved if the content is unchanged, or if `coalesce` is set and a revision was saved recently.
+pub async fn save_revision(tx: &mut Transaction<'_, Postgres>, note: i32, replacement: Option<&serde_json::Value>, coalesce: bool) -> Result<()> {
+    query!(/* language=postgresql */ "
+        INSERT INTO note_revisions (note, content, word_count)
+        SELECT id, content, tiptap_word_count(content)
+        FROM notes
+        WHERE id = $1
+            AND content IS DISTINCT FROM $2
+            AND NOT ($3 AND EXISTS (
+                SELECT 1 FROM note_revisions
+                WHERE note = $1 AND created_at > NOW() - make_interval(mins => $4)
+            ));
+        ", note, replacement, coalesce, REVISION_INTERVAL_MINUTES)
+        .execute(&mut **tx).await?;
+    query!(/* language=postgresql */ "
+        DELETE FROM note_revisions
+        WHERE note = $1 AND id NOT IN (
+            SELECT id FROM note_revisions
+            WHERE note = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2
+        );
+        ", note, MAX_REVISIONS)
+        .execute(&mut **tx).await?;
+    Ok(())
+}
+
+impl NoteMutation {
+    /// Fails unless the user can edit the note.
+    fn require_editor(&self) -> Result<()> {
+        if self.role >= NoteRole::Editor {
+            Ok(())
+        } else {
+            Err(Status::Forbidden.into())
+        }
+    }
+
+    /// Fails unless the user is one of the note's owners.
+    fn require_owner(&self) -> Result<()> {
+        if self.role == NoteRole::Owner {
+            Ok(())
+        } else {
+            Err(Status::Forbidden.into())
+        }
+    }
+}
 
 #[Object]
 impl NoteMutation {
-    /// Delete the note. Returns the deleted note.
+    /// Moves the note to its owner's trash, where it can be restored until it is purged. Returns the deleted note.
+    /// Requires the owner role.
     async fn delete(&self, ctx: &Context<'_>) -> Result<Note> {
-        query_as!(Note, /* language=postgresql */ "DELETE FROM notes WHERE id = $1 RETURNING *;", self.0)
-            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+        self.require_owner()?;
+        let pool = ctx.data::<PgPool>()?;
+        let note = trash_note(pool, self.id).await?;
+        ctx.data::<Arc<Collaboration>>()?.close(pool, self.id).await;
+        Ok(note)
     }
     
     /// Updates the note’s content. Returns the updated note.
+    /// The previous content is kept as a revision.
+    /// The content must be a valid `TipTap` document, otherwise the error lists every problem with its location.
+    /// Requires the editor or owner role.
     async fn content(&self, ctx: &Context<'_>, #[graphql(desc = "The new content of the note.")] content: Option<serde_json::Value>) -> Result<Note> {
-        query_as!(Note, /* language=postgresql */ "UPDATE notes SET content = $2 WHERE id = $1 RETURNING *;", self.0, content)
-            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+        self.require_editor()?;
+        let content = content.as_ref().map(validate_content).transpose()?;
+        let pool = ctx.data::<PgPool>()?;
+        let collaboration = ctx.data::<Arc<Collaboration>>()?;
+        let lock = collaboration.lock(self.id).await;
+        let mut tx = pool.begin().await?;
+        save_revision(&mut tx, self.id, content.as_ref(), true).await?;
+        let note = query_as!(Note, /* language=postgresql */ "UPDATE notes SET content = $2, last_edited_by = $3 WHERE id = $1 RETURNING *;", self.id, content, self.user)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        collaboration.replace(pool, lock, &note.content).await?;
+        Ok(note)
+    }
+
+    /// Merges changes a client made to the note's collaborative document, which other clients receive through `SubscriptionRoot.noteEvents`.
+    /// Changes made offline can be sent once the client reconnects, and merge with everyone else's without conflicts.
+    /// The merged document is written to the note's content every 30 seconds.
+    /// Returns the changes the client is missing. Fails with 409 Conflict if the note's content has since been replaced, in which case the client must start again from the new document.
+    /// The client must be subscribed to `SubscriptionRoot.noteEvents` by the authenticated user.
+    /// Requires the editor or owner role.
+    async fn merge_changes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID the client subscribed with.")] client_id: String,
+        #[graphql(desc = "The Automerge changes, base64-encoded.")] changes: Vec<String>,
+        #[graphql(desc = "The hashes of the latest changes the client had received before making these.")] heads: Vec<String>,
+    ) -> Result<NoteMerge> {
+        self.require_editor()?;
+        ctx.data::<Arc<Collaboration>>()?.merge(ctx.data::<PgPool>()?, self.id, self.user, &client_id, &changes, &heads).await
+    }
+
+    /// Shares the client's selection with the note's other collaborators. Always returns true or an error.
+    /// The client must be subscribed to `SubscriptionRoot.noteEvents` by the authenticated user.
+    /// Any role can share its selection.
+    async fn select(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID the client subscribed with.")] client_id: String,
+        #[graphql(desc = "The client's selection, in whatever form its editor uses. Default: null", default)] selection: Option<serde_json::Value>,
+    ) -> Result<bool> {
+        ctx.data::<Arc<Collaboration>>()?.select(self.id, self.user, &client_id, selection).await?;
+        Ok(true)
+    }
+
+    /// Replaces the note's content with that of a revision. Returns the updated note.
+    /// The replaced content is kept as a revision.
+    /// Requires the editor or owner role.
+    async fn restore_revision(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the revision to restore.")] id: i32) -> Result<Note> {
+        self.require_editor()?;
+        let pool = ctx.data::<PgPool>()?;
+        let collaboration = ctx.data::<Arc<Collaboration>>()?;
+        let lock = collaboration.lock(self.id).await;
+        let mut tx = pool.begin().await?;
+        let content = query!(/* language=postgresql */ "SELECT content FROM note_revisions WHERE note = $1 AND id = $2 LIMIT 1;", self.id, id)
+            .fetch_optional(&mut *tx).await?.ok_or(Status::NotFound)?.content;
+        let content = validate_content(&content)?;
+        save_revision(&mut tx, self.id, Some(&content), false).await?;
+        let note = query_as!(Note, /* language=postgresql */ "UPDATE notes SET content = $2, last_edited_by = $3 WHERE id = $1 RETURNING *;", self.id, content, self.user)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        collaboration.replace(pool, lock, &note.content).await?;
+        Ok(note)
     }
 
     /// Updates the note's date. Returns the updated note.
+    /// Requires the editor or owner role.
     async fn date(&self, ctx: &Context<'_>, #[graphql(desc = "The new date of the note.")] date: Option<chrono::NaiveDate>) -> Result<Note> {
-        query_as!(Note, /* language=postgresql */ "UPDATE notes SET date = $2 WHERE id = $1 RETURNING *;", self.0, date)
+        self.require_editor()?;
+        query_as!(Note, /* language=postgresql */ "UPDATE notes SET date = $2, last_edited_by = $3 WHERE id = $1 RETURNING *;", self.id, date, self.user)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
 
     /// Updates the note's title. Returns the updated note.
+    /// Requires the editor or owner role.
     async fn title(&self, ctx: &Context<'_>, #[graphql(desc = "The new title of the note.")] title: String) -> Result<Note> {
-        query_as!(Note, /* language=postgresql */ "UPDATE notes SET title = $2 WHERE id = $1 RETURNING *;", self.0, title)
+        self.require_editor()?;
+        query_as!(Note, /* language=postgresql */ "UPDATE notes SET title = $2, last_edited_by = $3 WHERE id = $1 RETURNING *;", self.id, title, self.user)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
 
     /// Updates the note's subject. Returns the updated note.
+    /// The subject must belong to the note's owner.
+    /// Requires the owner role.
     async fn subject(&self, ctx: &Context<'_>, #[graphql(desc = "The new subject of the note.")] subject: Option<i32>) -> Result<Note> {
-        query_as!(Note, /* language=postgresql */ "UPDATE notes SET subject = $2 WHERE id = $1 RETURNING *;", self.0, subject)
-            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+        self.require_owner()?;
+        let pool = ctx.data::<PgPool>()?;
+        if let Some(subject) = subject {
+            query(/* language=postgresql */ "SELECT 1 FROM subjects JOIN notes ON notes.owner = subjects.owner WHERE notes.id = $1 AND subjects.id = $2 AND subjects.trash IS NULL LIMIT 1;")
+                .bind(self.id)
+                .bind(subject)
+                .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        }
+        query_as!(Note, /* language=postgresql */ "UPDATE notes SET subject = $2 WHERE id = $1 RETURNING *;", self.id, subject)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Replaces the authenticated user's tags on the note. Returns the updated note.
+    /// Other users' tags on the note are kept.
+    /// Tags are personal, so any role can tag the note.
+    async fn tags(&self, ctx: &Context<'_>, #[graphql(desc = "The IDs of the note's new tags.")] tags: Vec<i32>) -> Result<Note> {
+        let pool = ctx.data::<PgPool>()?;
+        check_tags(pool, self.user, &tags).await?;
+        let mut tx = pool.begin().await?;
+        query!(/* language=postgresql */ "DELETE FROM note_tags WHERE note = $1 AND tag IN (SELECT id FROM tags WHERE owner = $2);", self.id, self.user)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "INSERT INTO note_tags (note, tag) SELECT $1, UNNEST($2::INTEGER[]) ON CONFLICT DO NOTHING;", self.id, &tags)
+            .execute(&mut *tx).await?;
+        let note = query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE id = $1 LIMIT 1;", self.id)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(note)
+    }
+
+    /// Shares the note with another user, or changes their role if it is already shared with them. Returns the grant.
+    /// Requires the owner role.
+    async fn share(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The username of the user to share the note with.")] username: String,
+        #[graphql(desc = "What the user can do with the note.")] role: NoteRole,
+    ) -> Result<NoteGrant> {
+        self.require_owner()?;
+        let pool = ctx.data::<PgPool>()?;
+        let user = query!(/* language=postgresql */ "SELECT id FROM users WHERE username = $1 LIMIT 1;", username)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?.id;
+        // Nothing is inserted for the note's owner, who cannot be given a lesser role
+        query_as!(NoteGrant, /* language=postgresql */ "
+            INSERT INTO note_grants (note, user_id, role)
+            SELECT $1, $2, $3 FROM notes WHERE id = $1 AND owner <> $2
+            ON CONFLICT (note, user_id) DO UPDATE SET role = excluded.role
+            RETURNING *;
+            ", self.id, user, role as i16)
+            .fetch_optional(pool).await?.ok_or(Status::BadRequest.into())
+    }
+
+    /// Shares the note with the members of a group the authenticated user manages or belongs to, or changes the group's role if it is already shared with it.
+    /// Returns the grant.
+    /// Requires the owner role.
+    async fn share_with_group(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the group to share the note with.")] group: i32,
+        #[graphql(desc = "What the group's members can do with the note.")] role: NoteRole,
+    ) -> Result<NoteGrant> {
+        self.require_owner()?;
+        let pool = ctx.data::<PgPool>()?;
+        query(/* language=postgresql */ "
+            SELECT 1 FROM user_groups
+            WHERE id = $2 AND (owner = $1 OR EXISTS (SELECT 1 FROM user_group_members WHERE group_id = $2 AND user_id = $1))
+            LIMIT 1;
+            ")
+            .bind(self.user)
+            .bind(group)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        query_as!(NoteGrant, /* language=postgresql */ "
+            INSERT INTO note_grants (note, group_id, role) VALUES ($1, $2, $3)
+            ON CONFLICT (note, group_id) DO UPDATE SET role = excluded.role
+            RETURNING *;
+            ", self.id, group, role as i16)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Stops sharing the note with a user or group. Always returns true or an error.
+    /// Requires the owner role.
+    async fn unshare(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the grant to remove.")] grant: i32) -> Result<bool> {
+        self.require_owner()?;
+        query(/* language=postgresql */ "DELETE FROM note_grants WHERE note = $1 AND id = $2 RETURNING 1;")
+            .bind(self.id)
+            .bind(grant)
+            .fetch_optional(ctx.data::<PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(true)
     }
 }
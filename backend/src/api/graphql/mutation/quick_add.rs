@@ -0,0 +1,505 @@
+use async_graphql::{Enum, Result, SimpleObject};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::todo::{Todo, TodoPriority};
+use crate::api::graphql::query::user::today;
+use crate::api::graphql::recurrence::Recurrence;
+
+/// What a part of quick-add text was read as.
+#[derive(Enum, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum QuickAddMeaning {
+    /// Part of the title.
+    Title,
+    /// The due date, such as `fri`, `tomorrow`, `in 3 days` or `12/11`.
+    Due,
+    /// The subject, from `#` and the subject's class.
+    Subject,
+    /// A tag, from `#` and the tag's name.
+    Tag,
+    /// The priority, such as `!high`.
+    Priority,
+    /// How often the to-do recurs, such as `every week` or `every school day`.
+    Recurrence,
+    /// How long the to-do is expected to take, such as `~30m` or `~1h30m`.
+    Estimate,
+}
+
+#[derive(SimpleObject)]
+pub struct QuickAddPart {
+    /// The words of the text.
+    pub text: String,
+
+    /// What the words were read as.
+    pub meaning: QuickAddMeaning,
+
+    /// What the words were understood to mean, such as the full date or the subject's name. Null for the title.
+    pub value: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct QuickAddedTodo {
+    /// The to-do created from the text.
+    pub todo: Todo,
+
+    /// How each part of the text was read, in order.
+    pub parts: Vec<QuickAddPart>,
+}
+
+/// A unit of time in relative dates and recurrences.
+#[derive(Copy, Clone)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Unit {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "day" | "days" => Some(Self::Day),
+            "week" | "weeks" => Some(Self::Week),
+            "month" | "months" => Some(Self::Month),
+            "year" | "years" => Some(Self::Year),
+            _ => None,
+        }
+    }
+
+    fn frequency(self) -> &'static str {
+        match self {
+            Self::Day => "DAILY",
+            Self::Week => "WEEKLY",
+            Self::Month => "MONTHLY",
+            Self::Year => "YEARLY",
+        }
+    }
+
+    /// Adds `count` of the unit to `date`.
+    fn add(self, date: NaiveDate, count: u32) -> Option<NaiveDate> {
+        match self {
+            Self::Day => date.checked_add_days(Days::new(count.into())),
+            Self::Week => date.checked_add_days(Days::new(u64::from(count) * 7)),
+            Self::Month => date.checked_add_months(Months::new(count)),
+            Self::Year => date.checked_add_months(Months::new(count.checked_mul(12)?)),
+        }
+    }
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    match word.trim_end_matches(',') {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "weds" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn month(word: &str) -> Option<u32> {
+    Some(match word {
+        "jan" | "january" => 1,
+        "feb" | "february" => 2,
+        "mar" | "march" => 3,
+        "apr" | "april" => 4,
+        "may" => 5,
+        "jun" | "june" => 6,
+        "jul" | "july" => 7,
+        "aug" | "august" => 8,
+        "sep" | "sept" | "september" => 9,
+        "oct" | "october" => 10,
+        "nov" | "november" => 11,
+        "dec" | "december" => 12,
+        _ => return None,
+    })
+}
+
+/// Parses a day of the month such as `3` or `3rd`.
+fn day_of_month(word: &str) -> Option<u32> {
+    let day = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &word[day.len()..];
+    if !["", "st", "nd", "rd", "th"].contains(&suffix) {
+        return None;
+    }
+    day.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// Finds the first `weekday` after `today`.
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> Option<NaiveDate> {
+    let days = (weekday.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7 + 1;
+    today.checked_add_days(Days::new(days.into()))
+}
+
+/// Finds the first day and month on or after `today`, this year or next.
+fn upcoming(today: NaiveDate, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(today.year(), month, day)
+        .filter(|date| *date >= today)
+        .or_else(|| NaiveDate::from_ymd_opt(today.year() + 1, month, day))
+}
+
+/// Parses a date such as `12/11` or `12/11/2026`, which is day first, on or after `today` if there is no year.
+fn slash_date(today: NaiveDate, word: &str) -> Option<NaiveDate> {
+    let mut parts = word.split('/');
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    match parts.next() {
+        None => upcoming(today, month, day),
+        Some(year) if parts.next().is_none() => {
+            let year = year.parse::<i32>().ok()?;
+            NaiveDate::from_ymd_opt(if year < 100 { 2000 + year } else { year }, month, day)
+        }
+        Some(_) => None,
+    }
+}
+
+/// Parses a due date at the start of `words`. Returns how many words it took and the date.
+fn due(words: &[&str], today: NaiveDate) -> Option<(usize, NaiveDate)> {
+    let first = *words.first()?;
+    let second = words.get(1).copied().unwrap_or_default();
+    match first {
+        "due" | "on" | "by" => return due(&words[1..], today).map(|(count, date)| (count + 1, date)),
+        "today" | "tonight" => return Some((1, today)),
+        "tomorrow" | "tmr" | "tmrw" => return Some((1, today.succ_opt()?)),
+        "next" if second == "week" => return Some((2, next_weekday(today, Weekday::Mon)?)),
+        "next" if second == "month" => return Some((2, today.with_day(1)?.checked_add_months(Months::new(1))?)),
+        "next" => return weekday(second).and_then(|weekday| next_weekday(today, weekday)).map(|date| (2, date)),
+        "in" => {
+            let count = second.parse().ok().filter(|count| *count > 0)?;
+            let unit = Unit::parse(words.get(2)?)?;
+            return unit.add(today, count).map(|date| (3, date));
+        }
+        _ => {}
+    }
+    if let Some(weekday) = weekday(first) {
+        return next_weekday(today, weekday).map(|date| (1, date));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+        return Some((1, date));
+    }
+    if first.contains('/') {
+        return slash_date(today, first).map(|date| (1, date));
+    }
+    if let (Some(day), Some(month)) = (day_of_month(first), month(second)) {
+        return upcoming(today, month, day).map(|date| (2, date));
+    }
+    if let (Some(month), Some(day)) = (month(first), day_of_month(second)) {
+        return upcoming(today, month, day).map(|date| (2, date));
+    }
+    None
+}
+
+/// Parses a recurrence such as `every week`, `every mon and wed` or `fortnightly` at the start of `words`.
+/// Returns how many words it took and the recurrence rule.
+fn recurrence(words: &[&str]) -> Option<(usize, String)> {
+    let (count, rule) = match *words.first()? {
+        "daily" => (1, "FREQ=DAILY".to_string()),
+        "weekly" => (1, "FREQ=WEEKLY".to_string()),
+        "fortnightly" => (1, "FREQ=WEEKLY;INTERVAL=2".to_string()),
+        "monthly" => (1, "FREQ=MONTHLY".to_string()),
+        "yearly" | "annually" => (1, "FREQ=YEARLY".to_string()),
+        "every" => {
+            let rest = &words[1..];
+            match rest {
+                ["school", "day" | "days", ..] => (3, "FREQ=DAILY;X-SCHOOL-DAYS=TRUE".to_string()),
+                ["weekday" | "weekdays", ..] => (2, "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".to_string()),
+                ["other", unit, ..] => (3, format!("FREQ={};INTERVAL=2", Unit::parse(unit)?.frequency())),
+                [unit, ..] if Unit::parse(unit).is_some() => (2, format!("FREQ={}", Unit::parse(unit)?.frequency())),
+                [interval, unit, ..] if interval.parse::<u32>().is_ok() => (3, format!("FREQ={};INTERVAL={interval}", Unit::parse(unit)?.frequency())),
+                _ => {
+                    // A list of weekdays, such as `mon, wed and fri` or `mon,wed`
+                    let mut weekdays = Vec::new();
+                    let mut taken = 0;
+                    while let Some(word) = rest.get(taken) {
+                        let days = word.split(',').filter(|day| !day.is_empty()).map(weekday).collect::<Option<Vec<_>>>();
+                        match days {
+                            Some(days) if !days.is_empty() => weekdays.extend(days),
+                            _ if ["and", "&"].contains(word) && !weekdays.is_empty() && rest.get(taken + 1).copied().and_then(weekday).is_some() => {}
+                            _ => break,
+                        }
+                        taken += 1;
+                    }
+                    if weekdays.is_empty() {
+                        return None;
+                    }
+                    let days = weekdays.iter().map(|weekday| weekday.to_string().to_uppercase()[..2].to_string()).collect::<Vec<_>>();
+                    (taken + 1, format!("FREQ=WEEKLY;BYDAY={}", days.join(",")))
+                }
+            }
+        }
+        _ => return None,
+    };
+    let rule = rule.parse::<Recurrence>().ok()?.to_string();
+    Some((count, rule))
+}
+
+fn priority(word: &str) -> Option<TodoPriority> {
+    match word.strip_prefix('!')? {
+        "low" => Some(TodoPriority::Low),
+        "normal" | "medium" | "med" => Some(TodoPriority::Normal),
+        "high" => Some(TodoPriority::High),
+        "urgent" => Some(TodoPriority::Urgent),
+        _ => None,
+    }
+}
+
+fn priority_name(priority: TodoPriority) -> &'static str {
+    match priority {
+        TodoPriority::Low => "Low",
+        TodoPriority::Normal => "Normal",
+        TodoPriority::High => "High",
+        TodoPriority::Urgent => "Urgent",
+    }
+}
+
+/// Parses an estimate such as `~30m`, `~1h30m`, `~2hrs` or `~45`, which is in minutes.
+fn estimate(word: &str) -> Option<i32> {
+    let mut rest = word.strip_prefix('~')?;
+    if let Ok(minutes) = rest.parse::<i32>() {
+        return Some(minutes).filter(|minutes| *minutes > 0);
+    }
+    let mut minutes = 0i32;
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let number = rest[..digits].parse::<i32>().ok()?;
+        rest = &rest[digits..];
+        let letters = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_alphabetic()).len();
+        let scale = match &rest[..letters] {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60,
+            "m" | "min" | "mins" | "minute" | "minutes" => 1,
+            _ => return None,
+        };
+        rest = &rest[letters..];
+        minutes = minutes.checked_add(number.checked_mul(scale)?)?;
+    }
+    Some(minutes).filter(|minutes| *minutes > 0)
+}
+
+/// What quick-add text was read as, before the to-do is created.
+#[derive(Default)]
+struct Interpretation {
+    title: Vec<String>,
+    due: Option<NaiveDate>,
+    subject: Option<i32>,
+    tags: Vec<i32>,
+    priority: Option<TodoPriority>,
+    recurrence: Option<String>,
+    estimated_minutes: Option<i32>,
+    parts: Vec<QuickAddPart>,
+}
+
+impl Interpretation {
+    fn push(&mut self, words: &[&str], meaning: QuickAddMeaning, value: Option<String>) {
+        let text = words.join(" ");
+        if meaning == QuickAddMeaning::Title {
+            self.title.push(text.clone());
+            // Consecutive title words are one part
+            if let Some(part) = self.parts.last_mut().filter(|part| part.meaning == QuickAddMeaning::Title) {
+                part.text.push(' ');
+                part.text.push_str(&text);
+                return;
+            }
+        }
+        self.parts.push(QuickAddPart { text, meaning, value });
+    }
+}
+
+/// Reads quick-add text such as `finish chem prac report fri #CHEM !high every week`.
+/// Anything that is not understood, or that repeats something already read, is part of the title.
+/// `subjects` are the user's subjects as their ID, class and name, in order of preference, and `tags` their tags' IDs and names.
+fn interpret(text: &str, today: NaiveDate, subjects: &[(i32, String, String)], tags: &[(i32, String)]) -> Interpretation {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    let lower = words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>();
+    let lower = lower.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut interpretation = Interpretation::default();
+    let mut index = 0;
+    while index < words.len() {
+        let word = lower[index];
+        let rest = &lower[index..];
+        let original = &words[index..];
+        if let Some(name) = word.strip_prefix('#').filter(|name| !name.is_empty()) {
+            let subject = subjects.iter().find(|(_, class, _)| class.to_lowercase() == name).filter(|_| interpretation.subject.is_none());
+            let tag = tags.iter().find(|(_, tag)| tag.to_lowercase() == name);
+            if let Some((id, class, subject_name)) = subject {
+                interpretation.subject = Some(*id);
+                interpretation.push(&original[..1], QuickAddMeaning::Subject, Some(format!("{subject_name} ({class})")));
+                index += 1;
+                continue;
+            }
+            if let Some((id, tag)) = tag.filter(|(id, _)| !interpretation.tags.contains(id)) {
+                interpretation.tags.push(*id);
+                interpretation.push(&original[..1], QuickAddMeaning::Tag, Some(tag.clone()));
+                index += 1;
+                continue;
+            }
+        }
+        if let Some(priority) = priority(word).filter(|_| interpretation.priority.is_none()) {
+            interpretation.priority = Some(priority);
+            interpretation.push(&original[..1], QuickAddMeaning::Priority, Some(priority_name(priority).to_string()));
+            index += 1;
+            continue;
+        }
+        if let Some(minutes) = estimate(word).filter(|_| interpretation.estimated_minutes.is_none()) {
+            interpretation.estimated_minutes = Some(minutes);
+            interpretation.push(&original[..1], QuickAddMeaning::Estimate, Some(format!("{minutes} minutes")));
+            index += 1;
+            continue;
+        }
+        if let Some((count, rule)) = recurrence(rest).filter(|_| interpretation.recurrence.is_none()) {
+            interpretation.push(&original[..count], QuickAddMeaning::Recurrence, Some(rule.clone()));
+            interpretation.recurrence = Some(rule);
+            index += count;
+            continue;
+        }
+        if let Some((count, date)) = due(rest, today).filter(|_| interpretation.due.is_none()) {
+            interpretation.due = Some(date);
+            interpretation.push(&original[..count], QuickAddMeaning::Due, Some(date.format("%A %-d %B %Y").to_string()));
+            index += count;
+            continue;
+        }
+        interpretation.push(&original[..1], QuickAddMeaning::Title, None);
+        index += 1;
+    }
+    interpretation
+}
+
+/// Creates a to-do from quick-add text, reading relative dates in the user's time zone.
+/// A recurring to-do without a due date is due on the first day of its recurrence from today.
+/// Fails with 400 Bad Request if there is no title left or it is too long.
+pub async fn quick_add(pool: &PgPool, owner: i32, text: &str) -> Result<QuickAddedTodo> {
+    let today = today(pool, owner).await?;
+    let subjects = query!(/* language=postgresql */ "SELECT id, class, name FROM subjects WHERE owner = $1 AND trash IS NULL ORDER BY archived, id DESC;", owner)
+        .fetch_all(pool).await?
+        .into_iter()
+        .map(|subject| (subject.id, subject.class, subject.name))
+        .collect::<Vec<_>>();
+    let tags = query!(/* language=postgresql */ "SELECT id, name FROM tags WHERE owner = $1;", owner)
+        .fetch_all(pool).await?
+        .into_iter()
+        .map(|tag| (tag.id, tag.name))
+        .collect::<Vec<_>>();
+    let mut interpretation = interpret(text, today, &subjects, &tags);
+    let title = interpretation.title.join(" ");
+    if title.is_empty() || title.chars().count() > 255 {
+        return Err(Status::BadRequest.into());
+    }
+    if let (None, Some(rule)) = (interpretation.due, &interpretation.recurrence) {
+        let rule = rule.parse::<Recurrence>().or(Err(Status::InternalServerError))?;
+        interpretation.due = rule.next(pool, owner, today, today.pred_opt().unwrap_or(today), today, 1).await?.map(|(due, _)| due);
+    }
+    let mut tx = pool.begin().await?;
+    let todo = query_as!(Todo, /* language=postgresql */ "
+        INSERT INTO todos (owner, title, due, subject, priority, recurrence, estimated_minutes)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *;
+        ", owner, title, interpretation.due, interpretation.subject, interpretation.priority.unwrap_or(TodoPriority::Normal) as i16,
+        interpretation.recurrence, interpretation.estimated_minutes)
+        .fetch_one(&mut *tx).await?;
+    query!(/* language=postgresql */ "INSERT INTO todo_tags (todo, tag) SELECT $1, UNNEST($2::INTEGER[]);", todo.id, &interpretation.tags)
+        .execute(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(QuickAddedTodo { todo, parts: interpretation.parts })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use super::{interpret, priority_name, Interpretation, QuickAddMeaning};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).expect("valid date")
+    }
+
+    /// Interprets text on Monday 19 October 2026.
+    fn read(text: &str) -> Interpretation {
+        read_on(text, date(2026, 10, 19))
+    }
+
+    fn read_on(text: &str, today: NaiveDate) -> Interpretation {
+        let subjects = [(1, "CHEM".to_string(), "Chemistry".to_string()), (2, "ENG".to_string(), "English".to_string())];
+        let tags = [(7, "exam".to_string()), (8, "chem".to_string())];
+        interpret(text, today, &subjects, &tags)
+    }
+
+    fn parts(interpretation: &Interpretation) -> Vec<(&str, QuickAddMeaning, Option<&str>)> {
+        interpretation.parts.iter().map(|part| (part.text.as_str(), part.meaning, part.value.as_deref())).collect()
+    }
+
+    #[test]
+    fn everything() {
+        let interpretation = read("finish chem prac report fri #CHEM !high every week");
+        assert_eq!(parts(&interpretation), [
+            ("finish chem prac report", QuickAddMeaning::Title, None),
+            ("fri", QuickAddMeaning::Due, Some("Friday 23 October 2026")),
+            ("#CHEM", QuickAddMeaning::Subject, Some("Chemistry (CHEM)")),
+            ("!high", QuickAddMeaning::Priority, Some("High")),
+            ("every week", QuickAddMeaning::Recurrence, Some("FREQ=WEEKLY")),
+        ]);
+        assert_eq!(interpretation.title.join(" "), "finish chem prac report");
+        assert_eq!(interpretation.due, Some(date(2026, 10, 23)));
+        assert_eq!(interpretation.subject, Some(1));
+        assert_eq!(interpretation.priority.map(priority_name), Some("High"));
+        assert_eq!(interpretation.recurrence.as_deref(), Some("FREQ=WEEKLY"));
+    }
+
+    #[test]
+    fn title() {
+        let interpretation = read("work on essay");
+        assert_eq!(parts(&interpretation), [("work on essay", QuickAddMeaning::Title, None)]);
+        assert_eq!(interpretation.due, None);
+        assert_eq!(read("#unknown tag").title.join(" "), "#unknown tag");
+    }
+
+    #[test]
+    fn repeats_are_title() {
+        let interpretation = read("essay #eng #chem #exam #exam !low !high ~30m ~1h tmr fri");
+        assert_eq!(interpretation.title.join(" "), "essay #exam !high ~1h fri");
+        assert_eq!(interpretation.subject, Some(2));
+        // The second subject is read as the tag with the same name
+        assert_eq!(interpretation.tags, [8, 7]);
+        assert_eq!(interpretation.priority.map(priority_name), Some("Low"));
+        assert_eq!(interpretation.estimated_minutes, Some(30));
+        assert_eq!(interpretation.due, Some(date(2026, 10, 20)));
+    }
+
+    #[test]
+    fn recurrences() {
+        let interpretation = read("gym every mon, wed and fri");
+        assert_eq!(parts(&interpretation), [
+            ("gym", QuickAddMeaning::Title, None),
+            ("every mon, wed and fri", QuickAddMeaning::Recurrence, Some("FREQ=WEEKLY;BYDAY=MO,WE,FR")),
+        ]);
+        assert_eq!(read("bins every other week").recurrence.as_deref(), Some("FREQ=WEEKLY;INTERVAL=2"));
+        assert_eq!(read("revise every school day").recurrence.as_deref(), Some("FREQ=DAILY;X-SCHOOL-DAYS=TRUE"));
+        assert_eq!(read("rent every 3 months").recurrence.as_deref(), Some("FREQ=MONTHLY;INTERVAL=3"));
+        assert_eq!(parts(&read("every and")), [("every and", QuickAddMeaning::Title, None)]);
+    }
+
+    #[test]
+    fn due_dates() {
+        assert_eq!(read("call nan in 3 days").due, Some(date(2026, 10, 22)));
+        assert_eq!(read("call nan in 2 weeks").due, Some(date(2026, 11, 2)));
+        assert_eq!(read("submit by 12/11").due, Some(date(2026, 11, 12)));
+        assert_eq!(read("submit 3rd jan").due, Some(date(2027, 1, 3)));
+        assert_eq!(read("submit next month").due, Some(date(2026, 11, 1)));
+        assert_eq!(read("submit next week").due, Some(date(2026, 10, 26)));
+        // Next week starts tomorrow on a Sunday
+        assert_eq!(read_on("submit next week", date(2026, 10, 18)).due, Some(date(2026, 10, 19)));
+        assert_eq!(read_on("submit next week", date(2026, 10, 24)).due, Some(date(2026, 10, 26)));
+    }
+
+    #[test]
+    fn estimates() {
+        let interpretation = read("practice ~1h30m");
+        assert_eq!(parts(&interpretation), [
+            ("practice", QuickAddMeaning::Title, None),
+            ("~1h30m", QuickAddMeaning::Estimate, Some("90 minutes")),
+        ]);
+        assert_eq!(read("practice ~45").estimated_minutes, Some(45));
+        assert_eq!(read("practice ~2hrs").estimated_minutes, Some(120));
+        assert_eq!(read("practice ~0m").estimated_minutes, None);
+        assert_eq!(read("practice ~1x").estimated_minutes, None);
+    }
+}
@@ -0,0 +1,43 @@
+use async_graphql::{Context, Object, Result};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::sharing::UserGroup;
+
+pub struct UserGroupMutation(pub i32);
+
+#[Object]
+impl UserGroupMutation {
+    /// Delete the group. Always returns true or an error.
+    /// Notes shared with the group are no longer shared with its members.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM user_groups WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the group's name. Returns the updated group.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the group.", validator(max_length = 255))] name: String) -> Result<UserGroup> {
+        query_as!(UserGroup, /* language=postgresql */ "UPDATE user_groups SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Adds a user to the group. Returns the updated group.
+    async fn add_member(&self, ctx: &Context<'_>, #[graphql(desc = "The username of the user to add.")] username: String) -> Result<UserGroup> {
+        let pool = ctx.data::<PgPool>()?;
+        let user = query!(/* language=postgresql */ "SELECT id FROM users WHERE username = $1 LIMIT 1;", username)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?.id;
+        query!(/* language=postgresql */ "INSERT INTO user_group_members (group_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING;", self.0, user)
+            .execute(pool).await?;
+        query_as!(UserGroup, /* language=postgresql */ "SELECT * FROM user_groups WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Removes a user from the group. Returns the updated group.
+    async fn remove_member(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the user to remove.")] user: i32) -> Result<UserGroup> {
+        let pool = ctx.data::<PgPool>()?;
+        query!(/* language=postgresql */ "DELETE FROM user_group_members WHERE group_id = $1 AND user_id = $2;", self.0, user)
+            .execute(pool).await?;
+        query_as!(UserGroup, /* language=postgresql */ "SELECT * FROM user_groups WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+}
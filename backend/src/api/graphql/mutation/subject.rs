@@ -1,15 +1,30 @@
+use std::sync::Arc;
 use async_graphql::{Context, Object, Result};
+use rocket::http::Status;
 use sqlx::{query, query_as};
+use crate::api::graphql::collaboration::Collaboration;
+use crate::api::graphql::mutation::timetable::{check_week, check_weekday, TimetableEntryMutation};
+use crate::api::graphql::mutation::trash::trash_subject;
 use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::timetable::{TimetableEntry, Weekday};
+use crate::auth::User;
 
 pub struct SubjectMutation(pub i32);
 
 #[Object]
 impl SubjectMutation {
-    /// Delete the subject. Always returns true or an error.
+    /// Moves the subject to the trash along with its notes and to-dos, where they can be restored until they are purged.
+    /// The subject's assessments, flashcard decks and timetable are hidden while it is in the trash and deleted when it is purged.
+    /// Always returns true or an error.
     async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
-        query!(/* language=postgresql */ "DELETE FROM subjects WHERE id = $1;", self.0)
-            .execute(ctx.data::<sqlx::PgPool>()?).await?;
+        let pool = ctx.data::<sqlx::PgPool>()?;
+        let subject = trash_subject(pool, self.0).await?;
+        let notes = query!(/* language=postgresql */ "SELECT id FROM notes WHERE trash = $1;", subject.trash)
+            .fetch_all(pool).await?;
+        let collaboration = ctx.data::<Arc<Collaboration>>()?;
+        for note in notes {
+            collaboration.close(pool, note.id).await;
+        }
         Ok(true)
     }
     
@@ -32,9 +47,97 @@ impl SubjectMutation {
             .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
     }
     
+    /// Updates the archived status of the subject. Returns the updated subject.
+    /// Archiving a subject also makes it inactive.
+    async fn archived(&self, ctx: &Context<'_>, #[graphql(desc = "The new archived status of the subject.")] archived: bool) -> Result<Subject> {
+        query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET archived = $2, active = active AND NOT $2 WHERE id = $1 RETURNING *;", self.0, archived)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+    
     /// Updates the subject's Google Classroom ID. Returns the updated subject.
     async fn google_classroom_id(&self, ctx: &Context<'_>, #[graphql(desc = "The new Google Classroom ID of the subject.", validator(max_length = 16))] google_classroom_id: Option<String>) -> Result<Subject> {
         query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET google_classroom_id = $2 WHERE id = $1 RETURNING *;", self.0, google_classroom_id)
             .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
     }
+
+    /// Updates the subject's grade scale. Returns the updated subject.
+    async fn grade_scale(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the new grade scale.")] id: Option<i32>) -> Result<Subject> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        if let Some(id) = id {
+            query(/* language=postgresql */ "SELECT 1 FROM grade_scales WHERE owner = $1 AND id = $2 LIMIT 1;")
+                .bind(user.id)
+                .bind(id)
+                .fetch_optional(ctx.data::<sqlx::PgPool>()?).await?.ok_or(Status::NotFound)?;
+        }
+        query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET grade_scale = $2 WHERE id = $1 RETURNING *;", self.0, id)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Get a timetable entry of the subject for modification.
+    async fn timetable_entry(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the timetable entry to modify.")] id: i32) -> Result<TimetableEntryMutation> {
+        query(/* language=postgresql */ "SELECT 1 FROM timetable_entries WHERE subject = $1 AND id = $2 LIMIT 1;")
+            .bind(self.0)
+            .bind(id)
+            .fetch_optional(ctx.data::<sqlx::PgPool>()?).await?.ok_or(Status::NotFound)?;
+        Ok(TimetableEntryMutation(id))
+    }
+
+    /// Adds a class to the subject's timetable. Returns the newly created timetable entry.
+    /// Classes cannot meet on weekends, and the week must be part of the rotation of the subject's academic year.
+    async fn create_timetable_entry(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The day of the week the class meets.")] weekday: Weekday,
+        #[graphql(desc = "The period the class meets.")] period: i16,
+        #[graphql(desc = "The week of the rotation the class meets, starting at 1. Default: null, meaning every week", default, validator(minimum = 1))] week: Option<i16>,
+        #[graphql(desc = "The room the class meets in. Default: null", default, validator(max_length = 16))] room: Option<String>,
+        #[graphql(desc = "The teacher of the class. Default: null", default, validator(max_length = 255))] teacher: Option<String>,
+    ) -> Result<TimetableEntry> {
+        check_weekday(weekday)?;
+        check_week(ctx.data::<sqlx::PgPool>()?, self.0, week).await?;
+        query_as!(TimetableEntry, /* language=postgresql */ "INSERT INTO timetable_entries (subject, weekday, period, week, room, teacher) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *;", self.0, weekday as i16, period, week, room, teacher)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the academic year the subject is taken during. Returns the updated subject.
+    /// Fails with 409 Conflict if any of the subject's classes meet in a week that is not part of the new year's timetable rotation.
+    async fn year(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the new academic year.")] id: Option<i32>) -> Result<Subject> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        if let Some(id) = id {
+            query(/* language=postgresql */ "SELECT 1 FROM academic_years WHERE owner = $1 AND id = $2 LIMIT 1;")
+                .bind(user.id)
+                .bind(id)
+                .fetch_optional(ctx.data::<sqlx::PgPool>()?).await?.ok_or(Status::NotFound)?;
+        }
+        let clash = query!(/* language=postgresql */ r#"
+            SELECT 1 AS "clash!" FROM timetable_entries
+            WHERE subject = $1 AND week > COALESCE((SELECT rotation_weeks FROM academic_years WHERE id = $2), 1)
+            LIMIT 1;
+            "#, self.0, id)
+            .fetch_optional(ctx.data::<sqlx::PgPool>()?).await?;
+        if clash.is_some() {
+            return Err(Status::Conflict.into());
+        }
+        query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET year = $2 WHERE id = $1 RETURNING *;", self.0, id)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the template new notes for the subject are created from by default. Returns the updated subject.
+    async fn default_template(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the new default template.")] id: Option<i32>) -> Result<Subject> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        if let Some(id) = id {
+            query(/* language=postgresql */ "SELECT 1 FROM note_templates WHERE owner = $1 AND id = $2 LIMIT 1;")
+                .bind(user.id)
+                .bind(id)
+                .fetch_optional(ctx.data::<sqlx::PgPool>()?).await?.ok_or(Status::NotFound)?;
+        }
+        query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET default_template = $2 WHERE id = $1 RETURNING *;", self.0, id)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
 }
@@ -0,0 +1,94 @@
+use async_graphql::{Context, Object, Result};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::tag::Tag;
+
+pub struct TagMutation(pub i32);
+
+/// Checks that all the tags belong to the user. Fails with 404 Not Found otherwise.
+pub async fn check_tags(pool: &PgPool, owner: i32, tags: &[i32]) -> Result<()> {
+    let count = query!(/* language=postgresql */ r#"SELECT COUNT(*) AS "count!" FROM tags WHERE owner = $1 AND id = ANY($2);"#, owner, tags)
+        .fetch_one(pool).await?.count;
+    let mut unique = tags.to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+    if usize::try_from(count)? != unique.len() {
+        return Err(Status::NotFound.into());
+    }
+    Ok(())
+}
+
+/// Checks that none of the user's tags other than `except` has the name, ignoring case. Fails with 409 Conflict otherwise.
+pub async fn check_tag_name(pool: &PgPool, owner: i32, name: &str, except: Option<i32>) -> Result<()> {
+    let clash = query(/* language=postgresql */ "SELECT 1 FROM tags WHERE owner = $1 AND LOWER(name) = LOWER($2) AND id IS DISTINCT FROM $3 LIMIT 1;")
+        .bind(owner)
+        .bind(name)
+        .bind(except)
+        .fetch_optional(pool).await?;
+    if clash.is_some() {
+        return Err(Status::Conflict.into());
+    }
+    Ok(())
+}
+
+/// Converts a clash with another of the user's tag names, which [`check_tag_name`] misses when two tags are named at the same time, to 409 Conflict.
+pub fn name_conflict(error: sqlx::Error) -> async_graphql::Error {
+    match &error {
+        sqlx::Error::Database(database) if database.is_unique_violation() => Status::Conflict.into(),
+        _ => error.into(),
+    }
+}
+
+#[Object]
+impl TagMutation {
+    /// Delete the tag. Always returns true or an error.
+    /// The tag is removed from everything it was attached to.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM tags WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Renames the tag. Returns the updated tag.
+    /// Fails with 409 Conflict if another of the user's tags has the name, in which case the tags can be merged instead.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the tag.", validator(min_length = 1, max_length = 64))] name: String) -> Result<Tag> {
+        let pool = ctx.data::<PgPool>()?;
+        let owner = query!(/* language=postgresql */ "SELECT owner FROM tags WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(pool).await?.owner;
+        check_tag_name(pool, owner, &name, Some(self.0)).await?;
+        query_as!(Tag, /* language=postgresql */ "UPDATE tags SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(pool).await.map_err(name_conflict)
+    }
+
+    /// Updates the tag's colour. Returns the updated tag.
+    async fn colour(&self, ctx: &Context<'_>, #[graphql(desc = "The new colour of the tag as a hex code such as `#808080`.", validator(regex = "^#[0-9a-fA-F]{6}$"))] colour: String) -> Result<Tag> {
+        query_as!(Tag, /* language=postgresql */ "UPDATE tags SET colour = $2 WHERE id = $1 RETURNING *;", self.0, colour.to_lowercase())
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Moves everything the tag is attached to onto another of the user's tags, then deletes this tag.
+    /// Returns the tag that was merged into.
+    async fn merge_into(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the tag to merge into.")] tag: i32) -> Result<Tag> {
+        let pool = ctx.data::<PgPool>()?;
+        if tag == self.0 {
+            return Err(Status::BadRequest.into());
+        }
+        query(/* language=postgresql */ "SELECT 1 FROM tags WHERE id = $2 AND owner = (SELECT owner FROM tags WHERE id = $1) LIMIT 1;")
+            .bind(self.0)
+            .bind(tag)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+        let mut tx = pool.begin().await?;
+        query!(/* language=postgresql */ "INSERT INTO note_tags (note, tag) SELECT note, $2 FROM note_tags WHERE tag = $1 ON CONFLICT DO NOTHING;", self.0, tag)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "INSERT INTO todo_tags (todo, tag) SELECT todo, $2 FROM todo_tags WHERE tag = $1 ON CONFLICT DO NOTHING;", self.0, tag)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "INSERT INTO assessment_tags (assessment, tag) SELECT assessment, $2 FROM assessment_tags WHERE tag = $1 ON CONFLICT DO NOTHING;", self.0, tag)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "DELETE FROM tags WHERE id = $1;", self.0)
+            .execute(&mut *tx).await?;
+        let tag = query_as!(Tag, /* language=postgresql */ "SELECT * FROM tags WHERE id = $1 LIMIT 1;", tag)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(tag)
+    }
+}
@@ -0,0 +1,37 @@
+use async_graphql::{Context, Object, Result};
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::template::NoteTemplate;
+use crate::api::graphql::tiptap::schema::validate_content;
+
+pub struct NoteTemplateMutation(pub i32);
+
+#[Object]
+impl NoteTemplateMutation {
+    /// Delete the template. Always returns true or an error.
+    /// Subjects using it as their default template no longer have one, and notes created from it are kept.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM note_templates WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the template's name. Returns the updated template.
+    async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the template.", validator(max_length = 255))] name: String) -> Result<NoteTemplate> {
+        query_as!(NoteTemplate, /* language=postgresql */ "UPDATE note_templates SET name = $2 WHERE id = $1 RETURNING *;", self.0, name)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the title of notes created from the template. Returns the updated template.
+    async fn title(&self, ctx: &Context<'_>, #[graphql(desc = "The new title of notes created from the template.", validator(max_length = 255))] title: String) -> Result<NoteTemplate> {
+        query_as!(NoteTemplate, /* language=postgresql */ "UPDATE note_templates SET title = $2 WHERE id = $1 RETURNING *;", self.0, title)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the template's content. Returns the updated template.
+    /// The content is validated against the editor's schema.
+    async fn content(&self, ctx: &Context<'_>, #[graphql(desc = "The new content of the template.")] content: serde_json::Value) -> Result<NoteTemplate> {
+        let content = validate_content(&content)?;
+        query_as!(NoteTemplate, /* language=postgresql */ "UPDATE note_templates SET content = $2 WHERE id = $1 RETURNING *;", self.0, content)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+}
@@ -0,0 +1,82 @@
+use async_graphql::{Context, Object, Result};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::timetable::{TimetableEntry, Weekday};
+
+pub struct TimetableEntryMutation(pub i32);
+
+/// Checks that a class can meet on `weekday`.
+/// Lessons only happen on school days, so classes cannot meet on weekends.
+pub(super) fn check_weekday(weekday: Weekday) -> Result<()> {
+    if matches!(weekday, Weekday::Saturday | Weekday::Sunday) {
+        return Err(Status::BadRequest.into());
+    }
+    Ok(())
+}
+
+/// Checks that `week` is part of the timetable rotation of the subject's academic year.
+/// Subjects that do not belong to an academic year have a rotation of one week.
+pub(super) async fn check_week(pool: &PgPool, subject: i32, week: Option<i16>) -> Result<()> {
+    let Some(week) = week else {
+        return Ok(());
+    };
+    let rotation_weeks = query!(/* language=postgresql */ r#"
+        SELECT COALESCE(academic_years.rotation_weeks, 1::SMALLINT) AS "rotation_weeks!"
+        FROM subjects
+        LEFT JOIN academic_years ON academic_years.id = subjects.year
+        WHERE subjects.id = $1
+        LIMIT 1;
+        "#, subject)
+        .fetch_one(pool).await?.rotation_weeks;
+    if week > rotation_weeks {
+        return Err(Status::BadRequest.into());
+    }
+    Ok(())
+}
+
+#[Object]
+impl TimetableEntryMutation {
+    /// Delete the timetable entry. Always returns true or an error.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM timetable_entries WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+
+    /// Updates the day of the week the class meets. Returns the updated timetable entry.
+    /// Classes cannot meet on weekends.
+    async fn weekday(&self, ctx: &Context<'_>, #[graphql(desc = "The new day of the week.")] weekday: Weekday) -> Result<TimetableEntry> {
+        check_weekday(weekday)?;
+        query_as!(TimetableEntry, /* language=postgresql */ "UPDATE timetable_entries SET weekday = $2 WHERE id = $1 RETURNING *;", self.0, weekday as i16)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the period the class meets. Returns the updated timetable entry.
+    async fn period(&self, ctx: &Context<'_>, #[graphql(desc = "The new period.")] period: i16) -> Result<TimetableEntry> {
+        query_as!(TimetableEntry, /* language=postgresql */ "UPDATE timetable_entries SET period = $2 WHERE id = $1 RETURNING *;", self.0, period)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the week of the rotation the class meets. Returns the updated timetable entry.
+    /// The week must be part of the rotation of the subject's academic year.
+    async fn week(&self, ctx: &Context<'_>, #[graphql(desc = "The new week of the rotation, or null for every week.", validator(minimum = 1))] week: Option<i16>) -> Result<TimetableEntry> {
+        let pool = ctx.data::<PgPool>()?;
+        let subject = query!(/* language=postgresql */ "SELECT subject FROM timetable_entries WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(pool).await?.subject;
+        check_week(pool, subject, week).await?;
+        query_as!(TimetableEntry, /* language=postgresql */ "UPDATE timetable_entries SET week = $2 WHERE id = $1 RETURNING *;", self.0, week)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Updates the room the class meets in. Returns the updated timetable entry.
+    async fn room(&self, ctx: &Context<'_>, #[graphql(desc = "The new room.", validator(max_length = 16))] room: Option<String>) -> Result<TimetableEntry> {
+        query_as!(TimetableEntry, /* language=postgresql */ "UPDATE timetable_entries SET room = $2 WHERE id = $1 RETURNING *;", self.0, room)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates the teacher of the class. Returns the updated timetable entry.
+    async fn teacher(&self, ctx: &Context<'_>, #[graphql(desc = "The new teacher.", validator(max_length = 255))] teacher: Option<String>) -> Result<TimetableEntry> {
+        query_as!(TimetableEntry, /* language=postgresql */ "UPDATE timetable_entries SET teacher = $2 WHERE id = $1 RETURNING *;", self.0, teacher)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+}
@@ -1,19 +1,188 @@
 use async_graphql::{Context, Object, Result};
 use chrono::NaiveDate;
 use rocket::http::Status;
-use sqlx::query_as;
-use crate::api::graphql::query::todo::Todo;
+use sqlx::{PgPool, Postgres, query, query_as, Transaction};
+use crate::api::graphql::mutation::tag::check_tags;
+use crate::api::graphql::mutation::trash::trash_todo;
+use crate::api::graphql::query::todo::{Todo, TodoPriority};
+use crate::api::graphql::query::user::today;
+use crate::api::graphql::recurrence::{normalise, Recurrence, RecurrenceValidator};
 use crate::auth::User;
 
 pub struct TodoMutation(pub i32);
 
+/// How many levels deep to-dos can be nested, counting top-level to-dos as the first level.
+const MAX_DEPTH: i32 = 10;
+
+/// Locks the to-dos of `owner` against other changes of parents until the transaction ends.
+/// Without it, two to-dos could be moved under each other at the same time, each passing [`check_parent`] before the other commits.
+async fn lock_tree(tx: &mut Transaction<'_, Postgres>, owner: i32) -> Result<()> {
+    query!(/* language=postgresql */ "SELECT pg_advisory_xact_lock('todos'::REGCLASS::OID::INTEGER, $1);", owner)
+        .execute(&mut **tx).await?;
+    Ok(())
+}
+
+/// Checks that `parent` can be the parent of `todo`, or of a new to-do if `todo` is `None`.
+/// The to-dos of `owner` must be locked with [`lock_tree`] first.
+/// Fails with 404 Not Found if `parent` does not exist or is not owned by `owner`,
+/// and with 400 Bad Request if `parent` is `todo` or one of its descendants, or the to-do and its descendants would be nested deeper than [`MAX_DEPTH`].
+async fn check_parent(tx: &mut Transaction<'_, Postgres>, owner: i32, todo: Option<i32>, parent: i32) -> Result<()> {
+    query!(/* language=postgresql */ "SELECT id FROM todos WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;", owner, parent)
+        .fetch_optional(&mut **tx).await?
+        .ok_or(Status::NotFound)?;
+    // The depth limits stop the recursion even if the tree already has a cycle
+    let ancestry = query!(/* language=postgresql */ r#"
+        WITH RECURSIVE ancestors AS (
+            SELECT id, parent, 1 AS depth FROM todos WHERE id = $1
+            UNION ALL
+            SELECT todos.id, todos.parent, ancestors.depth + 1
+            FROM todos
+            JOIN ancestors ON todos.id = ancestors.parent
+            WHERE ancestors.depth <= $3
+        )
+        SELECT COALESCE(BOOL_OR(id = $2), FALSE) AS "cycle!", COALESCE(MAX(depth), 0) AS "depth!" FROM ancestors;
+        "#, parent, todo, MAX_DEPTH)
+        .fetch_one(&mut **tx).await?;
+    let height = match todo {
+        Some(todo) => query!(/* language=postgresql */ r#"
+            WITH RECURSIVE descendants AS (
+                SELECT id, 1 AS depth FROM todos WHERE id = $1
+                UNION ALL
+                SELECT todos.id, descendants.depth + 1
+                FROM todos
+                JOIN descendants ON todos.parent = descendants.id
+                WHERE descendants.depth <= $2
+            )
+            SELECT COALESCE(MAX(depth), 0) AS "height!" FROM descendants;
+            "#, todo, MAX_DEPTH)
+            .fetch_one(&mut **tx).await?.height,
+        None => 1,
+    };
+    if ancestry.cycle || ancestry.depth + height > MAX_DEPTH {
+        return Err(Status::BadRequest.into());
+    }
+    Ok(())
+}
+
+/// How far apart the positions of to-dos are when a list is renumbered, the same as for new to-dos.
+const POSITION_GAP: i32 = 1024;
+
+/// Gets the position of `sibling`, which must be in the list of to-dos with the given owner and parent, other than `moving`.
+async fn sibling_position(tx: &mut Transaction<'_, Postgres>, owner: i32, parent: Option<i32>, moving: i32, sibling: i32) -> Result<i32> {
+    query!(/* language=postgresql */ "
+        SELECT position FROM todos
+        WHERE id = $1 AND owner = $2 AND parent IS NOT DISTINCT FROM $3 AND id <> $4 AND trash IS NULL
+        LIMIT 1;
+        ", sibling, owner, parent, moving)
+        .fetch_optional(&mut **tx).await?
+        .map(|sibling| sibling.position)
+        .ok_or(Status::BadRequest.into())
+}
+
+/// Finds the positions of the to-dos either side of where `moving` goes in the list of to-dos with the given owner and parent.
+/// It goes after `after` and before `before`, or at the end of the list if neither is given.
+async fn neighbours(
+    tx: &mut Transaction<'_, Postgres>,
+    owner: i32,
+    parent: Option<i32>,
+    moving: i32,
+    after: Option<i32>,
+    before: Option<i32>,
+) -> Result<(Option<i32>, Option<i32>)> {
+    let after = match after {
+        Some(after) => Some(sibling_position(tx, owner, parent, moving, after).await?),
+        None => None,
+    };
+    let before = match before {
+        Some(before) => Some(sibling_position(tx, owner, parent, moving, before).await?),
+        None => None,
+    };
+    Ok(match (after, before) {
+        (Some(_), Some(_)) => (after, before),
+        (Some(after), None) => (Some(after), query!(/* language=postgresql */ "
+            SELECT MIN(position) AS position FROM todos
+            WHERE owner = $1 AND parent IS NOT DISTINCT FROM $2 AND id <> $3 AND trash IS NULL AND position > $4;
+            ", owner, parent, moving, after)
+            .fetch_one(&mut **tx).await?.position),
+        (None, before) => (query!(/* language=postgresql */ "
+            SELECT MAX(position) AS position FROM todos
+            WHERE owner = $1 AND parent IS NOT DISTINCT FROM $2 AND id <> $3 AND trash IS NULL AND ($4::INTEGER IS NULL OR position < $4);
+            ", owner, parent, moving, before)
+            .fetch_one(&mut **tx).await?.position, before),
+    })
+}
+
+/// Spaces the positions of the list of to-dos with the given owner and parent [`POSITION_GAP`] apart, keeping their order.
+async fn renumber(tx: &mut Transaction<'_, Postgres>, owner: i32, parent: Option<i32>) -> Result<()> {
+    query!(/* language=postgresql */ "
+        UPDATE todos SET position = ordered.position
+        FROM (
+            SELECT id, ROW_NUMBER() OVER (ORDER BY position, id) * $3 AS position FROM todos
+            WHERE owner = $1 AND parent IS NOT DISTINCT FROM $2 AND trash IS NULL
+        ) AS ordered
+        WHERE ordered.id = todos.id;
+        ", owner, parent, i64::from(POSITION_GAP))
+        .execute(&mut **tx).await?;
+    Ok(())
+}
+
+/// Creates the next occurrence of a completed to-do that recurs or is standing, with copies of its children and tags.
+/// Children's due dates move by as much as the to-do's.
+/// Does nothing if the to-do does not recur, its next occurrence already exists, or its recurrence rule has ended.
+async fn create_next_occurrence(pool: &PgPool, tx: &mut Transaction<'_, Postgres>, todo: &Todo) -> Result<()> {
+    if todo.next_occurrence.is_some() {
+        return Ok(());
+    }
+    let (due, occurrence) = match &todo.recurrence {
+        Some(rule) => {
+            let rule = rule.parse::<Recurrence>().or(Err(Status::InternalServerError))?;
+            let today = today(pool, todo.owner).await?;
+            let start = todo.due.unwrap_or(today);
+            let number = u32::try_from(todo.occurrence).unwrap_or_default() + 1;
+            // Occurrences that would already be overdue are skipped, but still count towards the rule's `COUNT`
+            let Some((due, number)) = rule.next(pool, todo.owner, start, start, today, number).await? else {
+                return Ok(());
+            };
+            (Some(due), i32::try_from(number)?)
+        }
+        None if todo.standing => (None, todo.occurrence + 1),
+        None => return Ok(()),
+    };
+    let next = query!(/* language=postgresql */ "
+        INSERT INTO todos (owner, title, subject, parent, due, standing, recurrence, occurrence, priority, estimated_minutes)
+        SELECT owner, title, subject, parent, $2, standing, recurrence, $3, priority, estimated_minutes FROM todos WHERE id = $1
+        RETURNING id;
+        ", todo.id, due, occurrence)
+        .fetch_one(&mut **tx).await?.id;
+    query!(/* language=postgresql */ "UPDATE todos SET next_occurrence = $2 WHERE id = $1;", todo.id, next)
+        .execute(&mut **tx).await?;
+    let shift = due.zip(todo.due).map(|(due, previous)| due - previous);
+    let mut copies = vec![(todo.id, next)];
+    while let Some((original, copy)) = copies.pop() {
+        query!(/* language=postgresql */ "INSERT INTO todo_tags (todo, tag) SELECT $2, tag FROM todo_tags WHERE todo = $1;", original, copy)
+            .execute(&mut **tx).await?;
+        let children = query!(/* language=postgresql */ "SELECT id, due FROM todos WHERE parent = $1 AND trash IS NULL ORDER BY position, id;", original)
+            .fetch_all(&mut **tx).await?;
+        for child in children {
+            let due = child.due.zip(shift).map(|(due, shift)| due + shift);
+            let child_copy = query!(/* language=postgresql */ "
+                INSERT INTO todos (owner, title, subject, parent, due, standing, recurrence, priority, estimated_minutes)
+                SELECT owner, title, subject, $2, $3, standing, recurrence, priority, estimated_minutes FROM todos WHERE id = $1
+                RETURNING id;
+                ", child.id, copy, due)
+                .fetch_one(&mut **tx).await?.id;
+            copies.push((child.id, child_copy));
+        }
+    }
+    Ok(())
+}
+
 #[Object]
 impl TodoMutation {
-    /// Delete the to-do. Returns the deleted to-do.
+    /// Moves the to-do to the trash, where it can be restored until it is purged. Returns the deleted to-do.
     /// Also deletes children.
     async fn delete(&self, ctx: &Context<'_>) -> Result<Todo> {
-        query_as!(Todo, /* language=postgresql */ "DELETE FROM todos WHERE id = $1 RETURNING *;", self.0)
-            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+        trash_todo(ctx.data::<sqlx::PgPool>()?, self.0).await
     }
 
     /// Updates the to-do's title. Returns the updated to-do.
@@ -23,9 +192,20 @@ impl TodoMutation {
     }
 
     /// Updates the to-do's completed status. Returns the updated to-do.
+    /// Completing a to-do that recurs or is standing creates its next occurrence the first time.
+    /// Fails with 409 Conflict if the to-do recurs on school days only and there are no school days within a year.
     async fn completed(&self, ctx: &Context<'_>, #[graphql(desc = "The new completed status.")] completed: bool) -> Result<Todo> {
-        query_as!(Todo, /* language=postgresql */ "UPDATE todos SET completed = $2 WHERE id = $1 RETURNING *;", self.0, completed)
-            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+        let pool = ctx.data::<sqlx::PgPool>()?;
+        let mut tx = pool.begin().await?;
+        let todo = query_as!(Todo, /* language=postgresql */ "UPDATE todos SET completed = $2 WHERE id = $1 RETURNING *;", self.0, completed)
+            .fetch_one(&mut *tx).await?;
+        if todo.completed {
+            create_next_occurrence(pool, &mut tx, &todo).await?;
+        }
+        let todo = query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(todo)
     }
 
     /// Updates the to-do's subject. Returns the updated to-do.
@@ -35,9 +215,77 @@ impl TodoMutation {
     }
 
     /// Changes the to-do's parent. Returns the updated to-do.
+    /// The to-do goes at the end of its new parent's children.
+    /// Fails with 404 Not Found if the parent does not exist, and with 400 Bad Request if the parent is the to-do or one of its descendants
+    /// or the to-do would be nested too deeply.
     async fn parent(&self, ctx: &Context<'_>, #[graphql(desc = "The new parent's ID.")] id: Option<i32>) -> Result<Todo> {
-        query_as!(Todo, /* language=postgresql */ "UPDATE todos SET parent = $2 WHERE id = $1 RETURNING *;", self.0, id)
-            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+        let mut tx = ctx.data::<sqlx::PgPool>()?.begin().await?;
+        let owner = query!(/* language=postgresql */ "SELECT owner FROM todos WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(&mut *tx).await?.owner;
+        lock_tree(&mut tx, owner).await?;
+        if let Some(parent) = id {
+            check_parent(&mut tx, owner, Some(self.0), parent).await?;
+        }
+        let todo = query_as!(Todo, /* language=postgresql */ "
+            UPDATE todos
+            SET parent = $2,
+                position = CASE WHEN parent IS DISTINCT FROM $2 THEN COALESCE((
+                    SELECT MAX(position) FROM todos AS siblings WHERE siblings.owner = todos.owner AND siblings.parent IS NOT DISTINCT FROM $2
+                ), 0) + $3 ELSE position END
+            WHERE id = $1
+            RETURNING *;
+            ", self.0, id, POSITION_GAP)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(todo)
+    }
+
+    /// Moves the to-do to a position among the children of a parent, or among the top-level to-dos, in one step.
+    /// Returns the moved to-do.
+    /// Fails with 400 Bad Request if `after` or `before` is not in the list, or `after` comes after `before`.
+    /// The parent is checked like when changing the parent.
+    #[graphql(name = "move")]
+    async fn move_todo(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the to-do that should come right before this one. Default: null", default)] after: Option<i32>,
+        #[graphql(desc = "The ID of the to-do that should come right after this one. Default: null", default)] before: Option<i32>,
+        #[graphql(desc = "The ID of the new parent, or null to move the to-do to the top level. Default: null", default)] parent: Option<i32>,
+    ) -> Result<Todo> {
+        let mut tx = ctx.data::<sqlx::PgPool>()?.begin().await?;
+        let owner = query!(/* language=postgresql */ "SELECT owner FROM todos WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(&mut *tx).await?.owner;
+        lock_tree(&mut tx, owner).await?;
+        if let Some(parent) = parent {
+            check_parent(&mut tx, owner, Some(self.0), parent).await?;
+        }
+        let mut renumbered = false;
+        let position = loop {
+            // Worked out in i64 so that positions near the ends of the i32 range do not overflow
+            let position = match neighbours(&mut tx, owner, parent, self.0, after, before).await? {
+                (Some(after), Some(before)) => {
+                    let (after, before) = (i64::from(after), i64::from(before));
+                    (before - after >= 2).then_some(after + (before - after) / 2)
+                }
+                (Some(after), None) => Some(i64::from(after) + i64::from(POSITION_GAP)),
+                (None, Some(before)) => Some(i64::from(before) - i64::from(POSITION_GAP)),
+                (None, None) => Some(i64::from(POSITION_GAP)),
+            };
+            match position.map(i32::try_from) {
+                Some(Ok(position)) => break position,
+                // No room between the neighbours or past the end of the list, so space the list out and try again
+                _ if !renumbered => {
+                    renumber(&mut tx, owner, parent).await?;
+                    renumbered = true;
+                }
+                // The list is evenly spaced, so there is only no room if `after` comes after `before`
+                _ => return Err(Status::BadRequest.into()),
+            }
+        };
+        let todo = query_as!(Todo, /* language=postgresql */ "UPDATE todos SET parent = $2, position = $3 WHERE id = $1 RETURNING *;", self.0, parent, position)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(todo)
     }
 
     /// Updates the to-do's due date. Returns the updated to-do.
@@ -54,12 +302,60 @@ impl TodoMutation {
             .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
     }
     
-    async fn standing(&self, ctx: &Context<'_>, standing: bool) -> Result<Todo> {
+    /// Updates the to-do's standing status. Returns the updated to-do.
+    /// Completing a standing to-do without a recurrence rule creates its next occurrence straight away, without a due date.
+    async fn standing(&self, ctx: &Context<'_>, #[graphql(desc = "The new standing status.")] standing: bool) -> Result<Todo> {
         query_as!(Todo, /* language=postgresql */ "UPDATE todos SET standing = $2 WHERE id = $1 RETURNING *;", self.0, standing)
             .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
     }
 
+    /// Updates the to-do's priority. Returns the updated to-do.
+    async fn priority(&self, ctx: &Context<'_>, #[graphql(desc = "The new priority.")] priority: TodoPriority) -> Result<Todo> {
+        query_as!(Todo, /* language=postgresql */ "UPDATE todos SET priority = $2 WHERE id = $1 RETURNING *;", self.0, priority as i16)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Updates how many minutes the to-do is expected to take. Returns the updated to-do.
+    async fn estimated_minutes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The new estimate in minutes, or null for none.", validator(minimum = 1))] minutes: Option<i32>,
+    ) -> Result<Todo> {
+        query_as!(Todo, /* language=postgresql */ "UPDATE todos SET estimated_minutes = $2 WHERE id = $1 RETURNING *;", self.0, minutes)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+
+    /// Links the to-do to the assessment it is for, whose weight counts towards suggesting it. Returns the updated to-do.
+    /// Requires authentication.
+    async fn assessment(&self, ctx: &Context<'_>, #[graphql(desc = "The assessment's ID, or null to unlink the to-do.")] id: Option<i32>) -> Result<Todo> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<sqlx::PgPool>()?;
+        if let Some(id) = id {
+            query(/* language=postgresql */ "SELECT 1 FROM assessments WHERE owner = $1 AND id = $2 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;")
+                .bind(user.id)
+                .bind(id)
+                .fetch_optional(pool).await?
+                .ok_or(Status::NotFound)?;
+        }
+        query_as!(Todo, /* language=postgresql */ "UPDATE todos SET assessment = $2 WHERE id = $1 RETURNING *;", self.0, id)
+            .fetch_one(pool).await.map_err(Into::into)
+    }
+
+    /// Updates the to-do's iCalendar recurrence rule, such as `FREQ=WEEKLY;BYDAY=MO`. Returns the updated to-do.
+    /// Fails if the rule is invalid or uses parts that are not supported.
+    async fn recurrence(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The new recurrence rule, or null to stop the to-do recurring.", validator(custom = "RecurrenceValidator"))] rule: Option<String>,
+    ) -> Result<Todo> {
+        query_as!(Todo, /* language=postgresql */ "UPDATE todos SET recurrence = $2 WHERE id = $1 RETURNING *;", self.0, normalise(rule)?)
+            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+    }
+
     /// Creates a child to-do. Returns the newly created to-do.
+    /// Fails with 400 Bad Request if the child would be nested too deeply.
     /// Requires authentication.
     async fn create_child(
         &self,
@@ -71,7 +367,30 @@ impl TodoMutation {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
-        query_as!(Todo, /* language=postgresql */ "INSERT INTO todos (owner, title, completed, parent, due) VALUES ($1, $2, $3, $4, $5) RETURNING *;", user.id, title, completed, self.0, due)
-            .fetch_one(ctx.data::<sqlx::PgPool>()?).await.map_err(Into::into)
+        let mut tx = ctx.data::<sqlx::PgPool>()?.begin().await?;
+        lock_tree(&mut tx, user.id).await?;
+        check_parent(&mut tx, user.id, None, self.0).await?;
+        let todo = query_as!(Todo, /* language=postgresql */ "INSERT INTO todos (owner, title, completed, parent, due) VALUES ($1, $2, $3, $4, $5) RETURNING *;", user.id, title, completed, self.0, due)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(todo)
+    }
+
+    /// Replaces the to-do's tags. Returns the updated to-do.
+    async fn tags(&self, ctx: &Context<'_>, #[graphql(desc = "The IDs of the to-do's new tags.")] tags: Vec<i32>) -> Result<Todo> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<sqlx::PgPool>()?;
+        check_tags(pool, user.id, &tags).await?;
+        let mut tx = pool.begin().await?;
+        query!(/* language=postgresql */ "DELETE FROM todo_tags WHERE todo = $1;", self.0)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "INSERT INTO todo_tags (todo, tag) SELECT $1, UNNEST($2::INTEGER[]) ON CONFLICT DO NOTHING;", self.0, &tags)
+            .execute(&mut *tx).await?;
+        let todo = query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE id = $1 LIMIT 1;", self.0)
+            .fetch_one(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(todo)
     }
 }
@@ -0,0 +1,113 @@
+use async_graphql::{Context, Object, Result};
+use rocket::http::Status;
+use sqlx::{PgPool, Postgres, query, query_as, Transaction};
+use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::todo::Todo;
+use crate::api::graphql::query::trash::TrashedItem;
+
+pub struct TrashEntryMutation(pub i32);
+
+/// Adds an empty entry to a user's trash. Returns the ID of the entry.
+async fn create_entry(tx: &mut Transaction<'_, Postgres>, owner: i32) -> Result<i32> {
+    Ok(query!(/* language=postgresql */ "INSERT INTO trash (owner) VALUES ($1) RETURNING id;", owner)
+        .fetch_one(&mut **tx).await?.id)
+}
+
+/// Moves a note to its owner's trash. Returns the deleted note.
+pub async fn trash_note(pool: &PgPool, note: i32) -> Result<Note> {
+    let mut tx = pool.begin().await?;
+    let owner = query!(/* language=postgresql */ "SELECT owner FROM notes WHERE id = $1 LIMIT 1;", note)
+        .fetch_one(&mut *tx).await?.owner;
+    let entry = create_entry(&mut tx, owner).await?;
+    let note = query_as!(Note, /* language=postgresql */ "UPDATE notes SET trash = $2 WHERE id = $1 RETURNING *;", note, entry)
+        .fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(note)
+}
+
+/// Moves a to-do and its descendants to its owner's trash. Returns the deleted to-do.
+/// Descendants that were already deleted stay in their own trash entries.
+pub async fn trash_todo(pool: &PgPool, todo: i32) -> Result<Todo> {
+    let mut tx = pool.begin().await?;
+    let owner = query!(/* language=postgresql */ "SELECT owner FROM todos WHERE id = $1 LIMIT 1;", todo)
+        .fetch_one(&mut *tx).await?.owner;
+    let entry = create_entry(&mut tx, owner).await?;
+    query!(/* language=postgresql */ "
+        WITH RECURSIVE tree AS (
+            SELECT id FROM todos WHERE id = $1
+            UNION
+            SELECT todos.id FROM todos JOIN tree ON todos.parent = tree.id WHERE todos.trash IS NULL
+        )
+        UPDATE todos SET trash = $2 WHERE id IN (SELECT id FROM tree);
+        ", todo, entry)
+        .execute(&mut *tx).await?;
+    let todo = query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE id = $1 LIMIT 1;", todo)
+        .fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(todo)
+}
+
+/// Moves a subject to its owner's trash along with its notes and to-dos, including the to-dos' descendants.
+/// Returns the deleted subject.
+pub async fn trash_subject(pool: &PgPool, subject: i32) -> Result<Subject> {
+    let mut tx = pool.begin().await?;
+    let owner = query!(/* language=postgresql */ "SELECT owner FROM subjects WHERE id = $1 LIMIT 1;", subject)
+        .fetch_one(&mut *tx).await?.owner;
+    let entry = create_entry(&mut tx, owner).await?;
+    query!(/* language=postgresql */ "UPDATE notes SET trash = $2 WHERE subject = $1 AND trash IS NULL;", subject, entry)
+        .execute(&mut *tx).await?;
+    query!(/* language=postgresql */ "
+        WITH RECURSIVE tree AS (
+            SELECT id FROM todos WHERE subject = $1 AND trash IS NULL
+            UNION
+            SELECT todos.id FROM todos JOIN tree ON todos.parent = tree.id WHERE todos.trash IS NULL
+        )
+        UPDATE todos SET trash = $2 WHERE id IN (SELECT id FROM tree);
+        ", subject, entry)
+        .execute(&mut *tx).await?;
+    let subject = query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET trash = $2 WHERE id = $1 RETURNING *;", subject, entry)
+        .fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(subject)
+}
+
+#[Object]
+impl TrashEntryMutation {
+    /// Restores the deleted item and everything deleted with it, such as a subject's notes and to-dos or a to-do's children.
+    /// Returns the restored item.
+    /// Fails with 409 Conflict if the subject or parent to-do of anything in the entry is in another trash entry, which must be restored first.
+    async fn restore(&self, ctx: &Context<'_>) -> Result<TrashedItem> {
+        let mut tx = ctx.data::<PgPool>()?.begin().await?;
+        let conflict = query(/* language=postgresql */ "
+            SELECT 1 FROM notes JOIN subjects ON subjects.id = notes.subject WHERE notes.trash = $1 AND subjects.trash <> $1
+            UNION ALL
+            SELECT 1 FROM todos JOIN subjects ON subjects.id = todos.subject WHERE todos.trash = $1 AND subjects.trash <> $1
+            UNION ALL
+            SELECT 1 FROM todos JOIN todos AS parents ON parents.id = todos.parent WHERE todos.trash = $1 AND parents.trash <> $1
+            LIMIT 1;
+            ")
+            .bind(self.0)
+            .fetch_optional(&mut *tx).await?;
+        if conflict.is_some() {
+            return Err(Status::Conflict.into());
+        }
+        let notes = query_as!(Note, /* language=postgresql */ "UPDATE notes SET trash = NULL WHERE trash = $1 RETURNING *;", self.0)
+            .fetch_all(&mut *tx).await?;
+        let todos = query_as!(Todo, /* language=postgresql */ "UPDATE todos SET trash = NULL WHERE trash = $1 RETURNING *;", self.0)
+            .fetch_all(&mut *tx).await?;
+        let subjects = query_as!(Subject, /* language=postgresql */ "UPDATE subjects SET trash = NULL WHERE trash = $1 RETURNING *;", self.0)
+            .fetch_all(&mut *tx).await?;
+        query!(/* language=postgresql */ "DELETE FROM trash WHERE id = $1;", self.0)
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+        TrashedItem::root(notes, todos, subjects).ok_or(Status::NotFound.into())
+    }
+
+    /// Permanently deletes the item and everything deleted with it. Always returns true or an error.
+    async fn delete(&self, ctx: &Context<'_>) -> Result<bool> {
+        query!(/* language=postgresql */ "DELETE FROM trash WHERE id = $1;", self.0)
+            .execute(ctx.data::<PgPool>()?).await?;
+        Ok(true)
+    }
+}
@@ -2,6 +2,7 @@ use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::PasswordHasher;
 use async_graphql::{Context, Object, Result};
+use chrono_tz::Tz;
 use rocket::http::Status;
 use sqlx::{PgPool, query, query_as};
 use crate::api::graphql::mutation::session::SessionMutation;
@@ -20,13 +21,13 @@ impl UserMutation {
 
     /// Updates the user's username. Returns the updated user.
     async fn username(&self, ctx: &Context<'_>, #[graphql(desc = "The new username of the user.", validator(max_length = 16))] username: String) -> Result<User> {
-        query_as!(User, /* language=postgresql */ "UPDATE users SET username = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email;", self.0, username)
+        query_as!(User, /* language=postgresql */ "UPDATE users SET username = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email, timezone;", self.0, username)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
     
     /// Updates the user's name. Returns the updated user.
     async fn name(&self, ctx: &Context<'_>, #[graphql(desc = "The new name of the user.", validator(max_length = 255))] name: String) -> Result<User> {
-        query_as!(User, /* language=postgresql */ "UPDATE users SET name = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email;", self.0, name)
+        query_as!(User, /* language=postgresql */ "UPDATE users SET name = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email, timezone;", self.0, name)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
     
@@ -34,16 +35,26 @@ impl UserMutation {
     async fn password(&self, ctx: &Context<'_>, #[graphql(desc = "The new password of the user.")] password: String) -> Result<User> {
         let salt = SaltString::generate(&mut OsRng);
         let hash = ARGON2.hash_password(password.as_bytes(), &salt)?.to_string();
-        query_as!(User, /* language=postgresql */ "UPDATE users SET password = $2, require_password_change = FALSE WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email;", self.0, hash)
+        query_as!(User, /* language=postgresql */ "UPDATE users SET password = $2, require_password_change = FALSE WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email, timezone;", self.0, hash)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
     
     /// Updates the user's email. Returns the updated user.
     async fn email(&self, ctx: &Context<'_>, #[graphql(desc = "The new email of the user.", validator(email, max_length = 255))] email: String) -> Result<User> {
-        query_as!(User, /* language=postgresql */ "UPDATE users SET email = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email;", self.0, email)
+        query_as!(User, /* language=postgresql */ "UPDATE users SET email = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email, timezone;", self.0, email)
             .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
     }
     
+    /// Updates the user's time zone, used for relative dates such as "tomorrow". Returns the updated user.
+    /// Fails with 400 Bad Request if the time zone is not an IANA time zone name.
+    async fn timezone(&self, ctx: &Context<'_>, #[graphql(desc = "The new IANA time zone of the user, such as `Australia/Sydney`, or null to use the server's.")] timezone: Option<String>) -> Result<User> {
+        if timezone.as_deref().is_some_and(|timezone| timezone.parse::<Tz>().is_err()) {
+            return Err(Status::BadRequest.into());
+        }
+        query_as!(User, /* language=postgresql */ "UPDATE users SET timezone = $2 WHERE id = $1 RETURNING id, username, name, created_at, require_password_change, account_type, email, timezone;", self.0, timezone)
+            .fetch_one(ctx.data::<PgPool>()?).await.map_err(Into::into)
+    }
+
     /// Get a session for modification.
     async fn session(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the session to modify.")] id: i32) -> Result<SessionMutation> {
         query(/* language=postgresql */ "SELECT 1 FROM sessions WHERE user_id = $1 AND id = $2 LIMIT 1;")
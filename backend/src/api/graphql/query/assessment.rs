@@ -1,9 +1,11 @@
 use std::mem;
 use async_graphql::{ComplexObject, Context, SimpleObject, Result, Enum};
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use rocket::http::Status;
-use sqlx::{PgPool, query_as};
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::grade_scale::Grade;
 use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::tag::Tag;
 
 #[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
 #[repr(u8)]
@@ -86,7 +88,10 @@ pub struct Assessment {
     pub notification: Option<String>,
     pub submission: Option<String>,
     pub reference: Option<String>,
-
+    /// When the assessment was created.
+    pub created_at: NaiveDateTime,
+    /// When the assessment last changed.
+    pub updated_at: NaiveDateTime,
 }
 
 #[ComplexObject]
@@ -95,4 +100,27 @@ impl Assessment {
         query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE id = $1 LIMIT 1;", self.subject)
             .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError)).map_err(Into::into)
     }
+
+    /// The grade of the assessment according to its subject's grade scale.
+    /// Null if the assessment has not been marked or the subject has no grade scale.
+    async fn grade(&self, ctx: &Context<'_>) -> Result<Option<Grade>> {
+        let (Some(mark), Some(mark_out_of)) = (self.mark, self.mark_out_of) else {
+            return Ok(None);
+        };
+        if mark_out_of <= 0 {
+            return Ok(None);
+        }
+        let pool = ctx.data::<PgPool>()?;
+        let Some(grade_scale) = query!(/* language=postgresql */ "SELECT grade_scale FROM subjects WHERE id = $1 LIMIT 1;", self.subject)
+            .fetch_one(pool).await?.grade_scale else {
+            return Ok(None);
+        };
+        Grade::from_percentage(pool, grade_scale, f64::from(mark) / f64::from(mark_out_of) * 100.0).await
+    }
+
+    /// The tags of the assessment, ordered by name.
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        query_as!(Tag, /* language=postgresql */ "SELECT tags.* FROM assessment_tags JOIN tags ON tags.id = assessment_tags.tag WHERE assessment_tags.assessment = $1 ORDER BY tags.name;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
 }
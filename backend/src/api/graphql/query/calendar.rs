@@ -0,0 +1,224 @@
+use std::mem;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use chrono::{Days, NaiveDate};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::user::today;
+
+#[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[repr(u8)]
+pub enum HolidayKind {
+    /// A public or school holiday.
+    Holiday = 0,
+    /// A pupil-free day, such as a staff development day.
+    PupilFree = 1,
+}
+
+impl TryFrom<u8> for HolidayKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if !(0..=1).contains(&value) {
+            return Err(());
+        }
+        unsafe {
+            Ok(mem::transmute(value))
+        }
+    }
+}
+
+impl From<i16> for HolidayKind {
+    fn from(value: i16) -> Self {
+        u8::try_from(value).unwrap().try_into().unwrap()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct AcademicYear {
+    /// The ID of the academic year.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    /// The name of the academic year, for example "2024".
+    pub name: String,
+
+    /// The first day of the academic year.
+    pub start_date: NaiveDate,
+
+    /// The last day of the academic year.
+    pub end_date: NaiveDate,
+
+    /// The number of weeks in the timetable's rotation, for example 2 for week A/B timetables.
+    /// Week 1 starts on the Monday of the week containing the first day of the year.
+    pub rotation_weeks: i16,
+}
+
+#[ComplexObject]
+impl AcademicYear {
+    /// The terms of the academic year, in order.
+    async fn terms(&self, ctx: &Context<'_>) -> Result<Vec<Term>> {
+        query_as!(Term, /* language=postgresql */ "SELECT * FROM terms WHERE year = $1 ORDER BY start_date;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The holidays and pupil-free days of the academic year, in order.
+    async fn holidays(&self, ctx: &Context<'_>) -> Result<Vec<Holiday>> {
+        query_as!(Holiday, /* language=postgresql */ "SELECT * FROM holidays WHERE year = $1 ORDER BY start_date;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The subjects taken during the academic year.
+    async fn subjects(&self, ctx: &Context<'_>) -> Result<Vec<Subject>> {
+        query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE year = $1 AND trash IS NULL ORDER BY name;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Term {
+    /// The ID of the term.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub year: i32,
+
+    /// The name of the term, for example "Term 1".
+    pub name: String,
+
+    /// The first day of the term.
+    pub start_date: NaiveDate,
+
+    /// The last day of the term.
+    pub end_date: NaiveDate,
+}
+
+#[ComplexObject]
+impl Term {
+    /// The academic year the term is part of.
+    async fn year(&self, ctx: &Context<'_>) -> Result<AcademicYear> {
+        query_as!(AcademicYear, /* language=postgresql */ "SELECT * FROM academic_years WHERE id = $1 LIMIT 1;", self.year)
+            .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError)).map_err(Into::into)
+    }
+}
+
+impl Term {
+    /// Gets the first and last day of a term owned by the user.
+    /// Returns `None` if the term does not exist or belongs to someone else.
+    pub async fn dates(pool: &PgPool, owner: i32, id: i32) -> sqlx::Result<Option<(NaiveDate, NaiveDate)>> {
+        Ok(query!(/* language=postgresql */ "
+            SELECT terms.start_date, terms.end_date
+            FROM terms
+            JOIN academic_years ON academic_years.id = terms.year
+            WHERE academic_years.owner = $1 AND terms.id = $2
+            LIMIT 1;
+            ", owner, id)
+            .fetch_optional(pool).await?
+            .map(|term| (term.start_date, term.end_date)))
+    }
+
+    /// Gets the ID of the academic year of a term owned by the user.
+    /// Returns `None` if the term does not exist or belongs to someone else.
+    pub async fn academic_year(pool: &PgPool, owner: i32, id: i32) -> sqlx::Result<Option<i32>> {
+        Ok(query!(/* language=postgresql */ "
+            SELECT terms.year
+            FROM terms
+            JOIN academic_years ON academic_years.id = terms.year
+            WHERE academic_years.owner = $1 AND terms.id = $2
+            LIMIT 1;
+            ", owner, id)
+            .fetch_optional(pool).await?
+            .map(|term| term.year))
+    }
+
+    /// Resolves an optional term filter to the dates it covers.
+    /// Returns no bounds if no term is given.
+    pub async fn bounds(pool: &PgPool, owner: i32, id: Option<i32>) -> Result<(Option<NaiveDate>, Option<NaiveDate>)> {
+        let Some(id) = id else {
+            return Ok((None, None));
+        };
+        let (from, to) = Self::dates(pool, owner, id).await?.ok_or(Status::NotFound)?;
+        Ok((Some(from), Some(to)))
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Holiday {
+    /// The ID of the holiday.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub year: i32,
+
+    /// The name of the holiday.
+    pub name: String,
+
+    /// Whether this is a holiday or a pupil-free day.
+    pub kind: HolidayKind,
+
+    /// The first day of the holiday.
+    pub start_date: NaiveDate,
+
+    /// The last day of the holiday.
+    pub end_date: NaiveDate,
+}
+
+/// Lists the school days from `from` to `to` for the user, in order.
+/// School days are weekdays that are not holidays or pupil-free days.
+/// Days between terms are skipped, unless they are after the last term the user has entered.
+pub async fn school_days(pool: &PgPool, owner: i32, from: NaiveDate, to: NaiveDate) -> sqlx::Result<Vec<NaiveDate>> {
+    Ok(query!(/* language=postgresql */ r#"
+        SELECT day::DATE AS "day!"
+        FROM generate_series($2::DATE, $3::DATE, INTERVAL '1 day') AS day
+        WHERE EXTRACT(ISODOW FROM day) < 6
+            AND NOT EXISTS (
+                SELECT 1 FROM holidays
+                JOIN academic_years ON academic_years.id = holidays.year
+                WHERE academic_years.owner = $1 AND day BETWEEN holidays.start_date AND holidays.end_date
+            )
+            AND (
+                EXISTS (
+                    SELECT 1 FROM terms
+                    JOIN academic_years ON academic_years.id = terms.year
+                    WHERE academic_years.owner = $1 AND day BETWEEN terms.start_date AND terms.end_date
+                )
+                OR NOT EXISTS (
+                    SELECT 1 FROM terms
+                    JOIN academic_years ON academic_years.id = terms.year
+                    WHERE academic_years.owner = $1 AND terms.end_date >= day
+                )
+            )
+        ORDER BY day;
+        "#, owner, from, to)
+        .fetch_all(pool).await?
+        .into_iter()
+        .map(|day| day.day)
+        .collect())
+}
+
+/// Finds the first school day after `after` for the user.
+/// See [`school_days`].
+/// Returns `None` if there is no school day within a year.
+pub async fn next_school_day(pool: &PgPool, owner: i32, after: NaiveDate) -> sqlx::Result<Option<NaiveDate>> {
+    let (Some(from), Some(to)) = (after.succ_opt(), after.checked_add_days(Days::new(366))) else {
+        return Ok(None);
+    };
+    Ok(school_days(pool, owner, from, to).await?.first().copied())
+}
+
+/// Finds the first school day on or after today in the user's time zone, or today if there is none within a year.
+/// See [`school_days`].
+pub async fn current_school_day(pool: &PgPool, owner: i32) -> Result<NaiveDate> {
+    let today = today(pool, owner).await?;
+    Ok(next_school_day(pool, owner, today.pred_opt().unwrap_or(today)).await?.unwrap_or(today))
+}
+
+/// Checks whether `date` is a school day for the user.
+/// See [`school_days`].
+pub async fn is_school_day(pool: &PgPool, owner: i32, date: NaiveDate) -> sqlx::Result<bool> {
+    Ok(!school_days(pool, owner, date, date).await?.is_empty())
+}
@@ -0,0 +1,167 @@
+use std::mem;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use chrono::{NaiveDate, NaiveDateTime};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::user::today;
+use crate::auth::User;
+
+/// How well a card was remembered when it was reviewed.
+#[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[repr(u8)]
+pub enum ReviewGrade {
+    /// Forgotten. The card is learned again from the start.
+    Again = 0,
+    /// Remembered with serious difficulty.
+    Hard = 1,
+    /// Remembered after some hesitation.
+    Good = 2,
+    /// Remembered perfectly.
+    Easy = 3,
+}
+
+impl TryFrom<u8> for ReviewGrade {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if !(0..=3).contains(&value) {
+            return Err(());
+        }
+        unsafe {
+            Ok(mem::transmute(value))
+        }
+    }
+}
+
+impl From<i16> for ReviewGrade {
+    fn from(value: i16) -> Self {
+        u8::try_from(value).unwrap().try_into().unwrap()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Deck {
+    /// The ID of the deck.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    #[graphql(skip)]
+    pub subject: i32,
+
+    /// The name of the deck.
+    pub name: String,
+}
+
+#[ComplexObject]
+impl Deck {
+    /// The subject the deck is for.
+    async fn subject(&self, ctx: &Context<'_>) -> Result<Subject> {
+        query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE id = $1 LIMIT 1;", self.subject)
+            .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The cards in the deck, oldest first.
+    async fn cards(&self, ctx: &Context<'_>) -> Result<Vec<Card>> {
+        query_as!(Card, /* language=postgresql */ "SELECT * FROM cards WHERE deck = $1 ORDER BY id;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The number of cards in the deck due for review today.
+    async fn due_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query!(/* language=postgresql */ r#"SELECT COUNT(*) AS "count!" FROM cards WHERE deck = $1 AND due <= $2;"#, self.id, today(pool, self.owner).await?)
+            .fetch_one(pool).await.or(Err(Status::InternalServerError))?
+            .count)
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Card {
+    /// The ID of the card.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub deck: i32,
+
+    #[graphql(skip)]
+    pub note: Option<i32>,
+
+    /// The prompt shown when the card is reviewed.
+    pub front: String,
+
+    /// The answer revealed after the prompt.
+    pub back: String,
+
+    /// The day the card is next due for review.
+    pub due: NaiveDate,
+
+    /// The number of days between the card's last review and when it is due.
+    pub interval: i32,
+
+    /// How quickly the interval grows with each successful review, at least 1.3.
+    pub ease: f64,
+
+    /// The number of successful reviews in a row.
+    pub repetitions: i32,
+
+    /// The number of times the card has been forgotten after being learned.
+    pub lapses: i32,
+}
+
+#[ComplexObject]
+impl Card {
+    /// The deck the card is in.
+    async fn deck(&self, ctx: &Context<'_>) -> Result<Deck> {
+        query_as!(Deck, /* language=postgresql */ "SELECT * FROM decks WHERE id = $1 LIMIT 1;", self.deck)
+            .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The note the card was extracted from.
+    /// Null if it was created manually, or the note was deleted or is no longer shared with the authenticated user.
+    async fn note(&self, ctx: &Context<'_>) -> Result<Option<Note>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE id = $1 AND note_role(id, $2) IS NOT NULL LIMIT 1;", self.note, user.id)
+            .fetch_optional(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The card's reviews, newest first.
+    async fn reviews(&self, ctx: &Context<'_>) -> Result<Vec<CardReview>> {
+        query_as!(CardReview, /* language=postgresql */ "SELECT * FROM card_reviews WHERE card = $1 ORDER BY reviewed_at DESC, id DESC;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CardReview {
+    /// The ID of the review.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub card: i32,
+
+    /// When the card was reviewed.
+    pub reviewed_at: NaiveDateTime,
+
+    /// How well the card was remembered.
+    pub grade: ReviewGrade,
+
+    /// The card's interval before the review.
+    pub previous_interval: i32,
+
+    /// The card's ease before the review.
+    pub previous_ease: f64,
+
+    /// The card's interval after the review.
+    pub interval: i32,
+
+    /// The card's ease after the review.
+    pub ease: f64,
+}
@@ -0,0 +1,136 @@
+use std::mem;
+use async_graphql::{ComplexObject, Context, Enum, InputObject, Result, SimpleObject};
+use rocket::http::Status;
+use sqlx::{PgPool, query_as};
+
+#[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[repr(u8)]
+pub enum GradeScaleKind {
+    /// Percentage bands mapped to letters A–E.
+    Letter = 0,
+    /// Percentage bands mapped to letters with GPA points.
+    Gpa = 1,
+    /// User-defined bands.
+    Custom = 2,
+}
+
+impl GradeScaleKind {
+    /// The bands a new scale of this kind starts with if none are given.
+    pub fn default_bands(self) -> Vec<GradeBandInput> {
+        let points: &[Option<f64>] = match self {
+            Self::Letter => &[None; 5],
+            Self::Gpa => &[Some(4.0), Some(3.0), Some(2.0), Some(1.0), Some(0.0)],
+            Self::Custom => return Vec::new(),
+        };
+        [(85.0, "A"), (70.0, "B"), (55.0, "C"), (40.0, "D"), (0.0, "E")].into_iter()
+            .zip(points)
+            .map(|((minimum, label), points)| GradeBandInput {
+                minimum,
+                label: label.to_string(),
+                points: *points,
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<u8> for GradeScaleKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if !(0..=2).contains(&value) {
+            return Err(());
+        }
+        unsafe {
+            Ok(mem::transmute(value))
+        }
+    }
+}
+
+impl From<i16> for GradeScaleKind {
+    fn from(value: i16) -> Self {
+        u8::try_from(value).unwrap().try_into().unwrap()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct GradeScale {
+    /// The ID of the grade scale.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    /// The name of the grade scale.
+    pub name: String,
+
+    /// The kind of grade scale.
+    pub kind: GradeScaleKind,
+}
+
+#[ComplexObject]
+impl GradeScale {
+    /// The bands of the grade scale, highest first.
+    async fn bands(&self, ctx: &Context<'_>) -> Result<Vec<GradeBand>> {
+        query_as!(GradeBand, /* language=postgresql */ "SELECT * FROM grade_bands WHERE scale = $1 ORDER BY minimum DESC;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct GradeBand {
+    /// The ID of the band.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub scale: i32,
+
+    /// The lowest percentage that is awarded this grade.
+    pub minimum: f64,
+
+    /// The grade awarded, for example "A".
+    pub label: String,
+
+    /// The GPA points awarded, if the scale uses them.
+    pub points: Option<f64>,
+}
+
+#[derive(InputObject)]
+pub struct GradeBandInput {
+    /// The lowest percentage that is awarded this grade.
+    #[graphql(validator(minimum = 0, maximum = 100))]
+    pub minimum: f64,
+
+    /// The grade awarded, for example "A".
+    #[graphql(validator(max_length = 16))]
+    pub label: String,
+
+    /// The GPA points awarded. Default: null
+    pub points: Option<f64>,
+}
+
+#[derive(SimpleObject)]
+pub struct Grade {
+    /// The grade awarded, for example "A".
+    pub label: String,
+
+    /// The GPA points awarded, if the scale uses them.
+    pub points: Option<f64>,
+
+    /// The percentage the grade was calculated from.
+    pub percentage: f64,
+}
+
+impl Grade {
+    /// Converts a percentage to a grade using the given scale.
+    /// Returns `None` if no band of the scale covers the percentage.
+    pub async fn from_percentage(pool: &PgPool, scale: i32, percentage: f64) -> Result<Option<Self>> {
+        Ok(query_as!(GradeBand, /* language=postgresql */ "SELECT * FROM grade_bands WHERE scale = $1 AND minimum <= $2 ORDER BY minimum DESC LIMIT 1;", scale, percentage)
+            .fetch_optional(pool).await?
+            .map(|band| Self {
+                label: band.label,
+                points: band.points,
+                percentage,
+            }))
+    }
+}
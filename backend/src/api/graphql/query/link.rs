@@ -0,0 +1,54 @@
+use async_graphql::{Result, SimpleObject};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::note::Note;
+
+/// A link from one note to another.
+#[derive(SimpleObject)]
+pub struct NoteLink {
+    /// The ID of the note containing the link.
+    pub source: i32,
+
+    /// The ID of the linked note.
+    pub target: i32,
+}
+
+/// The notes of a subject and the links between them.
+#[derive(SimpleObject)]
+pub struct NoteGraph {
+    /// The notes in the graph.
+    pub nodes: Vec<Note>,
+
+    /// The links between the notes in the graph.
+    pub edges: Vec<NoteLink>,
+}
+
+/// Builds the graph of the links between the notes of one of the user's subjects.
+/// With `neighbours`, notes of other subjects that the subject's notes link to or are linked from are included, as long as the user can access them.
+pub async fn graph(pool: &PgPool, user: i32, subject: i32, neighbours: bool) -> Result<NoteGraph> {
+    query(/* language=postgresql */ "SELECT 1 FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;")
+        .bind(user)
+        .bind(subject)
+        .fetch_optional(pool).await?.ok_or(Status::NotFound)?;
+    let nodes = query_as!(Note, /* language=postgresql */ "
+        SELECT * FROM notes
+        WHERE subject = $2 AND trash IS NULL
+            OR $3 AND note_role(id, $1) IS NOT NULL AND id IN (
+                SELECT target FROM note_links JOIN notes AS source ON source.id = note_links.source WHERE source.subject = $2
+                UNION
+                SELECT source FROM note_links JOIN notes AS target ON target.id = note_links.target WHERE target.subject = $2
+            )
+        ORDER BY date, id;
+        ", user, subject, neighbours)
+        .fetch_all(pool).await?;
+    let ids = nodes.iter().map(|note| note.id).collect::<Vec<_>>();
+    let edges = query!(/* language=postgresql */ "SELECT source, target FROM note_links WHERE source = ANY($1) AND target = ANY($1) ORDER BY source, target;", &ids)
+        .fetch_all(pool).await?
+        .into_iter()
+        .map(|link| NoteLink {
+            source: link.source,
+            target: link.target,
+        })
+        .collect();
+    Ok(NoteGraph { nodes, edges })
+}
@@ -3,15 +3,42 @@ pub(super) mod session;
 pub(super) mod subject;
 pub(super) mod todo;
 pub(super) mod note;
-mod assessment;
+pub(super) mod assessment;
+pub(super) mod grade_scale;
+pub(super) mod report;
+pub(super) mod calendar;
+pub(super) mod timetable;
+mod search;
+mod revision;
+pub(super) mod sharing;
+mod link;
+pub(super) mod tag;
+pub(super) mod flashcard;
+pub(super) mod template;
+pub(super) mod order;
+pub(super) mod trash;
 
+use std::collections::HashMap;
 use async_graphql::{Context, Result, Object};
+use chrono::NaiveDate;
 use rocket::http::Status;
-use sqlx::{PgPool, query_as};
+use sqlx::{PgPool, query, query_as};
 use crate::api::graphql::query::assessment::Assessment;
+use crate::api::graphql::query::calendar::{AcademicYear, Term};
+use crate::api::graphql::query::flashcard::{Card, Deck};
+use crate::api::graphql::query::grade_scale::GradeScale;
+use crate::api::graphql::query::link::NoteGraph;
 use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::order::TimestampOrder;
+use crate::api::graphql::query::report::Report;
+use crate::api::graphql::query::search::NoteSearchResult;
+use crate::api::graphql::query::sharing::UserGroup;
 use crate::api::graphql::query::subject::Subject;
-use crate::api::graphql::query::todo::Todo;
+use crate::api::graphql::query::tag::Tag;
+use crate::api::graphql::query::template::NoteTemplate;
+use crate::api::graphql::query::timetable::Lesson;
+use crate::api::graphql::query::todo::{Todo, TodoNode};
+use crate::api::graphql::query::trash::TrashEntry;
 use crate::auth::User;
 
 #[allow(clippy::module_name_repetitions)]
@@ -24,14 +51,28 @@ impl QueryRoot {
         Ok(ctx.data::<Option<User>>()?.clone())
     }
 
-    /// Get list of all notes owned by the authenticated user.
+    /// Get list of all notes owned by or shared with the authenticated user.
     /// Requires authentication.
-    async fn notes(&self, ctx: &Context<'_>) -> Result<Vec<Note>> {
+    async fn notes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get notes dated during the term with this ID. Default: null")] term: Option<i32>,
+        #[graphql(desc = "Only get notes with all of the authenticated user's tags with these IDs. Default: null")] tags: Option<Vec<i32>>,
+        #[graphql(desc = "Sort by when the notes were created or last changed instead. Default: null", default)] order: Option<TimestampOrder>,
+    ) -> Result<Vec<Note>> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        Ok(query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE owner = $1;", user.id)
+        let (from, to) = Term::bounds(pool, user.id, term).await?;
+        Ok(query_as!(Note, /* language=postgresql */ "
+            SELECT * FROM notes
+            WHERE (owner = $1 AND trash IS NULL OR id IN (SELECT shared_notes($1)))
+                AND ($2::DATE IS NULL OR date BETWEEN $2 AND $3)
+                AND ($4::INTEGER[] IS NULL OR $4 <@ ARRAY(SELECT tag FROM note_tags JOIN tags ON tags.id = note_tags.tag WHERE note = notes.id AND tags.owner = $1))
+            ORDER BY
+                timestamp_order($5, created_at, updated_at), id;
+            ", user.id, from, to, tags.as_deref(), order.map(|order| order as i16))
             .fetch_all(pool).await?)
     }
 
@@ -42,18 +83,107 @@ impl QueryRoot {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+        query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE id = $2 AND note_role(id, $1) IS NOT NULL LIMIT 1;", user.id, id)
             .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
     }
 
+    /// Get list of all notes other users have shared with the authenticated user, directly or through a group, newest first.
+    /// Requires authentication.
+    async fn shared_notes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get notes with all of the authenticated user's tags with these IDs. Default: null")] tags: Option<Vec<i32>>,
+        #[graphql(desc = "Sort by when the notes were created or last changed instead. Default: null", default)] order: Option<TimestampOrder>,
+    ) -> Result<Vec<Note>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(Note, /* language=postgresql */ "
+            SELECT * FROM notes
+            WHERE owner <> $1 AND id IN (SELECT shared_notes($1))
+                AND ($2::INTEGER[] IS NULL OR $2 <@ ARRAY(SELECT tag FROM note_tags JOIN tags ON tags.id = note_tags.tag WHERE note = notes.id AND tags.owner = $1))
+            ORDER BY
+                timestamp_order($3, created_at, updated_at), date DESC, id DESC;
+            ", user.id, tags.as_deref(), order.map(|order| order as i16))
+            .fetch_all(pool).await?)
+    }
+
+    /// Get list of all groups the authenticated user manages or belongs to.
+    /// Requires authentication.
+    async fn groups(&self, ctx: &Context<'_>) -> Result<Vec<UserGroup>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(UserGroup, /* language=postgresql */ "
+            SELECT * FROM user_groups
+            WHERE owner = $1 OR id IN (SELECT group_id FROM user_group_members WHERE user_id = $1)
+            ORDER BY name;
+            ", user.id)
+            .fetch_all(pool).await?)
+    }
+
+    /// Search the titles and text of the authenticated user's notes, best matches first.
+    /// Requires authentication.
+    #[allow(clippy::too_many_arguments)]
+    async fn search_notes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = r#"The text to search for. Supports quoted phrases, "or" and excluding words with "-"."#, validator(min_length = 1))] query: String,
+        #[graphql(desc = "Only search notes for the subject with this ID. Default: null")] subject: Option<i32>,
+        #[graphql(desc = "Only search notes dated on or after this day. Default: null")] from: Option<NaiveDate>,
+        #[graphql(desc = "Only search notes dated on or before this day. Default: null")] to: Option<NaiveDate>,
+        #[graphql(desc = "Only search notes with all of the tags with these IDs. Default: null")] tags: Option<Vec<i32>>,
+        #[graphql(desc = "The maximum number of results. Default: 20", default = 20, validator(minimum = 1, maximum = 100))] limit: i64,
+    ) -> Result<Vec<NoteSearchResult>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        search::search_notes(ctx.data::<PgPool>()?, user.id, &query, subject, from, to, tags.as_deref(), limit).await
+    }
+
+    /// Get the notes of one of the authenticated user's subjects and the links between them, for drawing how its topics connect.
+    /// Requires authentication.
+    async fn note_graph(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the subject to get the graph of.")] subject: i32,
+        #[graphql(desc = "Also include notes of other subjects that the subject's notes link to or are linked from. Default: false", default)] neighbours: bool,
+    ) -> Result<NoteGraph> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        link::graph(ctx.data::<PgPool>()?, user.id, subject, neighbours).await
+    }
+
     /// Get list of all subjects owned by the authenticated user.
     /// Requires authentication.
-    async fn subjects(&self, ctx: &Context<'_>) -> Result<Vec<Subject>> {
+    async fn subjects(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get subjects taken during the academic year with this ID. Default: null")] year: Option<i32>,
+        #[graphql(desc = "Only get subjects taken during the academic year of the term with this ID. Default: null")] term: Option<i32>,
+        #[graphql(desc = "Only get subjects that are or are not archived. Default: null")] archived: Option<bool>,
+        #[graphql(desc = "Sort by when the subjects were created or last changed instead. Default: null", default)] order: Option<TimestampOrder>,
+    ) -> Result<Vec<Subject>> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        Ok(query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE owner = $1;", user.id)
+        let term_year = match term {
+            Some(term) => Some(Term::academic_year(pool, user.id, term).await?.ok_or(Status::NotFound)?),
+            None => None,
+        };
+        Ok(query_as!(Subject, /* language=postgresql */ "
+            SELECT * FROM subjects
+            WHERE owner = $1 AND trash IS NULL
+                AND ($2::INTEGER IS NULL OR year = $2)
+                AND ($3::INTEGER IS NULL OR year = $3)
+                AND ($4::BOOLEAN IS NULL OR archived = $4)
+            ORDER BY
+                timestamp_order($5, created_at, updated_at), id;
+            ", user.id, year, term_year, archived, order.map(|order| order as i16))
             .fetch_all(pool).await?)
     }
 
@@ -64,18 +194,42 @@ impl QueryRoot {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+        query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;", user.id, id)
             .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
     }
 
-    /// Get list of all todos owned by the authenticated user.
+    /// Get list of all todos owned by the authenticated user, in the order they were arranged, with each to-do's children right after it.
     /// Requires authentication.
-    async fn todos(&self, ctx: &Context<'_>) -> Result<Vec<Todo>> {
+    async fn todos(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get to-dos due during the term with this ID. Default: null")] term: Option<i32>,
+        #[graphql(desc = "Only get to-dos with all of the tags with these IDs. Default: null")] tags: Option<Vec<i32>>,
+        #[graphql(desc = "Sort by when the to-dos were created or last changed instead. Default: null", default)] order: Option<TimestampOrder>,
+    ) -> Result<Vec<Todo>> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        Ok(query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE owner = $1 ORDER BY title;", user.id)
+        let (from, to) = Term::bounds(pool, user.id, term).await?;
+        // Positions only order siblings, so to-dos are sorted by the positions of their ancestors first
+        Ok(query_as!(Todo, /* language=postgresql */ "
+            WITH RECURSIVE tree AS (
+                SELECT id, ARRAY[position, id] AS path FROM todos
+                WHERE owner = $1 AND trash IS NULL AND parent IS NULL
+                UNION ALL
+                SELECT todos.id, tree.path || ARRAY[todos.position, todos.id]
+                FROM todos
+                JOIN tree ON todos.parent = tree.id
+                WHERE todos.trash IS NULL
+            ) CYCLE id SET cycle USING visited
+            SELECT todos.* FROM todos
+            JOIN tree ON tree.id = todos.id AND NOT tree.cycle
+            WHERE ($2::DATE IS NULL OR due BETWEEN $2 AND $3)
+                AND ($4::INTEGER[] IS NULL OR $4 <@ ARRAY(SELECT tag FROM todo_tags WHERE todo = todos.id))
+            ORDER BY
+                timestamp_order($5, created_at, updated_at), tree.path;
+            ", user.id, from, to, tags.as_deref(), order.map(|order| order as i16))
             .fetch_all(pool).await?)
     }
 
@@ -86,16 +240,86 @@ impl QueryRoot {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+        query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE owner = $1 AND id = $2 AND trash IS NULL LIMIT 1;", user.id, id)
             .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
     }
 
-    async fn assessments(&self, ctx: &Context<'_>) -> Result<Vec<Assessment>> {
+    /// Suggests which open to-dos to work on now, best first.
+    /// To-dos are ranked by how soon they are due, which counts the most, then by their priority, the weight of their assessment and how quick they are.
+    /// To-dos with open children are left out in favour of the children, as are archived to-dos.
+    /// Requires authentication.
+    async fn next_up(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The maximum number of to-dos. Default: 5", default = 5, validator(minimum = 1, maximum = 100))] limit: usize,
+        #[graphql(desc = "Only suggest to-dos estimated to take at most this many minutes. To-dos without estimates are always suggested. Default: null", default)] available_minutes: Option<i32>,
+    ) -> Result<Vec<Todo>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        let todos = query_as!(Todo, /* language=postgresql */ "
+            SELECT * FROM todos
+            WHERE owner = $1 AND NOT completed AND NOT archived AND trash IS NULL
+                AND ($2::INTEGER IS NULL OR estimated_minutes IS NULL OR estimated_minutes <= $2)
+                AND NOT EXISTS (SELECT 1 FROM todos AS children WHERE children.parent = todos.id AND NOT children.completed AND children.trash IS NULL);
+            ", user.id, available_minutes)
+            .fetch_all(pool).await?;
+        let weights = query!(/* language=postgresql */ "SELECT id, weight FROM assessments WHERE owner = $1 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL);", user.id)
+            .fetch_all(pool).await?
+            .into_iter()
+            .map(|assessment| (assessment.id, assessment.weight))
+            .collect::<HashMap<_, _>>();
+        let today = user.today();
+        let mut scored = todos.into_iter()
+            .map(|todo| (todo.score(today, todo.assessment.and_then(|assessment| weights.get(&assessment).copied())), todo))
+            .collect::<Vec<_>>();
+        scored.sort_by(|(a_score, a), (b_score, b)| b_score.total_cmp(a_score)
+            .then(a.due.is_none().cmp(&b.due.is_none()))
+            .then(a.due.cmp(&b.due))
+            .then(a.position.cmp(&b.position)));
+        Ok(scored.into_iter().take(limit).map(|(_, todo)| todo).collect())
+    }
+
+    /// Get the trees of all top-level to-dos owned by the authenticated user, or the tree under one to-do, with their progress.
+    /// Loads every to-do at once, so it is faster than following `children`.
+    /// Requires authentication.
+    async fn todo_tree(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the to-do to get the tree under, or null for every top-level to-do. Default: null", default)] root: Option<i32>,
+    ) -> Result<Vec<TodoNode>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        let tree = TodoNode::load(pool, user.id, root).await?;
+        if root.is_some() && tree.is_empty() {
+            return Err(Status::NotFound.into());
+        }
+        Ok(tree)
+    }
+
+    async fn assessments(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get assessments due during the term with this ID. Default: null")] term: Option<i32>,
+        #[graphql(desc = "Only get assessments with all of the tags with these IDs. Default: null")] tags: Option<Vec<i32>>,
+        #[graphql(desc = "Sort by when the assessments were created or last changed instead. Default: null", default)] order: Option<TimestampOrder>,
+    ) -> Result<Vec<Assessment>> {
         let Some(user) = ctx.data::<Option<User>>()? else {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        Ok(query_as!(Assessment, /* language=postgresql */ "SELECT * FROM assessments WHERE owner = $1;", user.id)
+        let (from, to) = Term::bounds(pool, user.id, term).await?;
+        Ok(query_as!(Assessment, /* language=postgresql */ "
+            SELECT * FROM assessments
+            WHERE owner = $1 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL)
+                AND ($2::DATE IS NULL OR due BETWEEN $2 AND $3)
+                AND ($4::INTEGER[] IS NULL OR $4 <@ ARRAY(SELECT tag FROM assessment_tags WHERE assessment = assessments.id))
+            ORDER BY
+                timestamp_order($5, created_at, updated_at), id;
+            ", user.id, from, to, tags.as_deref(), order.map(|order| order as i16))
             .fetch_all(pool).await?)
     }
 
@@ -104,7 +328,211 @@ impl QueryRoot {
             return Err(Status::Unauthorized.into());
         };
         let pool = ctx.data::<PgPool>()?;
-        query_as!(Assessment, /* language=postgresql */ "SELECT * FROM assessments WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+        query_as!(Assessment, /* language=postgresql */ "SELECT * FROM assessments WHERE owner = $1 AND id = $2 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;", user.id, id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Get list of all flashcard decks owned by the authenticated user, ordered by name.
+    /// Requires authentication.
+    async fn decks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get decks for the subject with this ID. Default: null")] subject: Option<i32>,
+    ) -> Result<Vec<Deck>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(Deck, /* language=postgresql */ "SELECT * FROM decks WHERE owner = $1 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) AND ($2::INTEGER IS NULL OR subject = $2) ORDER BY name;", user.id, subject)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get a single flashcard deck by ID.
+    /// Requires authentication.
+    async fn deck(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the deck to get.")] id: i32) -> Result<Deck> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query_as!(Deck, /* language=postgresql */ "SELECT * FROM decks WHERE owner = $1 AND id = $2 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;", user.id, id)
             .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
     }
+
+    /// Get the authenticated user's flashcards that are due for review today, most overdue first.
+    /// Requires authentication.
+    async fn due_cards(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only get cards in the deck with this ID. Default: null")] deck: Option<i32>,
+        #[graphql(desc = "Only get cards in decks for the subject with this ID. Default: null")] subject: Option<i32>,
+        #[graphql(desc = "The maximum number of cards. Default: 50", default = 50, validator(minimum = 1, maximum = 500))] limit: i64,
+    ) -> Result<Vec<Card>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(Card, /* language=postgresql */ "
+            SELECT cards.* FROM cards
+            JOIN decks ON decks.id = cards.deck
+            WHERE decks.owner = $1 AND decks.subject IN (SELECT id FROM subjects WHERE trash IS NULL)
+                AND cards.due <= $2
+                AND ($3::INTEGER IS NULL OR decks.id = $3)
+                AND ($4::INTEGER IS NULL OR decks.subject = $4)
+            ORDER BY cards.due, cards.id
+            LIMIT $5;
+            ", user.id, user.today(), deck, subject, limit)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get list of all tags owned by the authenticated user, ordered by name.
+    /// Requires authentication.
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(Tag, /* language=postgresql */ "SELECT * FROM tags WHERE owner = $1 ORDER BY name;", user.id)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get a single tag by ID.
+    /// Requires authentication.
+    async fn tag(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the tag to get.")] id: i32) -> Result<Tag> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query_as!(Tag, /* language=postgresql */ "SELECT * FROM tags WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Get list of all note templates owned by the authenticated user, ordered by name.
+    /// Requires authentication.
+    async fn note_templates(&self, ctx: &Context<'_>) -> Result<Vec<NoteTemplate>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(NoteTemplate, /* language=postgresql */ "SELECT * FROM note_templates WHERE owner = $1 ORDER BY name, id;", user.id)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get a single note template by ID.
+    /// Requires authentication.
+    async fn note_template(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the template to get.")] id: i32) -> Result<NoteTemplate> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query_as!(NoteTemplate, /* language=postgresql */ "SELECT * FROM note_templates WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Get the entries in the authenticated user's trash, most recently deleted first.
+    /// Each entry is a deleted note, to-do or subject along with everything deleted with it.
+    /// Requires authentication.
+    async fn trash(&self, ctx: &Context<'_>) -> Result<Vec<TrashEntry>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(TrashEntry, /* language=postgresql */ "SELECT * FROM trash WHERE owner = $1 ORDER BY deleted_at DESC, id DESC;", user.id)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get list of all grade scales owned by the authenticated user.
+    /// Requires authentication.
+    async fn grade_scales(&self, ctx: &Context<'_>) -> Result<Vec<GradeScale>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(GradeScale, /* language=postgresql */ "SELECT * FROM grade_scales WHERE owner = $1 ORDER BY name;", user.id)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get a single grade scale by ID.
+    /// Requires authentication.
+    async fn grade_scale(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the grade scale to get.")] id: i32) -> Result<GradeScale> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query_as!(GradeScale, /* language=postgresql */ "SELECT * FROM grade_scales WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Get a summary of the authenticated user's subjects between two dates or during a term.
+    /// Either both `from` and `to` or only `term` must be given.
+    /// Requires authentication.
+    async fn report(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The first day to include in the report. Default: null")] from: Option<NaiveDate>,
+        #[graphql(desc = "The last day to include in the report. Default: null")] to: Option<NaiveDate>,
+        #[graphql(desc = "The ID of the term to report on. Default: null")] term: Option<i32>,
+    ) -> Result<Report> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        let (from, to) = match (from, to, term) {
+            (Some(from), Some(to), None) if from <= to => (from, to),
+            (None, None, Some(term)) => Term::dates(pool, user.id, term).await?.ok_or(Status::NotFound)?,
+            _ => return Err(Status::BadRequest.into()),
+        };
+        Report::build(pool, user.id, from, to).await
+    }
+
+    /// Get list of all academic years owned by the authenticated user.
+    /// Requires authentication.
+    async fn academic_years(&self, ctx: &Context<'_>) -> Result<Vec<AcademicYear>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        Ok(query_as!(AcademicYear, /* language=postgresql */ "SELECT * FROM academic_years WHERE owner = $1 ORDER BY start_date;", user.id)
+            .fetch_all(pool).await?)
+    }
+
+    /// Get a single academic year by ID.
+    /// Requires authentication.
+    async fn academic_year(&self, ctx: &Context<'_>, #[graphql(desc = "The ID of the academic year to get.")] id: i32) -> Result<AcademicYear> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        query_as!(AcademicYear, /* language=postgresql */ "SELECT * FROM academic_years WHERE owner = $1 AND id = $2 LIMIT 1;", user.id, id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Get the authenticated user's lessons on a day, ordered by period.
+    /// There are no lessons on days that are not school days.
+    /// Requires authentication.
+    async fn timetable(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The day to get lessons for. Default: today")] date: Option<NaiveDate>,
+    ) -> Result<Vec<Lesson>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let date = date.unwrap_or_else(|| user.today());
+        timetable::lessons(ctx.data::<PgPool>()?, user.id, date).await
+    }
+
+    /// Get the first school day after a date, skipping weekends, holidays, pupil-free days and the breaks between terms.
+    /// Null if there is no school day within a year.
+    /// Requires authentication.
+    async fn next_school_day(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The day to start searching after. Default: today")] after: Option<NaiveDate>,
+    ) -> Result<Option<NaiveDate>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let after = after.unwrap_or_else(|| user.today());
+        Ok(calendar::next_school_day(ctx.data::<PgPool>()?, user.id, after).await?)
+    }
 }
@@ -1,8 +1,13 @@
 use async_graphql::{ComplexObject, Context, SimpleObject, Result};
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use rocket::http::Status;
-use sqlx::{PgPool, query_as};
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::revision::{BlockChange, diff, NoteRevision};
+use crate::api::graphql::query::sharing::{NoteGrant, NoteRole};
 use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::tag::Tag;
+use crate::api::graphql::query::user::UserProfile;
+use crate::auth::User;
 
 #[derive(SimpleObject)]
 #[graphql(complex)]
@@ -17,19 +22,140 @@ pub struct Note {
     pub title: String,
     #[graphql(skip)]
     pub subject: Option<i32>,
-    /// The document’s content as understood by TipTap
+    /// The document’s content as understood by `TipTap`
     pub content: serde_json::Value,
+    #[graphql(skip)]
+    pub last_edited_by: Option<i32>,
+    /// When the note was created
+    pub created_at: NaiveDateTime,
+    /// When the note last changed, not counting changes to its tags or sharing
+    pub updated_at: NaiveDateTime,
+    #[graphql(skip)]
+    pub trash: Option<i32>,
 }
 
 #[ComplexObject]
 impl Note {
     /// The subject of the note.
+    /// Subjects are private, so this is null for users the note is shared with.
     async fn subject(&self, ctx: &Context<'_>) -> Result<Option<Subject>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
         Ok(if let Some(subject) = self.subject {
-            Some(query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE id = $1 LIMIT 1;", subject)
-                .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?)
+            query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE id = $1 AND owner = $2 LIMIT 1;", subject, user.id)
+                .fetch_optional(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?
+        } else {
+            None
+        })
+    }
+
+    /// The user who owns the note.
+    async fn owner(&self, ctx: &Context<'_>) -> Result<UserProfile> {
+        UserProfile::get(ctx.data::<PgPool>()?, self.owner).await
+    }
+
+    /// The user who last changed the note's title, date or content. Null if they no longer exist.
+    async fn last_edited_by(&self, ctx: &Context<'_>) -> Result<Option<UserProfile>> {
+        Ok(if let Some(user) = self.last_edited_by {
+            Some(UserProfile::get(ctx.data::<PgPool>()?, user).await?)
         } else {
             None
         })
     }
+
+    /// What the authenticated user can do with the note.
+    async fn role(&self, ctx: &Context<'_>) -> Result<NoteRole> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        NoteRole::of(ctx.data::<PgPool>()?, self.id, user.id).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// The authenticated user's tags on the note, ordered by name.
+    /// Tags are personal, so users the note is shared with do not see each other's tags.
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query_as!(Tag, /* language=postgresql */ "
+            SELECT tags.* FROM note_tags
+            JOIN tags ON tags.id = note_tags.tag
+            WHERE note_tags.note = $1 AND tags.owner = $2
+            ORDER BY tags.name;
+            ", self.id, user.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The users and groups the note is shared with.
+    /// Only visible to the note's owners.
+    async fn grants(&self, ctx: &Context<'_>) -> Result<Vec<NoteGrant>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        if NoteRole::of(pool, self.id, user.id).await? != Some(NoteRole::Owner) {
+            return Err(Status::Forbidden.into());
+        }
+        query_as!(NoteGrant, /* language=postgresql */ "SELECT * FROM note_grants WHERE note = $1 ORDER BY id;", self.id)
+            .fetch_all(pool).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The notes this note links to that the authenticated user can access, ordered by date.
+    async fn links(&self, ctx: &Context<'_>) -> Result<Vec<Note>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query_as!(Note, /* language=postgresql */ "
+            SELECT notes.* FROM note_links
+            JOIN notes ON notes.id = note_links.target
+            WHERE note_links.source = $1 AND note_role(notes.id, $2) IS NOT NULL
+            ORDER BY notes.date, notes.id;
+            ", self.id, user.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The notes linking to this note that the authenticated user can access, ordered by date.
+    async fn backlinks(&self, ctx: &Context<'_>) -> Result<Vec<Note>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        query_as!(Note, /* language=postgresql */ "
+            SELECT notes.* FROM note_links
+            JOIN notes ON notes.id = note_links.source
+            WHERE note_links.target = $1 AND note_role(notes.id, $2) IS NOT NULL
+            ORDER BY notes.date, notes.id;
+            ", self.id, user.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The saved revisions of the note's content, newest first.
+    /// Rapid edits are saved as a single revision.
+    async fn revisions(&self, ctx: &Context<'_>) -> Result<Vec<NoteRevision>> {
+        query_as!(NoteRevision, /* language=postgresql */ "
+            SELECT *, word_count - LAG(word_count) OVER (ORDER BY created_at, id) AS word_count_delta
+            FROM note_revisions
+            WHERE note = $1
+            ORDER BY created_at DESC, id DESC;
+            ", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// Compares the blocks of two revisions of the note.
+    async fn diff(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the older revision.")] from: i32,
+        #[graphql(desc = "The ID of the newer revision. Default: null, meaning the note's current content")] to: Option<i32>,
+    ) -> Result<Vec<BlockChange>> {
+        let pool = ctx.data::<PgPool>()?;
+        let old = query!(/* language=postgresql */ "SELECT content FROM note_revisions WHERE note = $1 AND id = $2 LIMIT 1;", self.id, from)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound)?.content;
+        let new = match to {
+            Some(to) => &query!(/* language=postgresql */ "SELECT content FROM note_revisions WHERE note = $1 AND id = $2 LIMIT 1;", self.id, to)
+                .fetch_optional(pool).await?.ok_or(Status::NotFound)?.content,
+            None => &self.content,
+        };
+        Ok(diff(&old, new))
+    }
 }
@@ -0,0 +1,16 @@
+use async_graphql::Enum;
+
+/// An order for lists of notes, to-dos, subjects or assessments by when they were created or last changed.
+/// Queries sort by the `timestamp_order` SQL function, which must handle every discriminant.
+#[derive(Enum, Eq, PartialEq, Copy, Clone)]
+#[repr(u8)]
+pub enum TimestampOrder {
+    /// Most recently created first.
+    CreatedNewest = 0,
+    /// Least recently created first.
+    CreatedOldest = 1,
+    /// Most recently changed first.
+    UpdatedNewest = 2,
+    /// Least recently changed first.
+    UpdatedOldest = 3,
+}
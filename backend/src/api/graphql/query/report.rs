@@ -0,0 +1,75 @@
+use async_graphql::{Result, SimpleObject};
+use chrono::NaiveDate;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::assessment::Assessment;
+use crate::api::graphql::query::grade_scale::Grade;
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::todo::Todo;
+
+#[derive(SimpleObject)]
+pub struct Report {
+    /// The first day covered by the report.
+    pub from: NaiveDate,
+
+    /// The last day covered by the report.
+    pub to: NaiveDate,
+
+    /// A summary of each subject with activity during the report's period, ordered by name.
+    pub subjects: Vec<SubjectReport>,
+}
+
+#[derive(SimpleObject)]
+pub struct SubjectReport {
+    /// The subject being summarised.
+    pub subject: Subject,
+
+    /// The subject's assessments due during the period, ordered by due date.
+    pub assessments: Vec<Assessment>,
+
+    /// The subject's grade calculated from the marks of `assessments` weighted by their weights.
+    /// Null if the subject has no grade scale or none of the assessments have been marked.
+    pub grade: Option<Grade>,
+
+    /// The subject's completed to-dos due during the period, ordered by due date.
+    pub completed_todos: Vec<Todo>,
+
+    /// The number of notes for the subject's classes during the period.
+    pub note_count: i64,
+}
+
+impl Report {
+    /// Assembles a report of the user's subjects between `from` and `to` inclusive.
+    pub async fn build(pool: &PgPool, owner: i32, from: NaiveDate, to: NaiveDate) -> Result<Self> {
+        let subjects = query_as!(Subject, /* language=postgresql */ "
+            SELECT * FROM subjects
+            WHERE owner = $1 AND trash IS NULL AND (
+                EXISTS (SELECT 1 FROM assessments WHERE subject = subjects.id AND due BETWEEN $2 AND $3)
+                OR EXISTS (SELECT 1 FROM todos WHERE subject = subjects.id AND completed AND due BETWEEN $2 AND $3 AND trash IS NULL)
+                OR EXISTS (SELECT 1 FROM notes WHERE subject = subjects.id AND date BETWEEN $2 AND $3 AND trash IS NULL)
+            )
+            ORDER BY name;
+            ", owner, from, to)
+            .fetch_all(pool).await?;
+        let mut reports = Vec::with_capacity(subjects.len());
+        for subject in subjects {
+            let assessments = query_as!(Assessment, /* language=postgresql */ "SELECT * FROM assessments WHERE subject = $1 AND due BETWEEN $2 AND $3 ORDER BY due, due_period;", subject.id, from, to)
+                .fetch_all(pool).await?;
+            let completed_todos = query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE subject = $1 AND completed AND due BETWEEN $2 AND $3 AND trash IS NULL ORDER BY due, title;", subject.id, from, to)
+                .fetch_all(pool).await?;
+            let note_count = query!(/* language=postgresql */ r#"SELECT COUNT(*) AS "count!" FROM notes WHERE subject = $1 AND date BETWEEN $2 AND $3 AND trash IS NULL;"#, subject.id, from, to)
+                .fetch_one(pool).await?.count;
+            reports.push(SubjectReport {
+                grade: subject.grade_between(pool, Some(from), Some(to)).await?,
+                subject,
+                assessments,
+                completed_todos,
+                note_count,
+            });
+        }
+        Ok(Self {
+            from,
+            to,
+            subjects: reports,
+        })
+    }
+}
@@ -0,0 +1,81 @@
+use async_graphql::{Enum, SimpleObject};
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+#[derive(SimpleObject)]
+pub struct NoteRevision {
+    /// The ID of the revision.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub note: i32,
+
+    /// The note's content before it was changed, as understood by `TipTap`.
+    pub content: Value,
+
+    /// The number of words in the revision.
+    pub word_count: i32,
+
+    /// The date and time the revision was saved.
+    pub created_at: NaiveDateTime,
+
+    /// The number of words added since the previous revision, negative if words were removed.
+    /// Null for the oldest revision.
+    pub word_count_delta: Option<i32>,
+}
+
+#[derive(Enum, Eq, PartialEq, Copy, Clone)]
+pub enum BlockChangeKind {
+    /// The block is in both documents.
+    Unchanged,
+    /// The block is only in the newer document.
+    Added,
+    /// The block is only in the older document.
+    Removed,
+}
+
+#[derive(SimpleObject)]
+pub struct BlockChange {
+    /// How the block changed.
+    pub kind: BlockChangeKind,
+
+    /// The block, such as a paragraph or heading, as understood by `TipTap`.
+    pub block: Value,
+}
+
+fn blocks(document: &Value) -> &[Value] {
+    document.get("content").and_then(Value::as_array).map_or(&[], Vec::as_slice)
+}
+
+/// Compares the top-level blocks of two `TipTap` documents.
+/// Edited blocks appear as the old block removed followed by the new block added.
+pub fn diff(old: &Value, new: &Value) -> Vec<BlockChange> {
+    let (old, new) = (blocks(old), blocks(new));
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            changes.push(BlockChange { kind: BlockChangeKind::Unchanged, block: new[j].clone() });
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lengths[i][j + 1] > lengths[i + 1][j]) {
+            changes.push(BlockChange { kind: BlockChangeKind::Added, block: new[j].clone() });
+            j += 1;
+        } else {
+            changes.push(BlockChange { kind: BlockChangeKind::Removed, block: old[i].clone() });
+            i += 1;
+        }
+    }
+    changes
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use async_graphql::{Result, SimpleObject};
+use chrono::NaiveDate;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::note::Note;
+
+#[derive(SimpleObject)]
+pub struct NoteSearchResult {
+    /// The matching note.
+    pub note: Note,
+
+    /// How well the note matches the search, higher is better.
+    pub rank: f64,
+
+    /// An excerpt of the note with matches wrapped in `<mark>` tags.
+    /// The rest of the excerpt is HTML-escaped.
+    pub snippet: String,
+}
+
+/// Searches the titles and text of the user's notes, best matches first.
+/// The query uses web search syntax, for example `"acid base" -titration`.
+#[allow(clippy::too_many_arguments)]
+pub async fn search_notes(
+    pool: &PgPool,
+    owner: i32,
+    search: &str,
+    subject: Option<i32>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    tags: Option<&[i32]>,
+    limit: i64,
+) -> Result<Vec<NoteSearchResult>> {
+    let matches = query!(/* language=postgresql */ r#"
+        SELECT
+            notes.id,
+            ts_rank(note_search.document, search)::DOUBLE PRECISION AS "rank!",
+            ts_headline(
+                'english',
+                replace(replace(replace(notes.title || ' — ' || tiptap_text(notes.content), '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                search,
+                'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, FragmentDelimiter=" … "'
+            ) AS "snippet!"
+        FROM notes
+        JOIN note_search ON note_search.note = notes.id,
+            websearch_to_tsquery('english', $2) AS search
+        WHERE notes.owner = $1 AND notes.trash IS NULL
+            AND note_search.document @@ search
+            AND ($3::INTEGER IS NULL OR notes.subject = $3)
+            AND ($4::DATE IS NULL OR notes.date >= $4)
+            AND ($5::DATE IS NULL OR notes.date <= $5)
+            AND ($6::INTEGER[] IS NULL OR $6 <@ ARRAY(SELECT tag FROM note_tags JOIN tags ON tags.id = note_tags.tag WHERE note = notes.id AND tags.owner = $1))
+        ORDER BY 2 DESC, notes.date DESC
+        LIMIT $7;
+        "#, owner, search, subject, from, to, tags, limit)
+        .fetch_all(pool).await?;
+    let ids = matches.iter().map(|result| result.id).collect::<Vec<_>>();
+    let mut notes = query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE id = ANY($1);", &ids)
+        .fetch_all(pool).await?
+        .into_iter()
+        .map(|note| (note.id, note))
+        .collect::<HashMap<_, _>>();
+    Ok(matches.into_iter()
+        .filter_map(|result| Some(NoteSearchResult {
+            note: notes.remove(&result.id)?,
+            rank: result.rank,
+            snippet: result.snippet,
+        }))
+        .collect())
+}
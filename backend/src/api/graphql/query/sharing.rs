@@ -0,0 +1,119 @@
+use std::mem;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::user::UserProfile;
+
+#[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[repr(u8)]
+pub enum NoteRole {
+    /// Can read the note.
+    Viewer = 0,
+    /// Can read and edit the note.
+    Editor = 1,
+    /// Can read, edit, share and delete the note.
+    Owner = 2,
+}
+
+impl NoteRole {
+    /// Gets the user's role for a note, or `None` if the note does not exist or is not shared with them.
+    pub async fn of(pool: &PgPool, note: i32, user: i32) -> sqlx::Result<Option<Self>> {
+        Ok(query!(/* language=postgresql */ "SELECT note_role($1, $2) AS role;", note, user)
+            .fetch_one(pool).await?
+            .role
+            .map(Self::from))
+    }
+}
+
+impl TryFrom<u8> for NoteRole {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if !(0..=2).contains(&value) {
+            return Err(());
+        }
+        unsafe {
+            Ok(mem::transmute(value))
+        }
+    }
+}
+
+impl From<i16> for NoteRole {
+    fn from(value: i16) -> Self {
+        u8::try_from(value).unwrap().try_into().unwrap()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct NoteGrant {
+    /// The ID of the grant.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub note: i32,
+
+    #[graphql(skip)]
+    pub user_id: Option<i32>,
+
+    #[graphql(skip)]
+    pub group_id: Option<i32>,
+
+    /// What the user or group can do with the note.
+    pub role: NoteRole,
+}
+
+#[ComplexObject]
+impl NoteGrant {
+    /// The user the note is shared with. Null if it is shared with a group.
+    async fn user(&self, ctx: &Context<'_>) -> Result<Option<UserProfile>> {
+        Ok(if let Some(user) = self.user_id {
+            Some(UserProfile::get(ctx.data::<PgPool>()?, user).await?)
+        } else {
+            None
+        })
+    }
+
+    /// The group the note is shared with. Null if it is shared with a single user.
+    async fn group(&self, ctx: &Context<'_>) -> Result<Option<UserGroup>> {
+        Ok(if let Some(group) = self.group_id {
+            Some(query_as!(UserGroup, /* language=postgresql */ "SELECT * FROM user_groups WHERE id = $1 LIMIT 1;", group)
+                .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?)
+        } else {
+            None
+        })
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct UserGroup {
+    /// The ID of the group.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    /// The name of the group.
+    pub name: String,
+}
+
+#[ComplexObject]
+impl UserGroup {
+    /// The user who manages the group.
+    async fn owner(&self, ctx: &Context<'_>) -> Result<UserProfile> {
+        UserProfile::get(ctx.data::<PgPool>()?, self.owner).await
+    }
+
+    /// The members of the group, ordered by name.
+    async fn members(&self, ctx: &Context<'_>) -> Result<Vec<UserProfile>> {
+        query_as!(UserProfile, /* language=postgresql */ "
+            SELECT users.id, users.username, users.name
+            FROM user_group_members
+            JOIN users ON users.id = user_group_members.user_id
+            WHERE user_group_members.group_id = $1
+            ORDER BY users.name, users.id;
+            ", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+}
@@ -1,6 +1,14 @@
-use async_graphql::SimpleObject;
+use async_graphql::{ComplexObject, Context, Result, SimpleObject};
+use chrono::{NaiveDate, NaiveDateTime};
+use rocket::http::Status;
+use sqlx::{PgPool, query, query_as};
+use crate::api::graphql::query::calendar::AcademicYear;
+use crate::api::graphql::query::grade_scale::{Grade, GradeScale};
+use crate::api::graphql::query::template::NoteTemplate;
+use crate::api::graphql::query::timetable::TimetableEntry;
 
 #[derive(SimpleObject)]
+#[graphql(complex)]
 pub struct Subject {
     /// The ID of the subject.
     pub id: i32,
@@ -19,4 +27,91 @@ pub struct Subject {
     
     /// The Google Classroom ID of the subject.
     pub google_classroom_id: Option<String>,
+    
+    #[graphql(skip)]
+    pub grade_scale: Option<i32>,
+    
+    #[graphql(skip)]
+    pub year: Option<i32>,
+    
+    /// Whether the subject has been archived at the end of its academic year.
+    /// Archived subjects keep their notes, to-dos and assessments.
+    pub archived: bool,
+
+    #[graphql(skip)]
+    pub default_template: Option<i32>,
+
+    /// When the subject was created.
+    pub created_at: NaiveDateTime,
+
+    /// When the subject last changed.
+    pub updated_at: NaiveDateTime,
+
+    #[graphql(skip)]
+    pub trash: Option<i32>,
+}
+
+#[ComplexObject]
+impl Subject {
+    /// The grade scale used to report grades for the subject.
+    async fn grade_scale(&self, ctx: &Context<'_>) -> Result<Option<GradeScale>> {
+        Ok(if let Some(grade_scale) = self.grade_scale {
+            Some(query_as!(GradeScale, /* language=postgresql */ "SELECT * FROM grade_scales WHERE id = $1 LIMIT 1;", grade_scale)
+                .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?)
+        } else {
+            None
+        })
+    }
+
+    /// The academic year the subject is taken during.
+    async fn year(&self, ctx: &Context<'_>) -> Result<Option<AcademicYear>> {
+        Ok(if let Some(year) = self.year {
+            Some(query_as!(AcademicYear, /* language=postgresql */ "SELECT * FROM academic_years WHERE id = $1 LIMIT 1;", year)
+                .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?)
+        } else {
+            None
+        })
+    }
+
+    /// The template new notes for the subject are created from when they are not given content or another template.
+    async fn default_template(&self, ctx: &Context<'_>) -> Result<Option<NoteTemplate>> {
+        Ok(if let Some(template) = self.default_template {
+            Some(query_as!(NoteTemplate, /* language=postgresql */ "SELECT * FROM note_templates WHERE id = $1 LIMIT 1;", template)
+                .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?)
+        } else {
+            None
+        })
+    }
+
+    /// The subject's classes, ordered by week, day and period.
+    async fn timetable(&self, ctx: &Context<'_>) -> Result<Vec<TimetableEntry>> {
+        query_as!(TimetableEntry, /* language=postgresql */ "SELECT * FROM timetable_entries WHERE subject = $1 ORDER BY week NULLS FIRST, weekday, period;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The overall grade of the subject, calculated from the marks of its assessments weighted by their weights.
+    /// Null if the subject has no grade scale or no marked assessments.
+    async fn grade(&self, ctx: &Context<'_>) -> Result<Option<Grade>> {
+        self.grade_between(ctx.data::<PgPool>()?, None, None).await
+    }
+}
+
+impl Subject {
+    /// Calculates the subject's grade from the marks of its assessments due between `from` and `to` inclusive.
+    /// Either bound may be omitted.
+    pub async fn grade_between(&self, pool: &PgPool, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Option<Grade>> {
+        let Some(grade_scale) = self.grade_scale else {
+            return Ok(None);
+        };
+        let Some(percentage) = query!(/* language=postgresql */ "
+            SELECT SUM(weight * mark::DOUBLE PRECISION / mark_out_of) / NULLIF(SUM(weight), 0) * 100 AS percentage
+            FROM assessments
+            WHERE subject = $1 AND mark IS NOT NULL AND mark_out_of > 0
+                AND ($2::DATE IS NULL OR due >= $2) AND ($3::DATE IS NULL OR due <= $3);
+            ", self.id, from, to)
+            .fetch_one(pool).await?.percentage else {
+            return Ok(None);
+        };
+        Grade::from_percentage(pool, grade_scale, percentage).await
+    }
 }
@@ -0,0 +1,56 @@
+use async_graphql::{ComplexObject, Context, Result, SimpleObject};
+use rocket::http::Status;
+use sqlx::{PgPool, query_as};
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Tag {
+    /// The ID of the tag.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    /// The name of the tag, unique among the user's tags regardless of case.
+    pub name: String,
+
+    /// The colour of the tag as a hex code such as `#808080`.
+    pub colour: String,
+}
+
+#[derive(SimpleObject)]
+pub struct TagUsage {
+    /// The number of notes with the tag.
+    pub notes: i64,
+
+    /// The number of to-dos with the tag.
+    pub todos: i64,
+
+    /// The number of assessments with the tag.
+    pub assessments: i64,
+
+    /// The number of notes, to-dos and assessments with the tag.
+    pub total: i64,
+}
+
+#[ComplexObject]
+impl Tag {
+    /// How many items the tag is attached to.
+    async fn usage(&self, ctx: &Context<'_>) -> Result<TagUsage> {
+        query_as!(TagUsage, /* language=postgresql */ r#"
+            SELECT
+                notes AS "notes!",
+                todos AS "todos!",
+                assessments AS "assessments!",
+                notes + todos + assessments AS "total!"
+            FROM (
+                SELECT
+                    (SELECT COUNT(*) FROM note_tags JOIN notes ON notes.id = note_tags.note WHERE tag = $1 AND notes.trash IS NULL) AS notes,
+                    (SELECT COUNT(*) FROM todo_tags JOIN todos ON todos.id = todo_tags.todo WHERE tag = $1 AND todos.trash IS NULL) AS todos,
+                    (SELECT COUNT(*) FROM assessment_tags JOIN assessments ON assessments.id = assessment_tags.assessment
+                        WHERE tag = $1 AND assessments.subject IN (SELECT id FROM subjects WHERE trash IS NULL)) AS assessments
+            ) AS counts;
+            "#, self.id)
+            .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+}
@@ -0,0 +1,51 @@
+use async_graphql::{Result, SimpleObject};
+use chrono::NaiveDate;
+use rocket::http::Status;
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::tiptap::Node;
+use crate::api::graphql::tiptap::schema::validate_content;
+use crate::api::graphql::tiptap::template::{fill, fill_document};
+
+#[derive(SimpleObject)]
+pub struct NoteTemplate {
+    /// The ID of the template.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    /// The name of the template.
+    pub name: String,
+
+    /// The title of notes created from the template, which can contain the same placeholders as the content except `{{title}}`.
+    pub title: String,
+
+    /// The content of notes created from the template as understood by `TipTap`.
+    /// Text can contain the placeholders `{{date}}`, `{{title}}`, `{{subject.name}}` and `{{subject.class}}`,
+    /// which are replaced with the note's date, title and subject when a note is created.
+    pub content: serde_json::Value,
+}
+
+impl NoteTemplate {
+    /// Renders the title and content of a note created from the template.
+    /// The template's title is used unless `title` is given, and subject placeholders are left empty for notes without a subject.
+    pub fn render(&self, title: &str, date: NaiveDate, subject: Option<&Subject>) -> Result<(String, serde_json::Value)> {
+        let date = date.format("%-d %B %Y").to_string();
+        let mut values = vec![
+            ("date", date.as_str()),
+            ("subject.name", subject.map_or("", |subject| subject.name.as_str())),
+            ("subject.class", subject.map_or("", |subject| subject.class.as_str())),
+        ];
+        let title = if title.is_empty() {
+            // Placeholders can make the title longer than titles are allowed to be
+            fill(&self.title, &values).chars().take(255).collect()
+        } else {
+            title.to_string()
+        };
+        values.push(("title", &title));
+        let mut document = Node::from_value(&self.content).or(Err(Status::InternalServerError))?;
+        fill_document(&mut document, &values);
+        let content = validate_content(&serde_json::to_value(document)?)?;
+        Ok((title, content))
+    }
+}
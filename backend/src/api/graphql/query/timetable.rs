@@ -0,0 +1,133 @@
+use std::mem;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use chrono::{Datelike, NaiveDate};
+use rocket::http::Status;
+use sqlx::{PgPool, query_as};
+use crate::api::graphql::query::calendar::{AcademicYear, is_school_day};
+use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::subject::Subject;
+
+#[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[repr(u8)]
+pub enum Weekday {
+    Monday = 1,
+    Tuesday = 2,
+    Wednesday = 3,
+    Thursday = 4,
+    Friday = 5,
+    Saturday = 6,
+    Sunday = 7,
+}
+
+impl TryFrom<u8> for Weekday {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if !(1..=7).contains(&value) {
+            return Err(());
+        }
+        unsafe {
+            Ok(mem::transmute(value))
+        }
+    }
+}
+
+impl From<i16> for Weekday {
+    fn from(value: i16) -> Self {
+        u8::try_from(value).unwrap().try_into().unwrap()
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct TimetableEntry {
+    /// The ID of the timetable entry.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub subject: i32,
+
+    /// The day of the week the class meets.
+    pub weekday: Weekday,
+
+    /// The period the class meets.
+    pub period: i16,
+
+    /// The week of the rotation the class meets, starting at 1.
+    /// Null if the class meets every week.
+    pub week: Option<i16>,
+
+    /// The room the class meets in.
+    pub room: Option<String>,
+
+    /// The teacher of the class.
+    pub teacher: Option<String>,
+}
+
+#[ComplexObject]
+impl TimetableEntry {
+    /// The subject of the class.
+    async fn subject(&self, ctx: &Context<'_>) -> Result<Subject> {
+        query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE id = $1 LIMIT 1;", self.subject)
+            .fetch_one(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError)).map_err(Into::into)
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Lesson {
+    /// The date of the lesson.
+    pub date: NaiveDate,
+
+    /// The week of the rotation the lesson is in, starting at 1.
+    pub week: i16,
+
+    /// The timetable entry the lesson is an occurrence of.
+    pub entry: TimetableEntry,
+}
+
+#[ComplexObject]
+impl Lesson {
+    /// The note for the lesson's subject dated on the day of the lesson, if one has been created.
+    async fn note(&self, ctx: &Context<'_>) -> Result<Option<Note>> {
+        query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE subject = $1 AND date = $2 AND trash IS NULL ORDER BY id LIMIT 1;", self.entry.subject, self.date)
+            .fetch_optional(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+}
+
+/// Finds the week of the rotation `date` falls in, starting at 1.
+pub fn rotation_week(year: &AcademicYear, date: NaiveDate) -> i16 {
+    let start = year.start_date.week(chrono::Weekday::Mon).first_day();
+    let weeks = (date - start).num_weeks().rem_euclid(i64::from(year.rotation_weeks.max(1)));
+    i16::try_from(weeks).unwrap_or(0) + 1
+}
+
+/// Gets the user's lessons on `date`, ordered by period.
+/// There are no lessons on days that are not school days.
+pub async fn lessons(pool: &PgPool, owner: i32, date: NaiveDate) -> Result<Vec<Lesson>> {
+    if !is_school_day(pool, owner, date).await? {
+        return Ok(Vec::new());
+    }
+    let year = query_as!(AcademicYear, /* language=postgresql */ "SELECT * FROM academic_years WHERE owner = $1 AND $2 BETWEEN start_date AND end_date ORDER BY start_date DESC LIMIT 1;", owner, date)
+        .fetch_optional(pool).await?;
+    let week = year.as_ref().map_or(1, |year| rotation_week(year, date));
+    let weekday = i16::try_from(date.weekday().number_from_monday()).unwrap_or_default();
+    Ok(query_as!(TimetableEntry, /* language=postgresql */ "
+        SELECT timetable_entries.*
+        FROM timetable_entries
+        JOIN subjects ON subjects.id = timetable_entries.subject
+        WHERE subjects.owner = $1 AND subjects.trash IS NULL
+            AND (subjects.year IS NULL OR subjects.year = $2)
+            AND timetable_entries.weekday = $3
+            AND (timetable_entries.week IS NULL OR timetable_entries.week = $4)
+        ORDER BY timetable_entries.period;
+        ", owner, year.map(|year| year.id), weekday, week)
+        .fetch_all(pool).await?
+        .into_iter()
+        .map(|entry| Lesson {
+            date,
+            week,
+            entry,
+        })
+        .collect())
+}
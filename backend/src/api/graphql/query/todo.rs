@@ -1,8 +1,40 @@
-use async_graphql::{ComplexObject, Context, Result, SimpleObject};
-use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::mem;
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use chrono::{NaiveDate, NaiveDateTime};
 use rocket::http::Status;
 use sqlx::{PgPool, query_as};
+use crate::api::graphql::query::assessment::Assessment;
 use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::tag::Tag;
+
+#[derive(Enum, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+#[repr(u8)]
+pub enum TodoPriority {
+    Low = 0,
+    Normal = 1,
+    High = 2,
+    Urgent = 3,
+}
+
+impl TryFrom<u8> for TodoPriority {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if !(0..=3).contains(&value) {
+            return Err(());
+        }
+        unsafe {
+            Ok(mem::transmute(value))
+        }
+    }
+}
+
+impl From<i16> for TodoPriority {
+    fn from(value: i16) -> Self {
+        u8::try_from(value).unwrap().try_into().unwrap()
+    }
+}
 
 #[derive(SimpleObject)]
 #[graphql(complex)]
@@ -10,6 +42,7 @@ pub struct Todo {
     /// The ID of the to-do.
     pub id: i32,
 
+
     #[graphql(skip)]
     pub owner: i32,
 
@@ -31,7 +64,67 @@ pub struct Todo {
     /// Whether the to-do is archived.
     /// Archived todos are hidden from the default view.
     pub archived: bool,
+
+    /// Whether the to-do is a standing task, such as ongoing reading, that is listed separately.
+    /// Completing a standing to-do without a recurrence rule creates its next occurrence straight away, without a due date.
     pub standing: bool,
+
+    /// When the to-do was created.
+    pub created_at: NaiveDateTime,
+
+    /// When the to-do last changed.
+    pub updated_at: NaiveDateTime,
+
+    #[graphql(skip)]
+    pub trash: Option<i32>,
+
+    /// The iCalendar recurrence rule (RRULE) of the to-do, such as `FREQ=WEEKLY;BYDAY=MO`.
+    /// Completing the to-do creates its next occurrence, due on the next day of the rule that is not already past.
+    /// Days that are skipped because they are already past still count towards the rule's `COUNT`.
+    /// `X-SCHOOL-DAYS=TRUE` skips days that are not school days, so `FREQ=DAILY;X-SCHOOL-DAYS=TRUE` is every school day.
+    pub recurrence: Option<String>,
+
+    /// The number of the occurrence, counting from 1, for recurring to-dos.
+    pub occurrence: i32,
+
+    #[graphql(skip)]
+    pub next_occurrence: Option<i32>,
+
+    /// The position of the to-do among the to-dos with the same parent, lowest first.
+    pub position: i32,
+
+    /// How important the to-do is.
+    pub priority: TodoPriority,
+
+    /// How many minutes the to-do is expected to take.
+    pub estimated_minutes: Option<i32>,
+
+    #[graphql(skip)]
+    pub assessment: Option<i32>,
+}
+
+impl Todo {
+    /// Scores how much the to-do should be worked on now, higher first, for suggesting what to do next.
+    /// Due dates count the most, from 8 for today down to nothing for none, with 2 more for overdue to-dos.
+    /// Priority adds from 0 for low to 6 for urgent, the weight of the linked assessment adds up to 5 for 100%,
+    /// and quick to-dos get up to 1 more so they can be fitted in.
+    pub fn score(&self, today: NaiveDate, weight: Option<i16>) -> f64 {
+        let due = self.due.map_or(0.0, |due| {
+            let days = (due - today).num_days();
+            let overdue = if days < 0 { 2.0 } else { 0.0 };
+            let days = f64::from(i32::try_from(days.max(0)).unwrap_or(i32::MAX));
+            8.0 / (1.0 + days) + overdue
+        });
+        let priority = match self.priority {
+            TodoPriority::Low => 0.0,
+            TodoPriority::Normal => 1.0,
+            TodoPriority::High => 3.0,
+            TodoPriority::Urgent => 6.0,
+        };
+        let weight = f64::from(weight.unwrap_or_default().clamp(0, 100)) / 20.0;
+        let effort = self.estimated_minutes.map_or(0.5, |minutes| 1.0 / (1.0 + f64::from(minutes) / 30.0));
+        due + priority + weight + effort
+    }
 }
 
 #[ComplexObject]
@@ -56,9 +149,102 @@ impl Todo {
         })
     }
     
-    /// The children of the to-do.
+    /// The children of the to-do, in order.
     async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Todo>> {
-        query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE parent = $1;", self.id)
+        query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE parent = $1 AND trash IS NULL ORDER BY position, id;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The occurrence created when the to-do was completed, if it recurs.
+    async fn next_occurrence(&self, ctx: &Context<'_>) -> Result<Option<Todo>> {
+        Ok(if let Some(next) = self.next_occurrence {
+            query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE id = $1 AND trash IS NULL LIMIT 1;", next)
+                .fetch_optional(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?
+        } else {
+            None
+        })
+    }
+
+    /// The assessment the to-do is for.
+    async fn assessment(&self, ctx: &Context<'_>) -> Result<Option<Assessment>> {
+        Ok(if let Some(assessment) = self.assessment {
+            query_as!(Assessment, /* language=postgresql */ "SELECT * FROM assessments WHERE id = $1 AND subject IN (SELECT id FROM subjects WHERE trash IS NULL) LIMIT 1;", assessment)
+                .fetch_optional(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError))?
+        } else {
+            None
+        })
+    }
+
+    /// The tags of the to-do, ordered by name.
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        query_as!(Tag, /* language=postgresql */ "SELECT tags.* FROM todo_tags JOIN tags ON tags.id = todo_tags.tag WHERE todo_tags.todo = $1 ORDER BY tags.name;", self.id)
             .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
     }
 }
+
+/// A to-do with all of its descendants and its progress, from a to-do tree.
+#[derive(SimpleObject)]
+pub struct TodoNode {
+    /// The to-do.
+    pub todo: Todo,
+
+    /// The children of the to-do, in order.
+    pub children: Vec<TodoNode>,
+
+    /// The number of the to-do's descendants that are completed.
+    pub completed: i32,
+
+    /// The number of the to-do's descendants.
+    pub total: i32,
+
+    /// The earliest due date of the to-do and its descendants that are not completed.
+    pub next_due: Option<NaiveDate>,
+}
+
+impl TodoNode {
+    /// Builds the tree under `todo` from to-dos grouped by their parents, in order.
+    /// Each group is used at most once, so the tree is finite even if the to-dos have a cycle.
+    fn build(todo: Todo, by_parent: &mut HashMap<i32, Vec<Todo>>) -> Self {
+        let children = by_parent.remove(&todo.id).unwrap_or_default()
+            .into_iter()
+            .map(|child| Self::build(child, by_parent))
+            .collect::<Vec<_>>();
+        let mut node = Self {
+            completed: 0,
+            total: 0,
+            next_due: todo.due.filter(|_| !todo.completed),
+            todo,
+            children: Vec::new(),
+        };
+        for child in &children {
+            node.completed += child.completed + i32::from(child.todo.completed);
+            node.total += child.total + 1;
+            node.next_due = node.next_due.into_iter().chain(child.next_due).min();
+        }
+        node.children = children;
+        node
+    }
+
+    /// Loads the trees of all of a user's top-level to-dos, or just the tree under `root`, with one query.
+    pub async fn load(pool: &PgPool, owner: i32, root: Option<i32>) -> Result<Vec<Self>> {
+        let todos = query_as!(Todo, /* language=postgresql */ "
+            WITH RECURSIVE tree AS (
+                SELECT id FROM todos
+                WHERE owner = $1 AND trash IS NULL AND CASE WHEN $2::INTEGER IS NULL THEN parent IS NULL ELSE id = $2 END
+                UNION
+                SELECT todos.id FROM todos JOIN tree ON todos.parent = tree.id WHERE todos.trash IS NULL
+            )
+            SELECT * FROM todos WHERE id IN (SELECT id FROM tree) ORDER BY position, id;
+            ", owner, root)
+            .fetch_all(pool).await?;
+        let mut roots = Vec::new();
+        let mut by_parent = HashMap::<i32, Vec<Todo>>::new();
+        for todo in todos {
+            match todo.parent {
+                Some(parent) if root != Some(todo.id) => by_parent.entry(parent).or_default().push(todo),
+                _ => roots.push(todo),
+            }
+        }
+        Ok(roots.into_iter().map(|todo| Self::build(todo, &mut by_parent)).collect())
+    }
+}
@@ -0,0 +1,77 @@
+use async_graphql::{ComplexObject, Context, Result, SimpleObject, Union};
+use chrono::{Days, NaiveDateTime};
+use rocket::http::Status;
+use sqlx::{PgPool, query_as};
+use crate::api::graphql::query::note::Note;
+use crate::api::graphql::query::subject::Subject;
+use crate::api::graphql::query::todo::Todo;
+use crate::api::graphql::trash::Retention;
+
+/// Something that was deleted and can be restored from the trash.
+#[derive(Union)]
+pub enum TrashedItem {
+    Note(Note),
+    Todo(Todo),
+    Subject(Subject),
+}
+
+impl TrashedItem {
+    /// Finds the item that was deleted out of everything deleted with it:
+    /// a subject rather than its notes and to-dos, or a to-do rather than its children.
+    pub fn root(notes: Vec<Note>, todos: Vec<Todo>, subjects: Vec<Subject>) -> Option<Self> {
+        if let Some(subject) = subjects.into_iter().next() {
+            return Some(Self::Subject(subject));
+        }
+        let ids = todos.iter().map(|todo| todo.id).collect::<Vec<_>>();
+        if let Some(todo) = todos.into_iter().find(|todo| todo.parent.is_none_or(|parent| !ids.contains(&parent))) {
+            return Some(Self::Todo(todo));
+        }
+        notes.into_iter().next().map(Self::Note)
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct TrashEntry {
+    /// The ID of the trash entry.
+    pub id: i32,
+
+    #[graphql(skip)]
+    pub owner: i32,
+
+    /// When the item was deleted.
+    pub deleted_at: NaiveDateTime,
+}
+
+#[ComplexObject]
+impl TrashEntry {
+    /// The note, to-do or subject that was deleted.
+    async fn item(&self, ctx: &Context<'_>) -> Result<TrashedItem> {
+        let pool = ctx.data::<PgPool>()?;
+        let notes = query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE trash = $1 ORDER BY id LIMIT 1;", self.id)
+            .fetch_all(pool).await.or(Err(Status::InternalServerError))?;
+        let todos = query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE trash = $1 ORDER BY id;", self.id)
+            .fetch_all(pool).await.or(Err(Status::InternalServerError))?;
+        let subjects = query_as!(Subject, /* language=postgresql */ "SELECT * FROM subjects WHERE trash = $1 LIMIT 1;", self.id)
+            .fetch_all(pool).await.or(Err(Status::InternalServerError))?;
+        TrashedItem::root(notes, todos, subjects).ok_or(Status::InternalServerError.into())
+    }
+
+    /// The notes deleted with the item, including the item itself if it is a note.
+    async fn notes(&self, ctx: &Context<'_>) -> Result<Vec<Note>> {
+        query_as!(Note, /* language=postgresql */ "SELECT * FROM notes WHERE trash = $1 ORDER BY date, id;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// The to-dos deleted with the item, including the item itself if it is a to-do.
+    async fn todos(&self, ctx: &Context<'_>) -> Result<Vec<Todo>> {
+        query_as!(Todo, /* language=postgresql */ "SELECT * FROM todos WHERE trash = $1 ORDER BY id;", self.id)
+            .fetch_all(ctx.data::<PgPool>()?).await.or(Err(Status::InternalServerError.into()))
+    }
+
+    /// When the item and everything deleted with it will be deleted permanently.
+    async fn purge_at(&self, ctx: &Context<'_>) -> Result<NaiveDateTime> {
+        let retention = ctx.data::<Retention>()?;
+        Ok(self.deleted_at.checked_add_days(Days::new(retention.0.into())).unwrap_or(self.deleted_at))
+    }
+}
@@ -1,7 +1,8 @@
-use async_graphql::{Context, Object, Result};
-use chrono::NaiveDateTime;
+use async_graphql::{Context, Object, Result, SimpleObject};
+use chrono::{Local, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
 use rocket::http::Status;
-use sqlx::{PgPool, query_as};
+use sqlx::{PgPool, query, query_as};
 use crate::api::graphql::query::session::Session;
 use crate::auth::User;
 
@@ -39,6 +40,12 @@ impl User {
         self.require_password_change
     }
 
+    /// The IANA time zone of the user, such as `Australia/Sydney`, used for relative dates such as "tomorrow".
+    /// Null if the server's time zone is used.
+    async fn timezone(&self) -> Option<&String> {
+        self.timezone.as_ref()
+    }
+
     /// List of all active sessions for the user.
     async fn sessions(&self, ctx: &Context<'_>) -> Result<Vec<Session>> {
         let pool = ctx.data::<PgPool>()?;
@@ -53,3 +60,47 @@ impl User {
             .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
     }
 }
+
+/// Gets the current date in a time zone, or the server's if there is none.
+fn today_in(timezone: Option<&str>) -> NaiveDate {
+    match timezone.and_then(|timezone| timezone.parse::<Tz>().ok()) {
+        Some(timezone) => Utc::now().with_timezone(&timezone).date_naive(),
+        None => Local::now().date_naive(),
+    }
+}
+
+impl User {
+    /// Gets the current date in the user's time zone, or the server's if the user has not set one.
+    pub fn today(&self) -> NaiveDate {
+        today_in(self.timezone.as_deref())
+    }
+}
+
+/// Gets the current date in a user's time zone, or the server's if the user has not set one.
+/// Use [`User::today`] instead when the user is already loaded.
+pub async fn today(pool: &PgPool, user: i32) -> Result<NaiveDate> {
+    let timezone = query!(/* language=postgresql */ "SELECT timezone FROM users WHERE id = $1 LIMIT 1;", user)
+        .fetch_one(pool).await?.timezone;
+    Ok(today_in(timezone.as_deref()))
+}
+
+/// The public details of another user, such as someone a note is shared with.
+#[derive(SimpleObject)]
+pub struct UserProfile {
+    /// The user ID.
+    pub id: i32,
+
+    /// The unique username used for login.
+    pub username: String,
+
+    /// The name used in the UI.
+    pub name: String,
+}
+
+impl UserProfile {
+    /// Gets the public details of a user.
+    pub async fn get(pool: &PgPool, id: i32) -> Result<Self> {
+        query_as!(Self, /* language=postgresql */ "SELECT id, username, name FROM users WHERE id = $1 LIMIT 1;", id)
+            .fetch_optional(pool).await?.ok_or(Status::NotFound.into())
+    }
+}
@@ -0,0 +1,500 @@
+use std::fmt::{Display, Formatter, Write};
+use std::future::Future;
+use std::str::FromStr;
+use async_graphql::{CustomValidator, InputValueError};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use rocket::http::Status;
+use sqlx::PgPool;
+use crate::api::graphql::query::calendar::school_days;
+
+/// The most periods of a rule searched for an occurrence, so rules that never match cannot loop forever.
+const MAX_PERIODS: u32 = 5000;
+
+/// The most years of school days searched when finding an occurrence of a rule that skips other days.
+const MAX_SCHOOL_YEARS: u32 = 10;
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+#[derive(Copy, Clone, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// An iCalendar recurrence rule (RFC 5545) for dates, such as `FREQ=WEEKLY;BYDAY=MO,WE`.
+/// Supports `FREQ`, `INTERVAL`, `COUNT`, `UNTIL`, `BYDAY`, `BYMONTHDAY`, `BYMONTH` and `WKST`,
+/// and the extension `X-SCHOOL-DAYS=TRUE`, which skips occurrences that are not school days.
+/// Every school day is `FREQ=DAILY;X-SCHOOL-DAYS=TRUE`.
+pub struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    /// Weekdays, with the occurrence of the weekday within the month or year, or 0 for every occurrence.
+    by_day: Vec<(i32, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+    week_start: Weekday,
+    /// The number of occurrences, including the first.
+    pub count: Option<u32>,
+    /// The last day an occurrence can be on.
+    pub until: Option<NaiveDate>,
+    /// Whether occurrences that are not school days are skipped.
+    pub school_days: bool,
+}
+
+fn weekday(value: &str) -> Result<Weekday, String> {
+    WEEKDAYS.iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, weekday)| *weekday)
+        .ok_or_else(|| format!("`{value}` is not a weekday."))
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    WEEKDAYS[weekday.num_days_from_monday() as usize].0
+}
+
+fn number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("`{name}` must be a number, not `{value}`."))
+}
+
+/// Parses a comma-separated list with `parse`, checking that each value is `valid`.
+fn list<T: Copy>(value: &str, parse: impl Fn(&str) -> Result<T, String>, valid: impl Fn(T) -> bool) -> Result<Vec<T>, String> {
+    value.split(',')
+        .map(|item| parse(item).and_then(|parsed| if valid(parsed) { Ok(parsed) } else { Err(format!("`{item}` is out of range.")) }))
+        .collect()
+}
+
+/// Parses an `UNTIL` date, which may also have a time that is ignored.
+fn until(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.get(..8).unwrap_or(value), "%Y%m%d").map_err(|_| format!("`{value}` is not a date such as 20241231."))
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim().to_uppercase();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(&rule);
+        let mut frequency = None;
+        let mut recurrence = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            week_start: Weekday::Mon,
+            count: None,
+            until: None,
+            school_days: false,
+        };
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=').ok_or_else(|| format!("`{part}` is not of the form NAME=VALUE."))?;
+            match name {
+                "FREQ" => frequency = Some(match value {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return Err(format!("`{value}` is not a supported frequency. Use DAILY, WEEKLY, MONTHLY or YEARLY.")),
+                }),
+                "INTERVAL" => recurrence.interval = number(name, value)?,
+                "COUNT" => recurrence.count = Some(number(name, value)?),
+                "UNTIL" => recurrence.until = Some(until(value)?),
+                "BYDAY" => recurrence.by_day = list(value, |item| {
+                    let split = item.char_indices().rev().nth(1).map_or(0, |(split, _)| split);
+                    let ordinal = if split == 0 { 0 } else { number(name, &item[..split])? };
+                    Ok((ordinal, weekday(&item[split..])?))
+                }, |(ordinal, _): (i32, Weekday)| (-53..=53).contains(&ordinal))?,
+                "BYMONTHDAY" => recurrence.by_month_day = list(value, |item| number(name, item), |day: i32| day != 0 && (-31..=31).contains(&day))?,
+                "BYMONTH" => recurrence.by_month = list(value, |item| number(name, item), |month: u32| (1..=12).contains(&month))?,
+                "WKST" => recurrence.week_start = weekday(value)?,
+                "X-SCHOOL-DAYS" => recurrence.school_days = match value {
+                    "TRUE" => true,
+                    "FALSE" => false,
+                    _ => return Err("`X-SCHOOL-DAYS` must be TRUE or FALSE.".to_string()),
+                },
+                _ => return Err(format!("`{name}` is not supported.")),
+            }
+        }
+        recurrence.frequency = frequency.ok_or("`FREQ` is required.")?;
+        if recurrence.interval == 0 {
+            return Err("`INTERVAL` must be at least 1.".to_string());
+        }
+        if recurrence.count == Some(0) {
+            return Err("`COUNT` must be at least 1.".to_string());
+        }
+        if recurrence.count.is_some() && recurrence.until.is_some() {
+            return Err("`COUNT` and `UNTIL` cannot both be given.".to_string());
+        }
+        if matches!(recurrence.frequency, Frequency::Daily | Frequency::Weekly) && recurrence.by_day.iter().any(|(ordinal, _)| *ordinal != 0) {
+            return Err("Numbered weekdays in `BYDAY` can only be used with MONTHLY or YEARLY.".to_string());
+        }
+        if recurrence.frequency == Frequency::Weekly && !recurrence.by_month_day.is_empty() {
+            return Err("`BYMONTHDAY` cannot be used with WEEKLY.".to_string());
+        }
+        Ok(recurrence)
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.frequency {
+            Frequency::Daily => "FREQ=DAILY",
+            Frequency::Weekly => "FREQ=WEEKLY",
+            Frequency::Monthly => "FREQ=MONTHLY",
+            Frequency::Yearly => "FREQ=YEARLY",
+        })?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_month.is_empty() {
+            write!(f, ";BYMONTH={}", self.by_month.iter().map(ToString::to_string).collect::<Vec<_>>().join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            write!(f, ";BYMONTHDAY={}", self.by_month_day.iter().map(ToString::to_string).collect::<Vec<_>>().join(","))?;
+        }
+        if !self.by_day.is_empty() {
+            let days = self.by_day.iter().fold(String::new(), |mut days, (ordinal, weekday)| {
+                if !days.is_empty() {
+                    days.push(',');
+                }
+                if *ordinal != 0 {
+                    let _ = write!(days, "{ordinal}");
+                }
+                days.push_str(weekday_name(*weekday));
+                days
+            });
+            write!(f, ";BYDAY={days}")?;
+        }
+        if self.week_start != Weekday::Mon {
+            write!(f, ";WKST={}", weekday_name(self.week_start))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={count}")?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+        if self.school_days {
+            f.write_str(";X-SCHOOL-DAYS=TRUE")?;
+        }
+        Ok(())
+    }
+}
+
+/// Gets the number of days from the last `from` to `weekday`, from 0 to 6.
+fn days_since(weekday: Weekday, from: Weekday) -> u32 {
+    (weekday.num_days_from_monday() + 7 - from.num_days_from_monday()) % 7
+}
+
+/// Gets the number of days in a month.
+fn days_in_month(first: NaiveDate) -> u32 {
+    first.checked_add_months(Months::new(1)).map_or(31, |next| next.pred_opt().map_or(31, |last| last.day()))
+}
+
+/// Selects the days of `days` that fall on the weekdays of `by_day`, keeping only the numbered occurrence of numbered weekdays.
+fn select_weekdays(days: &[NaiveDate], by_day: &[(i32, Weekday)]) -> Vec<NaiveDate> {
+    let mut selected = Vec::new();
+    for (ordinal, weekday) in by_day {
+        let matching = days.iter().copied().filter(|day| day.weekday() == *weekday).collect::<Vec<_>>();
+        let index = match *ordinal {
+            0 => {
+                selected.extend(matching);
+                continue;
+            }
+            ordinal if ordinal > 0 => usize::try_from(ordinal - 1).ok(),
+            ordinal => matching.len().checked_sub(ordinal.unsigned_abs() as usize),
+        };
+        if let Some(day) = index.and_then(|index| matching.get(index)) {
+            selected.push(*day);
+        }
+    }
+    selected
+}
+
+impl Recurrence {
+    fn matches_month_day(&self, day: NaiveDate) -> bool {
+        let days = i32::try_from(days_in_month(day.with_day(1).unwrap_or(day))).unwrap_or(31);
+        let day = i32::try_from(day.day()).unwrap_or_default();
+        self.by_month_day.iter().any(|month_day| *month_day == day || days + 1 + *month_day == day)
+    }
+
+    /// Expands the days of the month starting on `first` that the rule selects.
+    /// Without `BYMONTHDAY` or `BYDAY`, this is the day of the month of `start`.
+    fn month(&self, first: NaiveDate, start: NaiveDate) -> Vec<NaiveDate> {
+        let days = (0..days_in_month(first)).filter_map(|offset| first.checked_add_days(Days::new(offset.into()))).collect::<Vec<_>>();
+        if !self.by_month_day.is_empty() {
+            days.into_iter()
+                .filter(|day| self.matches_month_day(*day))
+                .filter(|day| self.by_day.is_empty() || self.by_day.iter().any(|(_, weekday)| *weekday == day.weekday()))
+                .collect()
+        } else if !self.by_day.is_empty() {
+            select_weekdays(&days, &self.by_day)
+        } else {
+            first.with_day(start.day()).into_iter().collect()
+        }
+    }
+
+    /// Expands the days of the `index`th period of the rule counting from `start`, in order.
+    fn period(&self, start: NaiveDate, index: u32) -> Vec<NaiveDate> {
+        let step = index.saturating_mul(self.interval);
+        let mut days = match self.frequency {
+            Frequency::Daily => start.checked_add_days(Days::new(step.into()))
+                .filter(|day| self.by_month_day.is_empty() || self.matches_month_day(*day))
+                .filter(|day| self.by_day.is_empty() || self.by_day.iter().any(|(_, weekday)| *weekday == day.weekday()))
+                .into_iter()
+                .collect(),
+            Frequency::Weekly => {
+                let week = start.checked_sub_days(Days::new(days_since(start.weekday(), self.week_start).into()))
+                    .and_then(|week| week.checked_add_days(Days::new(u64::from(step) * 7)));
+                let weekdays = if self.by_day.is_empty() { vec![start.weekday()] } else { self.by_day.iter().map(|(_, weekday)| *weekday).collect() };
+                week.map(|week| weekdays.into_iter()
+                    .filter_map(|weekday| week.checked_add_days(Days::new(days_since(weekday, self.week_start).into())))
+                    .collect())
+                    .unwrap_or_default()
+            }
+            Frequency::Monthly => start.with_day(1)
+                .and_then(|first| first.checked_add_months(Months::new(step)))
+                .map(|first| self.month(first, start))
+                .unwrap_or_default(),
+            Frequency::Yearly => {
+                let Some(first) = i32::try_from(step).ok().and_then(|step| NaiveDate::from_ymd_opt(start.year().checked_add(step)?, 1, 1)) else {
+                    return Vec::new();
+                };
+                if self.by_month.is_empty() && self.by_month_day.is_empty() && !self.by_day.is_empty() {
+                    // Numbered weekdays count through the whole year when no months are given
+                    let days = first.iter_days().take_while(|day| day.year() == first.year()).collect::<Vec<_>>();
+                    select_weekdays(&days, &self.by_day)
+                } else if self.by_month.is_empty() && self.by_month_day.is_empty() {
+                    first.with_month(start.month()).and_then(|month| month.with_day(start.day())).into_iter().collect()
+                } else {
+                    let months = if self.by_month.is_empty() { (1..=12).collect() } else { self.by_month.clone() };
+                    months.into_iter()
+                        .filter_map(|month| first.with_month(month))
+                        .flat_map(|first| self.month(first, start))
+                        .collect()
+                }
+            }
+        };
+        if !self.by_month.is_empty() {
+            days.retain(|day| self.by_month.contains(&day.month()));
+        }
+        days.sort_unstable();
+        days.dedup();
+        days
+    }
+
+    /// Lists the days the rule selects from `start` onwards, in order, ignoring `COUNT`, `UNTIL` and school days.
+    fn days(&self, start: NaiveDate) -> impl Iterator<Item = NaiveDate> + '_ {
+        (0..MAX_PERIODS)
+            .flat_map(move |index| self.period(start, index))
+            .filter(move |day| *day >= start)
+    }
+
+    /// Finds the first occurrence on or after `from` of the rule starting on `start`, counting occurrences after `after`.
+    /// The first occurrence after `after` is the `number`th occurrence, counting from 1.
+    /// Returns the occurrence and its number, or `None` if the rule has ended.
+    /// Fails with 409 Conflict if the rule skips days that are not school days and the user has none within a year.
+    pub async fn next(&self, pool: &PgPool, owner: i32, start: NaiveDate, after: NaiveDate, from: NaiveDate, number: u32) -> async_graphql::Result<Option<(NaiveDate, u32)>> {
+        self.next_with(start, after, from, number, |from, to| school_days(pool, owner, from, to)).await
+    }
+
+    /// Like [`Self::next`], getting the school days from one day to another from `school_days`.
+    async fn next_with<F: Future<Output = sqlx::Result<Vec<NaiveDate>>>>(
+        &self,
+        start: NaiveDate,
+        after: NaiveDate,
+        from: NaiveDate,
+        number: u32,
+        mut school_days: impl FnMut(NaiveDate, NaiveDate) -> F,
+    ) -> async_graphql::Result<Option<(NaiveDate, u32)>> {
+        let candidates = self.days(start)
+            .filter(|day| *day > after)
+            .take_while(|day| self.until.is_none_or(|until| *day <= until));
+        let mut number = number;
+        let mut known = Vec::new();
+        let mut known_until = None;
+        let mut years = 0;
+        for day in candidates {
+            if self.count.is_some_and(|count| number > count) {
+                return Ok(None);
+            }
+            if self.school_days {
+                if known_until.is_none_or(|known_until| day > known_until) {
+                    if years == MAX_SCHOOL_YEARS {
+                        return Ok(None);
+                    }
+                    years += 1;
+                    let until = day.checked_add_days(Days::new(365)).unwrap_or(day);
+                    known = school_days(day, until).await?;
+                    if known.is_empty() {
+                        return Err(Status::Conflict.into());
+                    }
+                    known_until = Some(until);
+                }
+                if known.binary_search(&day).is_err() {
+                    continue;
+                }
+            }
+            if day >= from {
+                return Ok(Some((day, number)));
+            }
+            number += 1;
+        }
+        Ok(None)
+    }
+}
+
+/// Checks that an argument is a recurrence rule that can be parsed.
+pub struct RecurrenceValidator;
+
+impl CustomValidator<String> for RecurrenceValidator {
+    fn check(&self, value: &String) -> Result<(), InputValueError<String>> {
+        value.parse::<Recurrence>().map(|_| ()).map_err(InputValueError::custom)
+    }
+}
+
+/// Parses a validated recurrence rule into its usual form, such as `FREQ=WEEKLY;BYDAY=MO` for `rrule:freq=weekly;byday=mo`.
+pub fn normalise(rule: Option<String>) -> async_graphql::Result<Option<String>> {
+    rule.map(|rule| rule.parse::<Recurrence>().map(|rule| rule.to_string()).or(Err(Status::BadRequest.into())))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate, Weekday};
+    use super::Recurrence;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).expect("valid date")
+    }
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().expect("valid rule")
+    }
+
+    /// Lists the first `count` days a rule selects from `start` onwards.
+    fn days(rule: &str, start: NaiveDate, count: usize) -> Vec<NaiveDate> {
+        self::rule(rule).days(start).take(count).collect()
+    }
+
+    /// Finds the next occurrence of a rule, with every weekday other than those in `holidays` being a school day.
+    async fn next(rule: &str, start: NaiveDate, after: NaiveDate, from: NaiveDate, number: u32, holidays: &[NaiveDate]) -> async_graphql::Result<Option<(NaiveDate, u32)>> {
+        self::rule(rule).next_with(start, after, from, number, |from, to| async move {
+            Ok(from.iter_days()
+                .take_while(|day| *day <= to)
+                .filter(|day| !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) && !holidays.contains(day))
+                .collect())
+        }).await
+    }
+
+    #[test]
+    fn round_trip() {
+        for normal in [
+            "FREQ=DAILY",
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;WKST=SU;UNTIL=20261231",
+            "FREQ=MONTHLY;BYDAY=-1FR,2TU;COUNT=5",
+            "FREQ=MONTHLY;BYMONTHDAY=1,-1",
+            "FREQ=YEARLY;BYMONTH=11;BYDAY=4TH",
+            "FREQ=DAILY;X-SCHOOL-DAYS=TRUE",
+        ] {
+            assert_eq!(rule(normal).to_string(), normal);
+        }
+        assert_eq!(rule("rrule:freq=weekly;byday=mo;until=20261231T235959Z").to_string(), "FREQ=WEEKLY;BYDAY=MO;UNTIL=20261231");
+        assert_eq!(rule("FREQ=MONTHLY;BYDAY=+1MO,10SU").to_string(), "FREQ=MONTHLY;BYDAY=1MO,10SU");
+        assert_eq!(rule("FREQ=DAILY;INTERVAL=1;WKST=MO;X-SCHOOL-DAYS=FALSE").to_string(), "FREQ=DAILY");
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for invalid in [
+            "",
+            "BYDAY=MO",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;COUNT=0",
+            "FREQ=DAILY;COUNT=2;UNTIL=20261231",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=WEEKLY;BYMONTHDAY=1",
+            "FREQ=MONTHLY;BYDAY=54MO",
+            "FREQ=MONTHLY;BYDAY=1XX",
+            "FREQ=MONTHLY;BYMONTHDAY=0",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=YEARLY;BYMONTH=13",
+            "FREQ=DAILY;X-SCHOOL-DAYS=YES",
+        ] {
+            assert!(invalid.parse::<Recurrence>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn numbered_weekdays_in_months() {
+        assert_eq!(
+            days("FREQ=MONTHLY;BYDAY=-1FR,2TU", date(2026, 10, 1), 4),
+            [date(2026, 10, 13), date(2026, 10, 30), date(2026, 11, 10), date(2026, 11, 27)],
+        );
+        assert_eq!(days("FREQ=MONTHLY;BYDAY=5MO", date(2026, 10, 1), 2), [date(2026, 11, 30), date(2027, 3, 29)]);
+    }
+
+    #[test]
+    fn numbered_weekdays_in_years() {
+        assert_eq!(days("FREQ=YEARLY;BYDAY=20MO", date(2026, 1, 1), 2), [date(2026, 5, 18), date(2027, 5, 17)]);
+        assert_eq!(days("FREQ=YEARLY;BYDAY=-1SU", date(2026, 1, 1), 2), [date(2026, 12, 27), date(2027, 12, 26)]);
+        assert_eq!(days("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH", date(2026, 1, 1), 2), [date(2026, 11, 26), date(2027, 11, 25)]);
+    }
+
+    #[test]
+    fn negative_month_days() {
+        assert_eq!(
+            days("FREQ=MONTHLY;BYMONTHDAY=-1", date(2028, 1, 15), 3),
+            [date(2028, 1, 31), date(2028, 2, 29), date(2028, 3, 31)],
+        );
+        assert_eq!(days("FREQ=MONTHLY;BYMONTHDAY=-3,1", date(2026, 2, 2), 3), [date(2026, 2, 26), date(2026, 3, 1), date(2026, 3, 29)]);
+    }
+
+    #[test]
+    fn thirty_first_in_short_months() {
+        assert_eq!(
+            days("FREQ=MONTHLY", date(2026, 1, 31), 4),
+            [date(2026, 1, 31), date(2026, 3, 31), date(2026, 5, 31), date(2026, 7, 31)],
+        );
+        assert_eq!(days("FREQ=YEARLY", date(2024, 2, 29), 2), [date(2024, 2, 29), date(2028, 2, 29)]);
+    }
+
+    #[rocket::async_test]
+    async fn count_with_skipped_occurrences() {
+        let start = date(2026, 10, 5);
+        assert_eq!(next("FREQ=DAILY;COUNT=3", start, start, date(2026, 10, 6), 2, &[]).await.ok(), Some(Some((date(2026, 10, 6), 2))));
+        assert_eq!(next("FREQ=DAILY;COUNT=3", start, start, date(2026, 10, 7), 2, &[]).await.ok(), Some(Some((date(2026, 10, 7), 3))));
+        assert_eq!(next("FREQ=DAILY;COUNT=3", start, start, date(2026, 10, 8), 2, &[]).await.ok(), Some(None));
+        assert_eq!(next("FREQ=DAILY;UNTIL=20261007", start, start, date(2026, 10, 8), 2, &[]).await.ok(), Some(None));
+    }
+
+    #[rocket::async_test]
+    async fn school_days() {
+        let friday = date(2026, 10, 16);
+        assert_eq!(next("FREQ=DAILY;X-SCHOOL-DAYS=TRUE", friday, friday, friday, 2, &[]).await.ok(), Some(Some((date(2026, 10, 19), 2))));
+        let holidays = (19..=23).map(|day| date(2026, 10, day)).collect::<Vec<_>>();
+        assert_eq!(next("FREQ=DAILY;X-SCHOOL-DAYS=TRUE", friday, friday, friday, 2, &holidays).await.ok(), Some(Some((date(2026, 10, 26), 2))));
+        // Skipped days that are not school days are not counted
+        assert_eq!(
+            next("FREQ=DAILY;X-SCHOOL-DAYS=TRUE;COUNT=3", friday, friday, date(2026, 10, 28), 2, &holidays).await.ok(),
+            Some(None),
+        );
+        assert_eq!(
+            next("FREQ=DAILY;X-SCHOOL-DAYS=TRUE;COUNT=3", friday, friday, date(2026, 10, 27), 2, &holidays).await.ok(),
+            Some(Some((date(2026, 10, 27), 3))),
+        );
+        let error = rule("FREQ=DAILY;X-SCHOOL-DAYS=TRUE").next_with(friday, friday, friday, 2, |_, _| async { Ok(Vec::new()) }).await.err();
+        assert_eq!(error.map(|error| error.message).as_deref(), Some("409 Conflict"));
+    }
+}
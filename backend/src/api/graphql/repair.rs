@@ -0,0 +1,72 @@
+use sqlx::{PgPool, query};
+use crate::api::graphql::collaboration::REWRITTEN_CHANNEL;
+use crate::api::graphql::tiptap::schema::repair;
+
+/// Checks every note and revision against the editor's schema, printing each problem found.
+/// Documents that only need normalising are rewritten in normal form.
+/// Anything in a note the editor cannot open is removed, after saving the note's previous content as a revision.
+/// Attributes the schema does not know are reported and removed the same way.
+/// Invalid revisions are kept as they are so that nothing is lost.
+/// With `dry_run`, nothing is changed.
+pub async fn repair_notes(pool: &PgPool, dry_run: bool) -> sqlx::Result<()> {
+    let (mut normalised, mut repaired, mut invalid) = (0, 0, 0);
+    // Revisions are checked first so that those saved below are kept as they are
+    let revisions = query!(/* language=postgresql */ "SELECT id, note, content FROM note_revisions ORDER BY id;")
+        .fetch_all(pool).await?;
+    for revision in &revisions {
+        let repaired = repair(&revision.content);
+        if repaired.content == revision.content {
+            continue;
+        }
+        if !repaired.errors.is_empty() || !repaired.dropped.is_empty() {
+            for error in repaired.errors.iter().chain(&repaired.dropped) {
+                println!("Revision {} of note {}: {error}", revision.id, revision.note);
+            }
+            invalid += 1;
+            continue;
+        }
+        normalised += 1;
+        if !dry_run {
+            query!(/* language=postgresql */ "UPDATE note_revisions SET content = $2 WHERE id = $1;", revision.id, repaired.content)
+                .execute(pool).await?;
+        }
+    }
+
+    let notes = query!(/* language=postgresql */ "SELECT id, content FROM notes ORDER BY id;")
+        .fetch_all(pool).await?;
+    for note in &notes {
+        let repaired_note = repair(&note.content);
+        if repaired_note.content == note.content {
+            continue;
+        }
+        for error in repaired_note.errors.iter().chain(&repaired_note.dropped) {
+            println!("Note {}: {error}", note.id);
+        }
+        let lossy = !repaired_note.errors.is_empty() || !repaired_note.dropped.is_empty();
+        if lossy { repaired += 1 } else { normalised += 1 }
+        if dry_run {
+            continue;
+        }
+        let mut tx = pool.begin().await?;
+        if lossy {
+            query!(/* language=postgresql */ "INSERT INTO note_revisions (note, content, word_count) VALUES ($1, $2, tiptap_word_count($2));", note.id, note.content)
+                .execute(&mut *tx).await?;
+        }
+        query!(/* language=postgresql */ "UPDATE notes SET content = $2 WHERE id = $1;", note.id, repaired_note.content)
+            .execute(&mut *tx).await?;
+        // Collaborative edits continue from the repaired content, once a running server has closed the note's session
+        query!(/* language=postgresql */ "DELETE FROM note_documents WHERE note = $1;", note.id)
+            .execute(&mut *tx).await?;
+        query!(/* language=postgresql */ "SELECT pg_notify($1, $2);", REWRITTEN_CHANNEL, note.id.to_string())
+            .execute(&mut *tx).await?;
+        tx.commit().await?;
+    }
+
+    println!(
+        "{}Checked {} notes and {} revisions. Normalised: {normalised}, repaired: {repaired}, invalid revisions kept: {invalid}",
+        if dry_run { "Dry run, nothing was changed. " } else { "" },
+        notes.len(),
+        revisions.len(),
+    );
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::fmt::Write;
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use rocket::{get, Route, routes, State};
+use rocket::http::Status;
+use rocket::response::content::RawHtml;
+use sqlx::PgPool;
+use crate::api::graphql::query::assessment::AssessmentStatus;
+use crate::api::graphql::query::calendar::Term;
+use crate::api::graphql::query::grade_scale::Grade;
+use crate::api::graphql::query::report::Report;
+use crate::api::graphql::tiptap::html::escape;
+use crate::auth::User;
+
+lazy_static! {
+    pub static ref ROUTES: Vec<Route> = routes![
+        report,
+    ];
+}
+
+const STYLE: &str = "
+    body { font-family: sans-serif; margin: 2em; }
+    h1, h2 { margin-bottom: 0.25em; }
+    section { break-inside: avoid; margin-bottom: 2em; }
+    table { border-collapse: collapse; width: 100%; }
+    th, td { border: 1px solid #999; padding: 0.25em 0.5em; text-align: left; }
+    .muted { color: #666; }
+";
+
+fn format_grade(grade: Option<&Grade>) -> String {
+    grade.map_or_else(|| "—".to_string(), |grade| format!("{} ({:.1}%)", escape(&grade.label), grade.percentage))
+}
+
+fn format_status(status: AssessmentStatus) -> &'static str {
+    match status {
+        AssessmentStatus::NotIssued => "Not issued",
+        AssessmentStatus::NotStarted => "Not started",
+        AssessmentStatus::InProgress => "In progress",
+        AssessmentStatus::Finished => "Finished",
+        AssessmentStatus::ResultsReceived => "Results received",
+    }
+}
+
+/// Renders a report as a standalone, printable HTML document.
+fn render(report: &Report) -> Result<String, std::fmt::Error> {
+    let mut html = String::new();
+    write!(html, "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Report {from} – {to}</title><style>{STYLE}</style></head><body>", from = report.from, to = report.to)?;
+    write!(html, "<h1>Report</h1><p class=\"muted\">{} – {}</p>", report.from.format("%-d %B %Y"), report.to.format("%-d %B %Y"))?;
+    if report.subjects.is_empty() {
+        write!(html, "<p>Nothing happened during this period.</p>")?;
+    }
+    for subject in &report.subjects {
+        write!(html, "<section><h2>{} <span class=\"muted\">{}</span></h2>", escape(&subject.subject.name), escape(&subject.subject.class))?;
+        write!(html, "<p>Grade: {} · Completed to-dos: {} · Notes: {}</p>", format_grade(subject.grade.as_ref()), subject.completed_todos.len(), subject.note_count)?;
+        if !subject.assessments.is_empty() {
+            write!(html, "<table><tr><th>Assessment</th><th>Due</th><th>Weight</th><th>Status</th><th>Mark</th></tr>")?;
+            for assessment in &subject.assessments {
+                let mark = match (assessment.mark, assessment.mark_out_of) {
+                    (Some(mark), Some(mark_out_of)) => format!("{mark}/{mark_out_of}"),
+                    _ => "—".to_string(),
+                };
+                write!(
+                    html,
+                    "<tr><td>{title}{exam}</td><td>{due}</td><td>{weight}%</td><td>{status}</td><td>{mark}</td></tr>",
+                    title = escape(&assessment.title),
+                    exam = if assessment.exam { " (exam)" } else { "" },
+                    due = assessment.due.map(|due| due.to_string()).unwrap_or_default(),
+                    weight = assessment.weight,
+                    status = format_status(assessment.status),
+                )?;
+            }
+            write!(html, "</table>")?;
+        }
+        if !subject.completed_todos.is_empty() {
+            write!(html, "<h3>Completed to-dos</h3><ul>")?;
+            for todo in &subject.completed_todos {
+                write!(html, "<li>{}</li>", escape(&todo.title))?;
+            }
+            write!(html, "</ul>")?;
+        }
+        write!(html, "</section>")?;
+    }
+    write!(html, "</body></html>")?;
+    Ok(html)
+}
+
+/// Renders a summary of the authenticated user's subjects between two dates or during a term as printable HTML.
+/// Either both `from` and `to` or only `term` must be given.
+#[get("/?<from>&<to>&<term>")]
+async fn report(user: User, db: &State<PgPool>, from: Option<&str>, to: Option<&str>, term: Option<i32>) -> Result<RawHtml<String>, Status> {
+    let (from, to) = match (from, to, term) {
+        (Some(from), Some(to), None) => (
+            NaiveDate::parse_from_str(from, "%Y-%m-%d").or(Err(Status::BadRequest))?,
+            NaiveDate::parse_from_str(to, "%Y-%m-%d").or(Err(Status::BadRequest))?,
+        ),
+        (None, None, Some(term)) => Term::dates(db, user.id, term).await
+            .or(Err(Status::InternalServerError))?
+            .ok_or(Status::NotFound)?,
+        _ => return Err(Status::BadRequest),
+    };
+    if from > to {
+        return Err(Status::BadRequest);
+    }
+    let report = Report::build(db, user.id, from, to).await.or(Err(Status::InternalServerError))?;
+    render(&report).map(RawHtml).or(Err(Status::InternalServerError))
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use async_graphql::{Context, Result, Subscription};
+use futures_util::{stream, Stream, StreamExt};
+use rocket::http::Status;
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use crate::api::graphql::collaboration::{Collaboration, Collaborator, NoteEvent};
+use crate::api::graphql::query::sharing::NoteRole;
+use crate::auth::User;
+
+pub struct SubscriptionRoot;
+
+/// A client subscribed to a note's events, which leaves the note's collaborators when the subscription ends.
+struct Subscriber {
+    collaboration: Arc<Collaboration>,
+    pool: PgPool,
+    note: i32,
+    client_id: String,
+    events: Receiver<NoteEvent>,
+}
+
+impl Subscriber {
+    /// Waits for the next event for the client, skipping the client's own changes.
+    async fn next(&mut self) -> Option<NoteEvent> {
+        loop {
+            match self.events.recv().await {
+                // Clients already have their own changes
+                Ok(NoteEvent::Changes(changes)) if changes.client_id == self.client_id => {}
+                Ok(event) => return Some(event),
+                // Clients that fell behind start again from the whole document
+                Err(RecvError::Lagged(_)) => return self.collaboration.sync(&self.pool, self.note).await.ok().map(NoteEvent::Sync),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let collaboration = Arc::clone(&self.collaboration);
+        let (note, client_id) = (self.note, self.client_id.clone());
+        tokio::spawn(async move {
+            collaboration.leave(note, &client_id).await;
+        });
+    }
+}
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Live events for editing a note together with other users.
+    /// The first event is a `NoteSync` with the whole document, followed by other clients' changes and presence.
+    /// Changes are sent with `MutationRoot.note.mergeChanges`.
+    /// Requires authentication and access to the note.
+    async fn note_events(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "The ID of the note to follow.")] note: i32,
+        #[graphql(desc = "An ID unique to the subscribing client, such as a random UUID.", validator(min_length = 1, max_length = 64))] client_id: String,
+    ) -> Result<impl Stream<Item = NoteEvent>> {
+        let Some(user) = ctx.data::<Option<User>>()? else {
+            return Err(Status::Unauthorized.into());
+        };
+        let pool = ctx.data::<PgPool>()?;
+        NoteRole::of(pool, note, user.id).await?.ok_or(Status::NotFound)?;
+        let collaboration = Arc::clone(ctx.data::<Arc<Collaboration>>()?);
+        let (sync, events) = collaboration.join(pool, note, Collaborator {
+            client_id: client_id.clone(),
+            user: user.id,
+            selection: None,
+        }).await?;
+        let subscriber = Subscriber {
+            collaboration,
+            pool: pool.clone(),
+            note,
+            client_id,
+            events,
+        };
+        Ok(stream::once(async { NoteEvent::Sync(sync) })
+            .chain(stream::unfold(subscriber, |mut subscriber| async move {
+                subscriber.next().await.map(|event| (event, subscriber))
+            })))
+    }
+}
@@ -0,0 +1,123 @@
+use std::ops::Range;
+use crate::api::graphql::tiptap::Node;
+
+/// What a cloze deletion is replaced with on the front of its card.
+const GAP: &str = "[…]";
+
+/// Content of a document that can be learned with flashcards.
+pub enum Flashcard<'a> {
+    /// A question or definition term, and the answers or descriptions that follow it.
+    Pair { front: &'a Node, back: Vec<&'a Node> },
+    /// A block's inline content and the runs of it marked for cloze deletion.
+    Cloze { content: &'a [Node], deletions: Vec<Range<usize>> },
+}
+
+/// A flashcard found in a document.
+pub struct ExtractedCard {
+    pub front: String,
+    pub back: String,
+}
+
+/// Finds the content of a document that can be learned with flashcards, in document order.
+/// Each question of a question and answer block is paired with the answers that follow it,
+/// each term of a definition list is paired with its descriptions,
+/// and each block with cloze deletions is found once with all of its deletions.
+pub fn find(document: &Node) -> Vec<Flashcard<'_>> {
+    let mut found = Vec::new();
+    visit(document, &mut found);
+    found
+}
+
+/// Finds the flashcards in a document as plain text.
+/// Each cloze deletion becomes its own card with the deleted text replaced by a gap.
+/// Cards with an empty front or back are left out.
+pub fn extract(document: &Node) -> Vec<ExtractedCard> {
+    let mut cards = Vec::new();
+    for flashcard in find(document) {
+        match flashcard {
+            Flashcard::Pair { front, back } => cards.push(ExtractedCard {
+                front: text(front),
+                back: back.into_iter().map(text).collect::<Vec<_>>().join("\n"),
+            }),
+            Flashcard::Cloze { content, deletions } => {
+                let back = content.iter().map(text).collect::<String>();
+                for deletion in deletions {
+                    let front = content[..deletion.start].iter().map(text)
+                        .chain([GAP.to_string()])
+                        .chain(content[deletion.end..].iter().map(text))
+                        .collect();
+                    cards.push(ExtractedCard { front, back: back.clone() });
+                }
+            }
+        }
+    }
+    cards.retain(|card| !card.front.trim().is_empty() && !card.back.trim().is_empty());
+    cards
+}
+
+fn visit<'a>(node: &'a Node, found: &mut Vec<Flashcard<'a>>) {
+    match node.kind.as_str() {
+        "questionAnswer" => pairs(&node.content, "question", found),
+        "definitionList" => pairs(&node.content, "definitionTerm", found),
+        _ => {}
+    }
+    if node.content.iter().any(is_inline) {
+        let deletions = deletions(&node.content);
+        if !deletions.is_empty() {
+            found.push(Flashcard::Cloze { content: &node.content, deletions });
+        }
+    }
+    for child in &node.content {
+        visit(child, found);
+    }
+}
+
+/// Pairs each node of the `front` type with the other nodes before the next one.
+fn pairs<'a>(nodes: &'a [Node], front: &str, found: &mut Vec<Flashcard<'a>>) {
+    let mut nodes = nodes.iter().peekable();
+    while let Some(node) = nodes.next() {
+        if node.kind != front {
+            continue;
+        }
+        let mut back = Vec::new();
+        while let Some(node) = nodes.next_if(|node| node.kind != front) {
+            back.push(node);
+        }
+        found.push(Flashcard::Pair { front: node, back });
+    }
+}
+
+/// Finds the runs of inline content with the cloze mark.
+fn deletions(nodes: &[Node]) -> Vec<Range<usize>> {
+    let mut deletions = Vec::new();
+    let mut index = 0;
+    while index < nodes.len() {
+        if !is_cloze(&nodes[index]) {
+            index += 1;
+            continue;
+        }
+        let end = nodes[index..].iter().position(|node| !is_cloze(node)).map_or(nodes.len(), |length| index + length);
+        deletions.push(index..end);
+        index = end;
+    }
+    deletions
+}
+
+fn is_inline(node: &Node) -> bool {
+    matches!(node.kind.as_str(), "text" | "hardBreak" | "image" | "noteLink")
+}
+
+fn is_cloze(node: &Node) -> bool {
+    node.marks.iter().any(|mark| mark.kind == "cloze")
+}
+
+/// Gets the plain text of a node, with line breaks between blocks.
+fn text(node: &Node) -> String {
+    match node.kind.as_str() {
+        "text" => node.text.clone().unwrap_or_default(),
+        "hardBreak" => "\n".to_string(),
+        "noteLink" => node.note_link_label(),
+        _ if node.content.iter().any(is_inline) => node.content.iter().map(text).collect(),
+        _ => node.content.iter().map(text).filter(|text| !text.is_empty()).collect::<Vec<_>>().join("\n"),
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt::{Result, Write};
+use crate::api::graphql::tiptap::{Mark, Node};
+
+/// Escapes text for inclusion in HTML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+/// Checks that a link or image URL cannot run scripts when opened.
+pub fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    match url.split_once(':') {
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => {
+            ["http", "https", "mailto", "tel"].contains(&scheme) || url.starts_with("data:image/")
+        }
+        _ => true,
+    }
+}
+
+/// Renders a `TipTap` document as an HTML fragment.
+pub fn render(document: &Node) -> std::result::Result<String, std::fmt::Error> {
+    let mut html = String::new();
+    nodes(&mut html, &document.content)?;
+    Ok(html)
+}
+
+fn nodes(html: &mut String, nodes: &[Node]) -> Result {
+    for node in nodes {
+        self::node(html, node)?;
+    }
+    Ok(())
+}
+
+fn wrap(html: &mut String, tag: &str, node: &Node) -> Result {
+    write!(html, "<{tag}>")?;
+    nodes(html, &node.content)?;
+    write!(html, "</{tag}>")
+}
+
+fn node(html: &mut String, node: &Node) -> Result {
+    match node.kind.as_str() {
+        "text" => text(html, node),
+        "paragraph" => wrap(html, "p", node),
+        "heading" => wrap(html, &format!("h{}", node.int_attr("level").unwrap_or(1).clamp(1, 6)), node),
+        "blockquote" => wrap(html, "blockquote", node),
+        "bulletList" => wrap(html, "ul", node),
+        "orderedList" => match node.int_attr("start") {
+            Some(start) if start != 1 => {
+                write!(html, "<ol start=\"{start}\">")?;
+                nodes(html, &node.content)?;
+                write!(html, "</ol>")
+            }
+            _ => wrap(html, "ol", node),
+        },
+        "listItem" => wrap(html, "li", node),
+        "taskList" => {
+            write!(html, "<ul class=\"task-list\">")?;
+            nodes(html, &node.content)?;
+            write!(html, "</ul>")
+        }
+        "taskItem" => {
+            let checked = node.attrs.get("checked").and_then(serde_json::Value::as_bool).unwrap_or(false);
+            write!(html, "<li class=\"task-item\"><input type=\"checkbox\" disabled{}> ", if checked { " checked" } else { "" })?;
+            nodes(html, &node.content)?;
+            write!(html, "</li>")
+        }
+        "codeBlock" => {
+            match node.str_attr("language") {
+                Some(language) => write!(html, "<pre><code class=\"language-{}\">", escape(language))?,
+                None => write!(html, "<pre><code>")?,
+            }
+            write!(html, "{}</code></pre>", escape(&node.text_content()))
+        }
+        "horizontalRule" => write!(html, "<hr>"),
+        "hardBreak" => write!(html, "<br>"),
+        "image" => {
+            let src = node.str_attr("src").filter(|src| is_safe_url(src)).unwrap_or_default();
+            write!(html, "<img src=\"{}\" alt=\"{}\"", escape(src), escape(node.str_attr("alt").unwrap_or_default()))?;
+            if let Some(title) = node.str_attr("title") {
+                write!(html, " title=\"{}\"", escape(title))?;
+            }
+            write!(html, ">")
+        }
+        "noteLink" => write!(
+            html,
+            "<span class=\"note-link\" data-note=\"{}\">{}</span>",
+            node.int_attr("id").unwrap_or_default(),
+            escape(&node.note_link_label()),
+        ),
+        "questionAnswer" => {
+            write!(html, "<div class=\"question-answer\">")?;
+            nodes(html, &node.content)?;
+            write!(html, "</div>")
+        }
+        "question" | "answer" => {
+            write!(html, "<div class=\"{}\">", node.kind)?;
+            nodes(html, &node.content)?;
+            write!(html, "</div>")
+        }
+        "definitionList" => wrap(html, "dl", node),
+        "definitionTerm" => wrap(html, "dt", node),
+        "definitionDescription" => wrap(html, "dd", node),
+        "table" => wrap(html, "table", node),
+        "tableRow" => wrap(html, "tr", node),
+        "tableHeader" | "tableCell" => {
+            let tag = if node.kind == "tableHeader" { "th" } else { "td" };
+            write!(html, "<{tag}")?;
+            for span in ["colspan", "rowspan"] {
+                if let Some(count) = node.int_attr(span).filter(|count| *count > 1) {
+                    write!(html, " {span}=\"{count}\"")?;
+                }
+            }
+            write!(html, ">")?;
+            nodes(html, &node.content)?;
+            write!(html, "</{tag}>")
+        }
+        _ => nodes(html, &node.content),
+    }
+}
+
+/// Gets the opening and closing tags of a mark.
+fn tags(mark: &Mark) -> (String, &'static str) {
+    match mark.kind.as_str() {
+        "bold" => ("<strong>".to_string(), "</strong>"),
+        "italic" => ("<em>".to_string(), "</em>"),
+        "strike" => ("<s>".to_string(), "</s>"),
+        "underline" => ("<u>".to_string(), "</u>"),
+        "code" => ("<code>".to_string(), "</code>"),
+        "highlight" => ("<mark>".to_string(), "</mark>"),
+        "subscript" => ("<sub>".to_string(), "</sub>"),
+        "superscript" => ("<sup>".to_string(), "</sup>"),
+        "cloze" => ("<span class=\"cloze\">".to_string(), "</span>"),
+        "link" => {
+            let href = mark.str_attr("href").filter(|href| is_safe_url(href)).unwrap_or_default();
+            (format!("<a href=\"{}\">", escape(href)), "</a>")
+        }
+        _ => (String::new(), ""),
+    }
+}
+
+fn text(html: &mut String, node: &Node) -> Result {
+    let tags = node.marks.iter().map(tags).collect::<Vec<_>>();
+    for (open, _) in &tags {
+        write!(html, "{open}")?;
+    }
+    write!(html, "{}", escape(node.text.as_deref().unwrap_or_default()))?;
+    for (_, close) in tags.iter().rev() {
+        write!(html, "{close}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,529 @@
+use std::collections::BTreeSet;
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag};
+use serde_json::Value;
+use crate::api::graphql::tiptap::{Mark, Node};
+
+/// Renders a `TipTap` document as standard Markdown.
+/// Tables, task lists and strikethrough use GitHub Flavored Markdown, and marks Markdown has no syntax for use inline HTML.
+pub fn render(document: &Node) -> String {
+    let mut markdown = blocks(&document.content);
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Escapes characters in text that Markdown would otherwise interpret.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if matches!(char, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '&') {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+/// Escapes the start of a line that would otherwise begin a heading, list, quote or thematic break.
+fn escape_line_start(line: &str) -> String {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if line.starts_with(['#', '-', '+', '=']) {
+        format!("\\{line}")
+    } else if digits > 0 && line[digits..].starts_with(['.', ')']) {
+        format!("{}\\{}", &line[..digits], &line[digits..])
+    } else {
+        line.to_string()
+    }
+}
+
+/// Prefixes the first line of `text` with `first` and every other line with `rest`.
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            let prefix = if index == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Gets a string of backticks longer than any run of backticks in `text`.
+fn fence(text: &str, minimum: usize) -> String {
+    let longest = text.split(|char| char != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(minimum.max(longest + 1))
+}
+
+/// Formats a URL as a link destination.
+fn destination(url: &str) -> String {
+    if url.contains([' ', '(', ')', '<', '>']) {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_string()
+    }
+}
+
+fn blocks(nodes: &[Node]) -> String {
+    nodes.iter()
+        .map(block)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn block(node: &Node) -> String {
+    match node.kind.as_str() {
+        "paragraph" => inline(&node.content).split('\n').map(escape_line_start).collect::<Vec<_>>().join("\n"),
+        "heading" => {
+            let level = usize::try_from(node.int_attr("level").unwrap_or(1).clamp(1, 6)).unwrap_or(1);
+            format!("{} {}", "#".repeat(level), inline(&node.content).replace("\\\n", " "))
+        }
+        "blockquote" => indent(&blocks(&node.content), "> ", "> "),
+        "bulletList" => list(&node.content, |_, _| "- ".to_string()),
+        "orderedList" => {
+            let start = node.int_attr("start").unwrap_or(1);
+            list(&node.content, |index, _| format!("{}. ", start.saturating_add(i64::try_from(index).unwrap_or(0))))
+        }
+        "taskList" => list(&node.content, |_, item| {
+            let checked = item.attrs.get("checked").and_then(serde_json::Value::as_bool).unwrap_or(false);
+            if checked { "- [x] " } else { "- [ ] " }.to_string()
+        }),
+        "codeBlock" => {
+            let code = node.text_content();
+            let fence = fence(&code, 3);
+            format!("{fence}{}\n{code}\n{fence}", node.str_attr("language").unwrap_or_default())
+        }
+        "horizontalRule" => "---".to_string(),
+        "table" => table(node),
+        "question" => indent(&blocks(&node.content), "**Q:** ", ""),
+        "answer" => indent(&blocks(&node.content), "**A:** ", ""),
+        // Definition lists use the PHP Markdown Extra syntax
+        "definitionList" => node.content.iter()
+            .map(|item| match item.kind.as_str() {
+                "definitionTerm" => inline(&item.content).replace("\\\n", " "),
+                _ => indent(&blocks(&item.content), ": ", "  "),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "text" | "hardBreak" | "image" | "noteLink" => inline(std::slice::from_ref(node)),
+        _ => blocks(&node.content),
+    }
+}
+
+fn list(items: &[Node], marker: impl Fn(usize, &Node) -> String) -> String {
+    items.iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let marker = marker(index, item);
+            // Task items continue at the column after the bullet, not after the checkbox
+            let width = if marker.starts_with("- ") { 2 } else { marker.len() };
+            // Nested lists follow their item's text directly so the outer list stays tight
+            let mut content = String::new();
+            for (index, block) in item.content.iter().enumerate() {
+                let text = self::block(block);
+                if index > 0 && !text.is_empty() {
+                    content.push_str(if block.kind.ends_with("List") { "\n" } else { "\n\n" });
+                }
+                content.push_str(&text);
+            }
+            indent(&content, &marker, &" ".repeat(width))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn table(node: &Node) -> String {
+    let rows = node.content.iter()
+        .map(|row| row.content.iter()
+            .map(|cell| cell.content.iter()
+                .map(|paragraph| inline(&paragraph.content).replace("\\\n", "<br>"))
+                .collect::<Vec<_>>()
+                .join("<br>"))
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if columns == 0 {
+        return String::new();
+    }
+    let format_row = |cells: &[String]| {
+        let mut row = String::from("|");
+        for column in 0..columns {
+            row.push(' ');
+            row.push_str(cells.get(column).map_or("", String::as_str));
+            row.push_str(" |");
+        }
+        row
+    };
+    let mut lines = vec![format_row(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|row| format_row(row)));
+    lines.join("\n")
+}
+
+/// Gets the opening and closing delimiters of a mark around `text`.
+fn delimiters(mark: &Mark, text: &str) -> (String, String) {
+    match mark.kind.as_str() {
+        "bold" => ("**".to_string(), "**".to_string()),
+        "italic" => ("*".to_string(), "*".to_string()),
+        "strike" => ("~~".to_string(), "~~".to_string()),
+        "code" => {
+            let fence = fence(text, 1);
+            if text.starts_with('`') || text.ends_with('`') {
+                (format!("{fence} "), format!(" {fence}"))
+            } else {
+                (fence.clone(), fence)
+            }
+        }
+        "link" => ("[".to_string(), format!("]({})", destination(mark.str_attr("href").unwrap_or_default()))),
+        "underline" => ("<u>".to_string(), "</u>".to_string()),
+        "highlight" => ("<mark>".to_string(), "</mark>".to_string()),
+        "subscript" => ("<sub>".to_string(), "</sub>".to_string()),
+        "superscript" => ("<sup>".to_string(), "</sup>".to_string()),
+        "cloze" => ("<span class=\"cloze\">".to_string(), "</span>".to_string()),
+        _ => (String::new(), String::new()),
+    }
+}
+
+/// Closes open marks until only `keep` remain.
+/// Trailing spaces are moved outside the delimiters, because emphasis cannot end with whitespace.
+fn close(markdown: &mut String, open: &mut Vec<(&Mark, String)>, keep: usize) {
+    if open.len() <= keep {
+        return;
+    }
+    let trimmed = markdown.trim_end_matches([' ', '\t']).len();
+    let whitespace = markdown.split_off(trimmed);
+    while open.len() > keep {
+        if let Some((_, closing)) = open.pop() {
+            markdown.push_str(&closing);
+        }
+    }
+    markdown.push_str(&whitespace);
+}
+
+/// Renders inline content, merging marks shared by adjacent text nodes.
+fn inline(nodes: &[Node]) -> String {
+    let mut markdown = String::new();
+    let mut open = Vec::<(&Mark, String)>::new();
+    for node in nodes {
+        let keep = open.iter().zip(&node.marks).take_while(|((open, _), mark)| *open == *mark).count();
+        close(&mut markdown, &mut open, keep);
+        match node.kind.as_str() {
+            "text" => {
+                let mut text = node.text.as_deref().unwrap_or_default();
+                if keep < node.marks.len() {
+                    // Emphasis cannot start with whitespace either
+                    let trimmed = text.trim_start_matches([' ', '\t']);
+                    markdown.push_str(&text[..text.len() - trimmed.len()]);
+                    text = trimmed;
+                }
+                for mark in &node.marks[keep..] {
+                    let (opening, closing) = delimiters(mark, text);
+                    markdown.push_str(&opening);
+                    open.push((mark, closing));
+                }
+                if node.marks.iter().any(|mark| mark.kind == "code") {
+                    markdown.push_str(text);
+                } else {
+                    markdown.push_str(&escape(text));
+                }
+            }
+            "hardBreak" => markdown.push_str("\\\n"),
+            "image" => {
+                markdown.push_str("![");
+                markdown.push_str(&escape(node.str_attr("alt").unwrap_or_default()));
+                markdown.push_str("](");
+                markdown.push_str(&destination(node.str_attr("src").unwrap_or_default()));
+                if let Some(title) = node.str_attr("title") {
+                    markdown.push_str(" \"");
+                    markdown.push_str(&title.replace('"', "\\\""));
+                    markdown.push('"');
+                }
+                markdown.push(')');
+            }
+            "noteLink" => {
+                markdown.push_str("[[");
+                markdown.push_str(&escape(&node.note_link_label()));
+                markdown.push_str("]]");
+            }
+            _ => markdown.push_str(&inline(&node.content)),
+        }
+    }
+    close(&mut markdown, &mut open, 0);
+    markdown
+}
+
+/// Nodes whose content must be blocks rather than text.
+const CONTAINERS: [&str; 6] = ["doc", "blockquote", "listItem", "taskItem", "tableHeader", "tableCell"];
+
+/// Inline HTML tags that stand for marks Markdown has no syntax for.
+const HTML_MARKS: [(&str, &str); 4] = [("u", "underline"), ("mark", "highlight"), ("sub", "subscript"), ("sup", "superscript")];
+
+#[derive(Default)]
+struct Builder {
+    /// The nodes being built, each with whether it is a paragraph that was opened for loose text.
+    stack: Vec<(Node, bool)>,
+    marks: Vec<Mark>,
+    image: Option<Node>,
+    table_head: bool,
+    warnings: BTreeSet<String>,
+}
+
+impl Builder {
+    /// The innermost node being built, or `None` if the document has already been closed.
+    fn top(&mut self) -> Option<&mut Node> {
+        self.stack.last_mut().map(|(node, _)| node)
+    }
+
+    fn close_implicit(&mut self) {
+        if self.stack.last().is_some_and(|(_, implicit)| *implicit) {
+            self.close();
+        }
+    }
+
+    fn open(&mut self, node: Node) {
+        self.close_implicit();
+        self.stack.push((node, false));
+    }
+
+    fn close(&mut self) {
+        let Some((mut node, _)) = self.stack.pop() else {
+            return;
+        };
+        if node.kind == "codeBlock" {
+            if let Some(text) = node.content.first_mut().and_then(|text| text.text.as_mut()) {
+                text.truncate(text.trim_end_matches('\n').len());
+            }
+            node.content.retain(|text| text.text.as_ref().is_some_and(|text| !text.is_empty()));
+        }
+        if CONTAINERS.contains(&node.kind.as_str()) && node.content.is_empty() {
+            node.content.push(Node::new("paragraph"));
+        }
+        match self.stack.last_mut() {
+            Some((parent, _)) => parent.content.push(node),
+            None => self.stack.push((node, false)),
+        }
+    }
+
+    fn block(&mut self, node: Node) {
+        self.open(node);
+        self.close();
+    }
+
+    fn inline(&mut self, node: Node) -> Option<()> {
+        if let Some(image) = &mut self.image {
+            // Text inside an image is its alternative text
+            if let Some(text) = &node.text {
+                let alt = format!("{}{text}", image.str_attr("alt").unwrap_or_default());
+                image.attrs.insert("alt".to_string(), Value::String(alt));
+            }
+            return Some(());
+        }
+        if !matches!(self.top()?.kind.as_str(), "paragraph" | "heading" | "codeBlock") {
+            self.stack.push((Node::new("paragraph"), true));
+        }
+        let parent = self.top()?;
+        if let (Some(last), Some(text)) = (parent.content.last_mut(), &node.text) {
+            if last.kind == "text" && last.marks == node.marks {
+                if let Some(last) = &mut last.text {
+                    last.push_str(text);
+                    return Some(());
+                }
+            }
+        }
+        parent.content.push(node);
+        Some(())
+    }
+
+    fn text(&mut self, text: &str) -> Option<()> {
+        if text.is_empty() {
+            return Some(());
+        }
+        let marks = if self.top()?.kind == "codeBlock" { Vec::new() } else { self.marks.clone() };
+        self.inline(Node::text(text, marks))
+    }
+
+    fn html(&mut self, html: &str) -> Option<()> {
+        let tag = html.trim().to_ascii_lowercase();
+        if matches!(tag.as_str(), "<br>" | "<br/>" | "<br />") {
+            return self.inline(Node::new("hardBreak"));
+        }
+        for (name, mark) in HTML_MARKS {
+            if tag == format!("<{name}>") {
+                self.marks.push(Mark::new(mark));
+                return Some(());
+            }
+            if tag == format!("</{name}>") {
+                if let Some(index) = self.marks.iter().rposition(|open| open.kind == mark) {
+                    self.marks.remove(index);
+                }
+                return Some(());
+            }
+        }
+        self.warnings.insert("HTML was imported as plain text.".to_string());
+        self.text(html.trim_end_matches('\n'))
+    }
+
+    fn start(&mut self, tag: Tag) -> Option<()> {
+        match tag {
+            Tag::Paragraph => self.open(Node::new("paragraph")),
+            Tag::Heading(level, _, _) => {
+                let mut heading = Node::new("heading");
+                let level = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    HeadingLevel::H3 => 3,
+                    HeadingLevel::H4 => 4,
+                    HeadingLevel::H5 => 5,
+                    HeadingLevel::H6 => 6,
+                };
+                heading.attrs.insert("level".to_string(), level.into());
+                self.open(heading);
+            }
+            Tag::BlockQuote => self.open(Node::new("blockquote")),
+            Tag::CodeBlock(kind) => {
+                let mut code = Node::new("codeBlock");
+                let language = match &kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next(),
+                    CodeBlockKind::Indented => None,
+                };
+                code.attrs.insert("language".to_string(), language.map_or(Value::Null, Into::into));
+                self.open(code);
+            }
+            Tag::List(start) => {
+                let mut list = Node::new(if start.is_some() { "orderedList" } else { "bulletList" });
+                if let Some(start) = start {
+                    list.attrs.insert("start".to_string(), start.into());
+                }
+                self.open(list);
+            }
+            Tag::Item => self.open(Node::new("listItem")),
+            Tag::FootnoteDefinition(label) => {
+                self.warnings.insert("Footnotes were imported as plain text.".to_string());
+                self.open(Node::new("paragraph"));
+                self.text(&format!("[^{label}]:"))?;
+                self.close();
+            }
+            Tag::Table(alignments) => {
+                if alignments.iter().any(|alignment| *alignment != pulldown_cmark::Alignment::None) {
+                    self.warnings.insert("Table column alignment is not supported.".to_string());
+                }
+                self.open(Node::new("table"));
+            }
+            Tag::TableHead => {
+                self.table_head = true;
+                self.open(Node::new("tableRow"));
+            }
+            Tag::TableRow => self.open(Node::new("tableRow")),
+            Tag::TableCell => self.open(Node::new(if self.table_head { "tableHeader" } else { "tableCell" })),
+            Tag::Emphasis => self.marks.push(Mark::new("italic")),
+            Tag::Strong => self.marks.push(Mark::new("bold")),
+            Tag::Strikethrough => self.marks.push(Mark::new("strike")),
+            Tag::Link(kind, url, title) => {
+                let mut link = Mark::new("link");
+                let href = if kind == LinkType::Email { format!("mailto:{url}") } else { url.to_string() };
+                link.attrs.insert("href".to_string(), href.into());
+                if !title.is_empty() {
+                    link.attrs.insert("title".to_string(), title.to_string().into());
+                }
+                self.marks.push(link);
+            }
+            Tag::Image(_, url, title) => {
+                let mut image = Node::new("image");
+                image.attrs.insert("src".to_string(), url.to_string().into());
+                if !title.is_empty() {
+                    image.attrs.insert("title".to_string(), title.to_string().into());
+                }
+                self.image = Some(image);
+            }
+        }
+        Some(())
+    }
+
+    fn end(&mut self, tag: &Tag) -> Option<()> {
+        match tag {
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) => {
+                self.marks.pop();
+            }
+            Tag::Image(..) => {
+                if let Some(image) = self.image.take() {
+                    self.inline(image)?;
+                }
+            }
+            Tag::FootnoteDefinition(_) => {}
+            Tag::List(_) => {
+                self.close();
+                // Task lists can only contain task items, so a list with any checkboxes becomes one
+                if let Some(list) = self.top()?.content.last_mut() {
+                    if list.content.iter().any(|item| item.kind == "taskItem") {
+                        list.kind = "taskList".to_string();
+                        list.attrs.clear();
+                        for item in &mut list.content {
+                            if item.kind == "listItem" {
+                                item.kind = "taskItem".to_string();
+                                item.attrs.insert("checked".to_string(), false.into());
+                            }
+                        }
+                    }
+                }
+            }
+            Tag::TableHead => {
+                self.close();
+                self.table_head = false;
+            }
+            _ => {
+                self.close_implicit();
+                self.close();
+            }
+        }
+        Some(())
+    }
+
+    fn event(&mut self, event: Event) -> Option<()> {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(&tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => self.inline(Node::text(&code, vec![Mark::new("code")])),
+            Event::Html(html) => self.html(&html),
+            Event::FootnoteReference(label) => {
+                self.warnings.insert("Footnotes were imported as plain text.".to_string());
+                self.text(&format!("[^{label}]"))
+            }
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.inline(Node::new("hardBreak")),
+            Event::Rule => {
+                self.block(Node::new("horizontalRule"));
+                Some(())
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some((item, _)) = self.stack.iter_mut().rev().find(|(node, _)| node.kind == "listItem") {
+                    item.kind = "taskItem".to_string();
+                    item.attrs.insert("checked".to_string(), checked.into());
+                }
+                Some(())
+            }
+        }
+    }
+}
+
+/// Parses Markdown, including GitHub Flavored Markdown tables, task lists and strikethrough, into a `TipTap` document.
+/// Returns the document and warnings about anything that could not be imported as-is,
+/// or `None` if the Markdown's blocks are unbalanced.
+pub fn parse(markdown: &str) -> Option<(Node, Vec<String>)> {
+    let mut builder = Builder::default();
+    builder.stack.push((Node::new("doc"), false));
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS | Options::ENABLE_FOOTNOTES;
+    for event in Parser::new_ext(markdown, options) {
+        builder.event(event)?;
+    }
+    while builder.stack.len() > 1 {
+        builder.close();
+    }
+    builder.close();
+    let (document, _) = builder.stack.pop()?;
+    Some((document, builder.warnings.into_iter().collect()))
+}
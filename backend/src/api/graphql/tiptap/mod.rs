@@ -0,0 +1,126 @@
+pub mod flashcards;
+pub mod html;
+pub mod markdown;
+pub mod schema;
+pub mod template;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A node of a `TipTap` document, such as a paragraph or a piece of text.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Node {
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub attrs: Map<String, Value>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content: Vec<Node>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub marks: Vec<Mark>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// A mark applied to a text node, such as bold or a link.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Mark {
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub attrs: Map<String, Value>,
+}
+
+impl Node {
+    /// Creates an empty node of the given type.
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Creates a text node.
+    pub fn text(text: &str, marks: Vec<Mark>) -> Self {
+        Self {
+            kind: "text".to_string(),
+            marks,
+            text: Some(text.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Converts plain text to a document.
+    /// Blank lines separate paragraphs, and other line breaks are kept as hard breaks.
+    pub fn from_plain_text(text: &str) -> Self {
+        let mut document = Self::new("doc");
+        for block in text.replace("\r\n", "\n").split("\n\n") {
+            let block = block.trim_matches('\n');
+            if block.trim().is_empty() {
+                continue;
+            }
+            let mut paragraph = Self::new("paragraph");
+            for (index, line) in block.split('\n').enumerate() {
+                if index > 0 {
+                    paragraph.content.push(Self::new("hardBreak"));
+                }
+                if !line.is_empty() {
+                    paragraph.content.push(Self::text(line, Vec::new()));
+                }
+            }
+            document.content.push(paragraph);
+        }
+        if document.content.is_empty() {
+            document.content.push(Self::new("paragraph"));
+        }
+        document
+    }
+
+    /// Reads a document from its JSON representation.
+    pub fn from_value(value: &Value) -> serde_json::Result<Self> {
+        Self::deserialize(value)
+    }
+
+    /// Gets a string attribute of the node.
+    pub fn str_attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).and_then(Value::as_str)
+    }
+
+    /// Gets an integer attribute of the node.
+    pub fn int_attr(&self, name: &str) -> Option<i64> {
+        self.attrs.get(name).and_then(Value::as_i64)
+    }
+
+    /// Gets the text shown for a link to another note, which is its label or else the note's ID.
+    pub fn note_link_label(&self) -> String {
+        self.str_attr("label").map_or_else(|| format!("Note {}", self.int_attr("id").unwrap_or_default()), ToString::to_string)
+    }
+
+    /// Gets the text of the node and all of its descendants.
+    pub fn text_content(&self) -> String {
+        match &self.text {
+            Some(text) => text.clone(),
+            None => self.content.iter().map(Self::text_content).collect(),
+        }
+    }
+}
+
+impl Mark {
+    /// Creates a mark of the given type.
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Gets a string attribute of the mark.
+    pub fn str_attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).and_then(Value::as_str)
+    }
+}
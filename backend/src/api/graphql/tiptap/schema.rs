@@ -0,0 +1,380 @@
+use std::fmt::{Display, Formatter};
+use async_graphql::ErrorExtensions;
+use serde_json::{Map, Value};
+use crate::api::graphql::tiptap::html::is_safe_url;
+use crate::api::graphql::tiptap::{Mark, Node};
+
+/// A problem with a document, located by a JSON pointer such as `/content/2/marks/0`.
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", if self.path.is_empty() { "/" } else { &self.path }, self.message)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Attr {
+    Integer { min: i64, max: i64, default: i64 },
+    Boolean { default: bool },
+    /// A string or null, null by default.
+    Text,
+    /// A required link or image address that cannot run scripts.
+    Url,
+    /// The required ID of another record, such as a linked note.
+    Id,
+    /// Null or a list of column widths in pixels.
+    Widths,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Group {
+    Block,
+    Inline,
+    /// Nodes that can only appear inside a particular parent, such as list items.
+    None,
+}
+
+#[derive(Copy, Clone)]
+enum Content {
+    /// The node cannot have content.
+    Leaf,
+    /// Any number of inline nodes.
+    Inline,
+    /// Any number of text nodes without marks.
+    Text,
+    /// At least one block.
+    Blocks,
+    /// A paragraph followed by any number of blocks.
+    Item,
+    /// At least one of the listed nodes.
+    Only(&'static [&'static str]),
+}
+
+struct NodeSpec {
+    group: Group,
+    content: Content,
+    attrs: &'static [(&'static str, Attr)],
+}
+
+const SPANS: [(&str, Attr); 3] = [
+    ("colspan", Attr::Integer { min: 1, max: 1000, default: 1 }),
+    ("rowspan", Attr::Integer { min: 1, max: 1000, default: 1 }),
+    ("colwidth", Attr::Widths),
+];
+
+/// Gets the schema of a node type.
+/// The frontend does not configure a `TipTap` editor yet, so there is no editor configuration to derive this from.
+/// Instead it follows the defaults of the `TipTap` extensions whose content the backend reads and writes:
+/// `StarterKit`, `Table`, `TaskList`, `TaskItem`, `Image`, `Link`, `Underline`, `Highlight`, `Subscript` and `Superscript`,
+/// plus the `noteLink`, `questionAnswer` and `definitionList` nodes and the `cloze` mark defined by this app.
+/// An editor must register extensions with the same names and attributes to open every valid document.
+fn node_spec(kind: &str) -> Option<NodeSpec> {
+    let (group, content, attrs): (_, _, &'static [_]) = match kind {
+        "doc" | "question" | "answer" | "definitionDescription" => (Group::None, Content::Blocks, &[]),
+        "paragraph" => (Group::Block, Content::Inline, &[]),
+        "heading" => (Group::Block, Content::Inline, &[("level", Attr::Integer { min: 1, max: 6, default: 1 })]),
+        "blockquote" => (Group::Block, Content::Blocks, &[]),
+        "bulletList" => (Group::Block, Content::Only(&["listItem"]), &[]),
+        "orderedList" => (Group::Block, Content::Only(&["listItem"]), &[("start", Attr::Integer { min: 0, max: i64::MAX, default: 1 })]),
+        "listItem" => (Group::None, Content::Item, &[]),
+        "taskList" => (Group::Block, Content::Only(&["taskItem"]), &[]),
+        "taskItem" => (Group::None, Content::Item, &[("checked", Attr::Boolean { default: false })]),
+        "codeBlock" => (Group::Block, Content::Text, &[("language", Attr::Text)]),
+        "horizontalRule" => (Group::Block, Content::Leaf, &[]),
+        "table" => (Group::Block, Content::Only(&["tableRow"]), &[]),
+        "tableRow" => (Group::None, Content::Only(&["tableHeader", "tableCell"]), &[]),
+        "tableHeader" | "tableCell" => (Group::None, Content::Blocks, &SPANS),
+        "text" | "hardBreak" => (Group::Inline, Content::Leaf, &[]),
+        "image" => (Group::Inline, Content::Leaf, &[("src", Attr::Url), ("alt", Attr::Text), ("title", Attr::Text)]),
+        "noteLink" => (Group::Inline, Content::Leaf, &[("id", Attr::Id), ("label", Attr::Text)]),
+        "questionAnswer" => (Group::Block, Content::Only(&["question", "answer"]), &[]),
+        "definitionList" => (Group::Block, Content::Only(&["definitionTerm", "definitionDescription"]), &[]),
+        "definitionTerm" => (Group::None, Content::Inline, &[]),
+        _ => return None,
+    };
+    Some(NodeSpec { group, content, attrs })
+}
+
+/// Gets the attributes of a mark type, see [`node_spec`] for where the schema comes from.
+fn mark_spec(kind: &str) -> Option<&'static [(&'static str, Attr)]> {
+    match kind {
+        "bold" | "italic" | "strike" | "underline" | "code" | "highlight" | "subscript" | "superscript" | "cloze" => Some(&[]),
+        "link" => Some(&[("href", Attr::Url), ("target", Attr::Text), ("rel", Attr::Text), ("class", Attr::Text), ("title", Attr::Text)]),
+        _ => None,
+    }
+}
+
+struct Validator {
+    errors: Vec<SchemaError>,
+    dropped: Vec<SchemaError>,
+}
+
+impl Validator {
+    fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.errors.push(SchemaError { path: path.to_string(), message: message.into() });
+    }
+
+    /// Checks a node's attributes, filling in defaults and dropping attributes the schema does not know.
+    /// Returns `None` if an attribute is invalid.
+    fn attrs(&mut self, value: &Value, spec: &[(&str, Attr)], path: &str) -> Option<Map<String, Value>> {
+        let empty = Map::new();
+        let given = match value.get("attrs") {
+            None | Some(Value::Null) => &empty,
+            Some(Value::Object(attrs)) => attrs,
+            Some(_) => {
+                self.error(&format!("{path}/attrs"), "Attributes must be an object.");
+                return None;
+            }
+        };
+        for name in given.keys().filter(|name| !spec.iter().any(|(known, _)| known == name)) {
+            self.dropped.push(SchemaError { path: format!("{path}/attrs/{name}"), message: format!("Unknown attribute `{name}` was removed.") });
+        }
+        let mut attrs = Map::new();
+        let mut valid = true;
+        for (name, attr) in spec {
+            let path = format!("{path}/attrs/{name}");
+            let value = given.get(*name).unwrap_or(&Value::Null);
+            let normalised = match (attr, value) {
+                (Attr::Integer { default, .. }, Value::Null) => Some(Value::from(*default)),
+                (Attr::Integer { min, max, .. }, value) => match value.as_i64() {
+                    Some(integer) if (*min..=*max).contains(&integer) => Some(value.clone()),
+                    _ => {
+                        self.error(&path, format!("Expected an integer from {min} to {max}."));
+                        None
+                    }
+                },
+                (Attr::Boolean { default }, Value::Null) => Some(Value::Bool(*default)),
+                (Attr::Boolean { .. }, Value::Bool(_)) | (Attr::Text, Value::Null | Value::String(_)) => Some(value.clone()),
+                (Attr::Boolean { .. }, _) => {
+                    self.error(&path, "Expected true or false.");
+                    None
+                }
+                (Attr::Text, _) => {
+                    self.error(&path, "Expected a string or null.");
+                    None
+                }
+                (Attr::Url, Value::String(url)) if is_safe_url(url) => Some(value.clone()),
+                (Attr::Url, Value::String(_)) => {
+                    self.error(&path, "Only http, https, mailto and tel links and data images are allowed.");
+                    None
+                }
+                (Attr::Url, _) => {
+                    self.error(&path, "Expected an address.");
+                    None
+                }
+                (Attr::Id, value) if value.as_i64().is_some_and(|id| (1..=i64::from(i32::MAX)).contains(&id)) => Some(value.clone()),
+                (Attr::Id, _) => {
+                    self.error(&path, "Expected an ID.");
+                    None
+                }
+                (Attr::Widths, Value::Null) => Some(Value::Null),
+                (Attr::Widths, Value::Array(widths)) if widths.iter().all(|width| width.is_null() || width.as_u64().is_some()) => Some(value.clone()),
+                (Attr::Widths, _) => {
+                    self.error(&path, "Expected null or a list of widths.");
+                    None
+                }
+            };
+            match normalised {
+                Some(value) => {
+                    attrs.insert((*name).to_string(), value);
+                }
+                None => valid = false,
+            }
+        }
+        valid.then_some(attrs)
+    }
+
+    /// Checks the marks of a text node, removing duplicates.
+    /// Code cannot be combined with other marks, so they are removed from code.
+    fn marks(&mut self, value: &Value, path: &str) -> Vec<Mark> {
+        let marks = match value.get("marks") {
+            None | Some(Value::Null) => return Vec::new(),
+            Some(Value::Array(marks)) => marks,
+            Some(_) => {
+                self.error(&format!("{path}/marks"), "Marks must be a list.");
+                return Vec::new();
+            }
+        };
+        let mut valid = Vec::<Mark>::with_capacity(marks.len());
+        for (index, mark) in marks.iter().enumerate() {
+            let path = format!("{path}/marks/{index}");
+            let Some(kind) = mark.get("type").and_then(Value::as_str) else {
+                self.error(&path, "Marks must have a type.");
+                continue;
+            };
+            let Some(spec) = mark_spec(kind) else {
+                self.error(&path, format!("Unknown mark type `{kind}`."));
+                continue;
+            };
+            let Some(attrs) = self.attrs(mark, spec, &path) else {
+                continue;
+            };
+            if !valid.iter().any(|mark| mark.kind == kind) {
+                valid.push(Mark { kind: kind.to_string(), attrs });
+            }
+        }
+        if valid.iter().any(|mark| mark.kind == "code") {
+            valid.retain(|mark| mark.kind == "code");
+        }
+        valid
+    }
+
+    /// Checks a node and its descendants. Returns `None` if the node must be removed.
+    fn node(&mut self, value: &Value, path: &str) -> Option<(Node, Group)> {
+        let Some(kind) = value.get("type").and_then(Value::as_str) else {
+            self.error(path, "Nodes must be objects with a type.");
+            return None;
+        };
+        let Some(spec) = node_spec(kind) else {
+            self.error(path, format!("Unknown node type `{kind}`."));
+            return None;
+        };
+        let attrs = self.attrs(value, spec.attrs, path)?;
+        let mut node = Node { kind: kind.to_string(), attrs, ..Node::default() };
+        if kind == "text" {
+            node.text = value.get("text").and_then(Value::as_str).map(ToString::to_string);
+            if node.text.is_none() {
+                self.error(path, "Text nodes must have text.");
+                return None;
+            }
+            node.marks = self.marks(value, path);
+            return Some((node, spec.group));
+        }
+        let children = match value.get("content") {
+            None | Some(Value::Null) => &[][..],
+            Some(Value::Array(children)) => children.as_slice(),
+            Some(_) => {
+                self.error(&format!("{path}/content"), "Content must be a list.");
+                return None;
+            }
+        };
+        for (index, child) in children.iter().enumerate() {
+            let path = format!("{path}/content/{index}");
+            let Some((child, group)) = self.node(child, &path) else {
+                continue;
+            };
+            let allowed = match spec.content {
+                Content::Leaf => false,
+                Content::Inline => group == Group::Inline,
+                Content::Text => child.kind == "text",
+                Content::Blocks | Content::Item => group == Group::Block,
+                Content::Only(kinds) => kinds.contains(&child.kind.as_str()),
+            };
+            if allowed {
+                node.content.push(child);
+            } else {
+                self.error(&path, format!("`{}` cannot contain `{}`.", kind, child.kind));
+            }
+        }
+        normalise(&mut node, spec.content);
+        Some((node, spec.group))
+    }
+}
+
+/// Repairs content that the editor would fix itself, such as empty list items and separate text nodes with the same marks.
+fn normalise(node: &mut Node, content: Content) {
+    match content {
+        Content::Inline | Content::Text => {
+            let mut merged = Vec::<Node>::with_capacity(node.content.len());
+            for mut child in node.content.drain(..) {
+                if matches!(content, Content::Text) {
+                    child.marks.clear();
+                }
+                if child.text.as_ref().is_some_and(String::is_empty) {
+                    continue;
+                }
+                if let (Some(last), Some(text)) = (merged.last_mut(), &child.text) {
+                    if last.text.is_some() && last.marks == child.marks {
+                        last.text.get_or_insert_with(String::new).push_str(text);
+                        continue;
+                    }
+                }
+                merged.push(child);
+            }
+            node.content = merged;
+        }
+        // The editor fills in empty documents itself, and they are the database's default
+        Content::Blocks if node.content.is_empty() && node.kind != "doc" => node.content.push(Node::new("paragraph")),
+        Content::Item if node.content.first().is_none_or(|first| first.kind != "paragraph") => node.content.insert(0, Node::new("paragraph")),
+        Content::Only(kinds) if node.content.is_empty() => {
+            let mut child = Node::new(kinds[0]);
+            if let Some(spec) = node_spec(kinds[0]) {
+                normalise(&mut child, spec.content);
+            }
+            node.content.push(child);
+        }
+        _ => {}
+    }
+}
+
+/// Converts a checked document back to JSON, keeping the content list of an empty document as the editor saves it.
+fn to_value(document: Node) -> Value {
+    let mut value = serde_json::to_value(document).unwrap_or(Value::Null);
+    if let Some(document) = value.as_object_mut() {
+        document.entry("content").or_insert_with(|| Value::Array(Vec::new()));
+    }
+    value
+}
+
+fn check(value: &Value) -> (Node, Validator) {
+    let mut validator = Validator { errors: Vec::new(), dropped: Vec::new() };
+    let document = match validator.node(value, "") {
+        Some((document, _)) if document.kind == "doc" => document,
+        Some(_) => {
+            validator.error("", "Documents must have the type `doc`.");
+            Node::new("doc")
+        }
+        None => Node::new("doc"),
+    };
+    (document, validator)
+}
+
+/// Checks that a document can be opened by the editor, returning it in normal form.
+/// Every problem is reported with its location in the document.
+pub fn validate(value: &Value) -> Result<Value, Vec<SchemaError>> {
+    let (document, validator) = check(value);
+    if !validator.errors.is_empty() {
+        return Err(validator.errors);
+    }
+    Ok(to_value(document))
+}
+
+/// A document made valid by [`repair`].
+pub struct Repaired {
+    /// The repaired document in normal form.
+    pub content: Value,
+
+    /// The problems that were fixed by removing part of the document.
+    pub errors: Vec<SchemaError>,
+
+    /// The attributes that were removed because the schema does not know them.
+    pub dropped: Vec<SchemaError>,
+}
+
+/// Makes a document valid by removing anything the editor cannot open.
+/// Returns the repaired document and what was removed.
+pub fn repair(value: &Value) -> Repaired {
+    let (document, validator) = check(value);
+    Repaired { content: to_value(document), errors: validator.errors, dropped: validator.dropped }
+}
+
+/// Validates content sent by a client, see [`validate`].
+/// Unknown attributes are removed rather than rejected, as the editor does when it opens a document.
+/// The error lists every problem under the `errors` extension, each with a `path` and `message`.
+pub fn validate_content(value: &Value) -> async_graphql::Result<Value> {
+    validate(value).map_err(|errors| {
+        let message = format!("Invalid document: {}", errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(" "));
+        let details = errors.iter()
+            .map(|error| serde_json::json!({"path": error.path, "message": error.message}))
+            .collect::<Vec<_>>();
+        async_graphql::Error::new(message).extend_with(|_, extensions| {
+            if let Ok(details) = async_graphql::Value::from_json(Value::Array(details)) {
+                extensions.set("errors", details);
+            }
+        })
+    })
+}
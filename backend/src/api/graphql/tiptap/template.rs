@@ -0,0 +1,39 @@
+use crate::api::graphql::tiptap::Node;
+
+/// Replaces placeholders like `{{date}}` in text with their values.
+/// Whitespace inside the braces is ignored, and unknown placeholders are left as they are.
+pub fn fill(text: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        let name = rest[start + 2..end].trim();
+        filled.push_str(&rest[..start]);
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => filled.push_str(value),
+            None => filled.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Replaces placeholders in the text of a document and its image descriptions.
+/// Placeholders are only found within a single text node, so they cannot be partly formatted.
+pub fn fill_document(node: &mut Node, values: &[(&str, &str)]) {
+    if let Some(text) = &node.text {
+        node.text = Some(fill(text, values));
+    }
+    if node.kind == "image" {
+        if let Some(alt) = node.str_attr("alt") {
+            let alt = fill(alt, values);
+            node.attrs.insert("alt".to_string(), alt.into());
+        }
+    }
+    for child in &mut node.content {
+        fill_document(child, values);
+    }
+}
@@ -0,0 +1,37 @@
+use std::env;
+use std::time::Duration;
+use rocket::error;
+use sqlx::{PgPool, query};
+
+/// How often entries older than the retention period are purged from the trash.
+const PURGE_INTERVAL: Duration = Duration::from_hours(1);
+
+/// How many days deleted notes, to-dos and subjects stay in the trash before they are deleted permanently.
+#[derive(Copy, Clone)]
+pub struct Retention(pub u16);
+
+impl Retention {
+    /// Reads the retention period from `TRASH_RETENTION_DAYS`, 30 days by default.
+    pub fn from_env() -> Self {
+        Self(env::var("TRASH_RETENTION_DAYS").map_or(30, |days| days.parse().expect("TRASH_RETENTION_DAYS must be a number of days")))
+    }
+}
+
+/// Permanently deletes everything that has been in the trash for longer than the retention period.
+/// Returns the number of trash entries deleted.
+pub async fn purge(pool: &PgPool, retention: Retention) -> sqlx::Result<u64> {
+    Ok(query!(/* language=postgresql */ "DELETE FROM trash WHERE deleted_at < NOW() - make_interval(days => $1);", i32::from(retention.0))
+        .execute(pool).await?
+        .rows_affected())
+}
+
+/// Purges the trash every [`PURGE_INTERVAL`], forever.
+pub async fn purge_periodically(pool: PgPool, retention: Retention) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(error) = purge(&pool, retention).await {
+            error!("Failed to purge the trash: {error}");
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::future::ready;
+use std::io;
+use std::pin::{pin, Pin};
+use async_graphql::{Data, Error};
+use async_graphql::http::{WebSocket, WebSocketProtocols, WsMessage};
+use futures_util::{SinkExt, StreamExt};
+use jsonwebtoken::DecodingKey;
+use rocket::{async_trait, Request, Response};
+use rocket::data::{IoHandler, IoStream};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use sqlx::PgPool;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use crate::api::graphql::Schema;
+use crate::auth::User;
+
+/// A request to upgrade the connection to a WebSocket speaking one of the GraphQL WebSocket protocols.
+pub struct Upgrade {
+    key: String,
+    protocol: WebSocketProtocols,
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for Upgrade {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        if !headers.get("Upgrade").any(|upgrade| upgrade.eq_ignore_ascii_case("websocket")) {
+            return Outcome::Error((Status::UpgradeRequired, ()));
+        }
+        let Some(key) = headers.get_one("Sec-WebSocket-Key") else {
+            return Outcome::Error((Status::BadRequest, ()));
+        };
+        // Clients list the protocols they support in order of preference
+        let Some(protocol) = headers.get("Sec-WebSocket-Protocol")
+            .flat_map(|protocols| protocols.split(','))
+            .find_map(|protocol| protocol.trim().parse().ok()) else {
+            return Outcome::Error((Status::BadRequest, ()));
+        };
+        Outcome::Success(Self {
+            key: key.to_string(),
+            protocol,
+        })
+    }
+}
+
+/// A GraphQL WebSocket connection.
+/// Users are authenticated by the Authorization header, or if that is missing by the `Authorization` or `token` field of the `connection_init` payload.
+pub struct Connection {
+    pub upgrade: Upgrade,
+    pub schema: Schema,
+    pub user: Option<User>,
+    pub pool: PgPool,
+    pub key: DecodingKey,
+}
+
+impl<'r> Responder<'r, 'static> for Connection {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .raw_header("Sec-WebSocket-Accept", derive_accept_key(self.upgrade.key.as_bytes()))
+            .raw_header("Sec-WebSocket-Protocol", self.upgrade.protocol.sec_websocket_protocol())
+            .upgrade("websocket", self)
+            .ok()
+    }
+}
+
+#[async_trait]
+impl IoHandler for Connection {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> io::Result<()> {
+        let Self { upgrade, schema, user, pool, key } = *Pin::into_inner(self);
+        let (mut sink, stream) = WebSocketStream::from_raw_socket(io, Role::Server, None).await.split();
+        let messages = stream
+            .take_while(|message| ready(matches!(message, Ok(message) if !message.is_close())))
+            .filter_map(|message| ready(match message {
+                Ok(Message::Text(text)) => Some(text.into_bytes()),
+                Ok(Message::Binary(bytes)) => Some(bytes),
+                _ => None,
+            }));
+        let mut replies = pin!(WebSocket::new(schema, messages, upgrade.protocol)
+            .on_connection_init(move |payload| async move {
+                let user = match user {
+                    Some(user) => Some(user),
+                    None => match ["Authorization", "authorization", "token"].iter().find_map(|field| payload.get(field)?.as_str()) {
+                        Some(token) => Some(User::from_token(&pool, &key, token).await?.ok_or(Error::new("Invalid token"))?),
+                        None => None,
+                    },
+                };
+                let mut data = Data::default();
+                data.insert(user);
+                Ok(data)
+            }));
+        while let Some(reply) = replies.next().await {
+            match reply {
+                WsMessage::Text(text) => sink.send(Message::Text(text)).await.map_err(io::Error::other)?,
+                WsMessage::Close(code, reason) => {
+                    sink.send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::from(code),
+                        reason: reason.into(),
+                    }))).await.map_err(io::Error::other)?;
+                    break;
+                }
+            }
+        }
+        // The client may already have gone
+        let _ = sink.close().await;
+        Ok(())
+    }
+}
@@ -98,6 +98,7 @@ pub struct User {
     pub account_type: AccountType,
     pub created_at: NaiveDateTime,
     pub require_password_change: bool,
+    pub timezone: Option<String>,
 }
 
 impl User {
@@ -118,7 +119,7 @@ impl User {
             return Ok(Outcome::Error((Status::Unauthorized, anyhow!("Invalid session"))));
         };
         Ok(Outcome::Success(query_as!(Self, /* language=postgresql */ "
-            SELECT id, username, name, email, account_type, created_at, require_password_change
+            SELECT id, username, name, email, account_type, created_at, require_password_change, timezone
             FROM users
             WHERE id = $1
             LIMIT 1;
@@ -127,6 +128,29 @@ impl User {
     }
 }
 
+impl User {
+    /// Authenticates a bearer token sent outside of the Authorization header, such as when a WebSocket connection is initialised.
+    /// Returns `None` if the token or its session is invalid.
+    pub async fn from_token(db: &PgPool, key: &DecodingKey, token: &str) -> anyhow::Result<Option<Self>> {
+        let token = token.trim();
+        let token = token.strip_prefix("Bearer ").unwrap_or(token);
+        let Ok(data) = decode::<Token>(token.trim(), key, &Validation::new(Algorithm::HS512)) else {
+            return Ok(None);
+        };
+        let Some(user_id) = query!(/* language=postgresql */ "UPDATE sessions SET last_seen = NOW() WHERE id = $1 RETURNING user_id;", data.claims.session)
+            .fetch_optional(db).await? else {
+            return Ok(None);
+        };
+        Ok(Some(query_as!(Self, /* language=postgresql */ "
+            SELECT id, username, name, email, account_type, created_at, require_password_change, timezone
+            FROM users
+            WHERE id = $1
+            LIMIT 1;
+            ", user_id.user_id)
+            .fetch_one(db).await?))
+    }
+}
+
 impl PartialEq<Self> for User {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -225,6 +249,7 @@ async fn login(login: Json<LoginPayload>, db: &State<PgPool>, secret_key: &State
             account_type: user.account_type.into(),
             created_at: user.created_at,
             require_password_change: user.require_password_change,
+            timezone: user.timezone,
         },
     }))
 }
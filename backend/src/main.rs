@@ -5,10 +5,14 @@ mod auth;
 mod api;
 
 use std::env;
+use std::sync::Arc;
 use jsonwebtoken::{DecodingKey, EncodingKey};
+use rocket::fairing::AdHoc;
 use rocket_cors::{AllowedOrigins, Cors, CorsOptions};
 use sqlx::PgPool;
+use crate::api::graphql::collaboration::Collaboration;
 use crate::api::graphql::create_schema;
+use crate::api::graphql::trash::Retention;
 
 #[allow(clippy::unwrap_used)]
 #[rocket::launch]
@@ -16,8 +20,16 @@ async fn rocket() -> _ {
     let db = PgPool::connect(&env::var("DATABASE_URL").unwrap()).await.unwrap();
     sqlx::migrate!().run(&db).await.unwrap();
 
+    // `sapiprudentia repair-notes [--dry-run]` fixes notes saved before their content was validated
+    #[cfg(feature = "api_graphql")]
+    if env::args().nth(1).as_deref() == Some("repair-notes") {
+        let dry_run = env::args().any(|arg| arg == "--dry-run");
+        api::graphql::repair::repair_notes(&db, dry_run).await.unwrap();
+        std::process::exit(0);
+    }
+
     #[allow(unused_mut)]
-    let mut rocket = rocket::build()
+    let mut rocket = rocket::custom(rocket::Config::figment().join(("limits.graphql", "16 MiB")))
         .mount("/auth", &**auth::ROUTES)
         .manage(db.clone())
         .manage(EncodingKey::from_base64_secret(&env::var("SECRET_KEY").unwrap()).unwrap())
@@ -27,9 +39,19 @@ async fn rocket() -> _ {
             .allow_credentials(true)
         ).unwrap());
     #[cfg(feature = "api_graphql")] {
+        let collaboration = Arc::new(Collaboration::default());
+        tokio::spawn(Arc::clone(&collaboration).compact_periodically(db.clone()));
+        tokio::spawn(Arc::clone(&collaboration).close_rewritten(db.clone()));
+        let retention = Retention::from_env();
+        tokio::spawn(api::graphql::trash::purge_periodically(db.clone(), retention));
         rocket = rocket
-            .manage(create_schema(db))
-            .mount("/graphql", &**api::graphql::ROUTES);
+            .manage(create_schema(db.clone(), Arc::clone(&collaboration), retention))
+            .attach(AdHoc::on_shutdown("Save collaborative edits", |_| Box::pin(async move {
+                collaboration.compact(&db).await;
+            })))
+            .mount("/graphql", &**api::graphql::ROUTES)
+            .mount("/report", &**api::graphql::report::ROUTES)
+            .mount("/", &**api::graphql::export::ROUTES);
     }
     rocket
 }